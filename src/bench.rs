@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::display::DisplayConfig;
+use crate::emulator::{Emulator, RunConfig};
+use crate::input::Input;
+use crate::sdl_wrapper::SdlWrapper;
+use crate::video;
+
+pub const DEFAULT_FRAMES: u32 = 600;
+
+// The renderer the texture replaced, one fill_rect per game boy pixel
+fn present_with_rects(canvas: &mut Canvas<Window>, frame: &[u8]) {
+    canvas.clear();
+    for (i, rgba) in frame.chunks_exact(video::BYTES_PER_PIXEL).enumerate() {
+        canvas.set_draw_color(sdl2::pixels::Color::RGBA(
+            rgba[0], rgba[1], rgba[2], rgba[3],
+        ));
+        let x = (i % video::SCREEN_WIDTH) * video::PIXEL_SIZE;
+        let y = (i / video::SCREEN_WIDTH) * video::PIXEL_SIZE;
+        canvas
+            .fill_rect(Rect::new(
+                x as i32,
+                y as i32,
+                video::PIXEL_SIZE as u32,
+                video::PIXEL_SIZE as u32,
+            ))
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+    canvas.present();
+}
+
+// Runs `frames` frames from power on and hands every finished one to present
+fn measure(path_to_rom: &String, frames: u32, mut present: impl FnMut(&[u8])) -> Duration {
+    let mut emulator = Emulator::new(RunConfig::default());
+    emulator
+        .load_rom(path_to_rom)
+        .unwrap_or_else(|err| panic!("{path_to_rom}: {err}"));
+    let input = Input::new();
+    let mut clock_t = 0;
+    let started = Instant::now();
+    for _ in 0..frames {
+        crate::run_frame(&mut emulator, &input, None, &mut clock_t);
+        if let Some(frame) = emulator.frame() {
+            present(frame);
+        }
+    }
+    started.elapsed()
+}
+
+fn report(name: &str, frames: u32, elapsed: Duration) {
+    println!(
+        "{name:>9}: {frames} frames in {:.2}s, {:.1} fps",
+        elapsed.as_secs_f64(),
+        frames as f64 / elapsed.as_secs_f64()
+    );
+}
+
+// bench <rom> [frames], unthrottled frame rate without rendering, with the old per pixel
+// renderer and with the streaming texture
+pub fn run(args: &[String]) {
+    let path_to_rom = args.first().expect("missing rom path");
+    let frames = args.get(1).map_or(DEFAULT_FRAMES, |arg| {
+        arg.parse()
+            .unwrap_or_else(|_| panic!("invalid frame count: {arg}"))
+    });
+
+    report("headless", frames, measure(path_to_rom, frames, |_| {}));
+
+    let sdl = SdlWrapper::new();
+    let mut canvas = sdl.get_window_canvas(
+        "benchmark",
+        (video::SCREEN_WIDTH * video::PIXEL_SIZE) as u32,
+        (video::SCREEN_HEIGHT * video::PIXEL_SIZE) as u32,
+    );
+    let elapsed = measure(path_to_rom, frames, |frame| {
+        present_with_rects(&mut canvas, frame)
+    });
+    report("fill_rect", frames, elapsed);
+
+    let texture_creator = canvas.texture_creator();
+    let mut texture = video::create_screen_texture(
+        &texture_creator,
+        (video::SCREEN_WIDTH as u32, video::SCREEN_HEIGHT as u32),
+    );
+    let target = DisplayConfig::default().screen_rect(canvas.window().size());
+    let elapsed = measure(path_to_rom, frames, |frame| {
+        video::upload_frame(&mut texture, frame);
+        video::present_screen(&mut canvas, &texture, target);
+    });
+    report("texture", frames, elapsed);
+}
@@ -1,67 +1,353 @@
+use std::fmt;
+
 const KB: usize = 1024;
 
-#[derive(Debug, PartialEq)]
+const HEADER_END: usize = 0x150;
+const TITLE_START: usize = 0x134;
+const MANUFACTURER_START: usize = 0x13F;
+const CGB_FLAG: usize = 0x143;
+const NEW_LICENSEE_CODE: usize = 0x144;
+const SGB_FLAG: usize = 0x146;
+const CARTRIDGE_TYPE: usize = 0x147;
+const ROM_SIZE: usize = 0x148;
+const RAM_SIZE: usize = 0x149;
+const DESTINATION_CODE: usize = 0x14A;
+const OLD_LICENSEE_CODE: usize = 0x14B;
+const MASK_ROM_VERSION: usize = 0x14C;
+const HEADER_CHECKSUM: usize = 0x14D;
+const GLOBAL_CHECKSUM: usize = 0x14E;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum CartridgeType {
     RomOnly,
     Mbc1,
     Mbc2,
+    Mmm01,
     Mbc3,
     Mbc5,
-    Invalid = 1000,
+    Mbc6,
+    Mbc7,
+    PocketCamera,
+    BandaiTama5,
+    HuC3,
+    HuC1,
+}
+
+// Extra hardware on the cartridge besides the mapper itself
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+pub struct CartridgeFeatures {
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+    pub sensor: bool,
 }
 
 impl CartridgeType {
-    fn from_u32(val: u32) -> CartridgeType {
-        match val {
-            0x0 | 0x8 | 0x9 => CartridgeType::RomOnly,
-            0x1..=0x3 => CartridgeType::Mbc1,
-            0x5 | 0x6 => CartridgeType::Mbc2,
-            0xF..=0x13 => CartridgeType::Mbc3,
-            0x19..=0x1E => CartridgeType::Mbc5,
-            _ => CartridgeType::Invalid,
+    fn from_u8(val: u8) -> Option<(CartridgeType, CartridgeFeatures)> {
+        let none = CartridgeFeatures::default();
+        let ram = CartridgeFeatures { ram: true, ..none };
+        let ram_battery = CartridgeFeatures {
+            battery: true,
+            ..ram
+        };
+        let battery = CartridgeFeatures {
+            battery: true,
+            ..none
+        };
+        let rumble = CartridgeFeatures {
+            rumble: true,
+            ..none
+        };
+        let result = match val {
+            0x00 => (CartridgeType::RomOnly, none),
+            0x01 => (CartridgeType::Mbc1, none),
+            0x02 => (CartridgeType::Mbc1, ram),
+            0x03 => (CartridgeType::Mbc1, ram_battery),
+            0x05 => (CartridgeType::Mbc2, none),
+            0x06 => (CartridgeType::Mbc2, battery),
+            0x08 => (CartridgeType::RomOnly, ram),
+            0x09 => (CartridgeType::RomOnly, ram_battery),
+            0x0B => (CartridgeType::Mmm01, none),
+            0x0C => (CartridgeType::Mmm01, ram),
+            0x0D => (CartridgeType::Mmm01, ram_battery),
+            0x0F => (
+                CartridgeType::Mbc3,
+                CartridgeFeatures {
+                    timer: true,
+                    ..battery
+                },
+            ),
+            0x10 => (
+                CartridgeType::Mbc3,
+                CartridgeFeatures {
+                    timer: true,
+                    ..ram_battery
+                },
+            ),
+            0x11 => (CartridgeType::Mbc3, none),
+            0x12 => (CartridgeType::Mbc3, ram),
+            0x13 => (CartridgeType::Mbc3, ram_battery),
+            0x19 => (CartridgeType::Mbc5, none),
+            0x1A => (CartridgeType::Mbc5, ram),
+            0x1B => (CartridgeType::Mbc5, ram_battery),
+            0x1C => (CartridgeType::Mbc5, rumble),
+            0x1D => (
+                CartridgeType::Mbc5,
+                CartridgeFeatures {
+                    ram: true,
+                    ..rumble
+                },
+            ),
+            0x1E => (
+                CartridgeType::Mbc5,
+                CartridgeFeatures {
+                    rumble: true,
+                    ..ram_battery
+                },
+            ),
+            0x20 => (CartridgeType::Mbc6, none),
+            0x22 => (
+                CartridgeType::Mbc7,
+                CartridgeFeatures {
+                    rumble: true,
+                    sensor: true,
+                    ..ram_battery
+                },
+            ),
+            0xFC => (CartridgeType::PocketCamera, none),
+            0xFD => (CartridgeType::BandaiTama5, none),
+            0xFE => (CartridgeType::HuC3, none),
+            0xFF => (CartridgeType::HuC1, ram_battery),
+            _ => return None,
+        };
+        Some(result)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CgbSupport {
+    // DMG only, the CGB runs it in compatibility mode
+    None,
+    // Works on DMG but uses CGB features when available
+    Enhanced,
+    Only,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Licensee {
+    Old(u8),
+    // Two ASCII characters at 0x144-0x145, used when the old code is 0x33
+    New(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Destination {
+    Japan,
+    Overseas,
+    Unknown(u8),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CartridgeError {
+    TooSmall(usize),
+    UnknownCartridgeType(u8),
+    UnknownRomSize(u8),
+    UnknownRamSize(u8),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CartridgeError::TooSmall(len) => write!(
+                f,
+                "rom is {len} bytes, too small to hold a cartridge header ({HEADER_END} bytes)"
+            ),
+            CartridgeError::UnknownCartridgeType(x) => write!(f, "unknown cartridge type {x:#04x}"),
+            CartridgeError::UnknownRomSize(x) => write!(f, "unknown rom size code {x:#04x}"),
+            CartridgeError::UnknownRamSize(x) => write!(f, "unknown ram size code {x:#04x}"),
         }
     }
 }
 
+impl std::error::Error for CartridgeError {}
+
 pub struct Cartridge {
+    pub title: String,
+    pub manufacturer_code: Option<String>,
+    pub cgb_support: CgbSupport,
+    pub sgb_support: bool,
+    pub licensee: Licensee,
     pub cartidge_type: CartridgeType,
+    pub cartridge_type_code: u8,
+    pub features: CartridgeFeatures,
     pub rom_size: usize,
     pub ram_size: usize,
+    pub destination: Destination,
+    pub mask_rom_version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+    // Sum of the 16 title bytes, how the CGB boot rom recognises DMG games
+    pub title_checksum: u8,
+    computed_header_checksum: u8,
+    computed_global_checksum: u16,
 }
 
 impl Cartridge {
-    pub fn new(data: &[u8]) -> Self {
-        let mut cartridge = Cartridge {
-            cartidge_type: CartridgeType::Invalid,
-            rom_size: 0,
-            ram_size: 0,
-        };
-        cartridge.cartidge_type = CartridgeType::from_u32(data[0x147] as u32);
-
-        cartridge.rom_size = match data[0x148] {
-            0 => 32 * KB,
-            1 => 64 * KB,
-            2 => 128 * KB,
-            3 => 256 * KB,
-            4 => 512 * KB,
-            5 => KB * KB,
-            6 => KB * KB * 2,
-            x => {
-                println!("unknown rom_bank type {x}");
-                data.len()
-            }
+    pub fn new(data: &[u8]) -> Result<Self, CartridgeError> {
+        if data.len() < HEADER_END {
+            return Err(CartridgeError::TooSmall(data.len()));
+        }
+
+        let cartridge_type_code = data[CARTRIDGE_TYPE];
+        let (cartidge_type, features) = CartridgeType::from_u8(cartridge_type_code)
+            .ok_or(CartridgeError::UnknownCartridgeType(cartridge_type_code))?;
+
+        let rom_size = match data[ROM_SIZE] {
+            x @ 0x00..=0x08 => (32 * KB) << x,
+            0x52 => 72 * 16 * KB,
+            0x53 => 80 * 16 * KB,
+            0x54 => 96 * 16 * KB,
+            x => return Err(CartridgeError::UnknownRomSize(x)),
         };
-        cartridge.ram_size = match data[0x149] {
-            0 => 2 * KB,
+        let ram_size = match data[RAM_SIZE] {
+            0 => 0,
             1 => 2 * KB,
             2 => 8 * KB,
             3 => 32 * KB,
             4 => 128 * KB,
-            x => {
-                println!("unknown ram_bank type {x}, defaulting to 128");
-                128 * KB
+            5 => 64 * KB,
+            x => return Err(CartridgeError::UnknownRamSize(x)),
+        };
+
+        let cgb_support = match data[CGB_FLAG] {
+            0xC0 => CgbSupport::Only,
+            x if x & 0x80 == 0x80 => CgbSupport::Enhanced,
+            _ => CgbSupport::None,
+        };
+
+        // Newer cartridges shortened the title to make room for the manufacturer code and CGB flag
+        let (title_end, manufacturer_code) = match cgb_support {
+            CgbSupport::None => (CGB_FLAG + 1, None),
+            _ => {
+                let code = &data[MANUFACTURER_START..CGB_FLAG];
+                if code.iter().all(|c| c.is_ascii_alphanumeric()) {
+                    (MANUFACTURER_START, Some(Self::ascii(code)))
+                } else {
+                    (CGB_FLAG, None)
+                }
             }
         };
-        cartridge
+
+        let licensee = match data[OLD_LICENSEE_CODE] {
+            0x33 => Licensee::New(Self::ascii(&data[NEW_LICENSEE_CODE..NEW_LICENSEE_CODE + 2])),
+            x => Licensee::Old(x),
+        };
+
+        let destination = match data[DESTINATION_CODE] {
+            0 => Destination::Japan,
+            1 => Destination::Overseas,
+            x => Destination::Unknown(x),
+        };
+
+        Ok(Cartridge {
+            title: Self::ascii(&data[TITLE_START..title_end]),
+            manufacturer_code,
+            cgb_support,
+            sgb_support: data[SGB_FLAG] == 0x03,
+            licensee,
+            cartidge_type,
+            cartridge_type_code,
+            features,
+            rom_size,
+            ram_size,
+            destination,
+            mask_rom_version: data[MASK_ROM_VERSION],
+            header_checksum: data[HEADER_CHECKSUM],
+            global_checksum: u16::from_be_bytes([data[GLOBAL_CHECKSUM], data[GLOBAL_CHECKSUM + 1]]),
+            title_checksum: data[TITLE_START..NEW_LICENSEE_CODE]
+                .iter()
+                .fold(0u8, |acc, &b| acc.wrapping_add(b)),
+            computed_header_checksum: Self::compute_header_checksum(data),
+            computed_global_checksum: Self::compute_global_checksum(data),
+        })
+    }
+
+    // Checked by the boot rom, a mismatch locks up real hardware
+    pub fn compute_header_checksum(data: &[u8]) -> u8 {
+        data[TITLE_START..HEADER_CHECKSUM]
+            .iter()
+            .fold(0u8, |acc, &b| acc.wrapping_sub(b).wrapping_sub(1))
+    }
+
+    // Sum of every byte except the checksum itself, never verified by hardware
+    pub fn compute_global_checksum(data: &[u8]) -> u16 {
+        data.iter()
+            .enumerate()
+            .filter(|(i, _)| *i != GLOBAL_CHECKSUM && *i != GLOBAL_CHECKSUM + 1)
+            .fold(0u16, |acc, (_, &b)| acc.wrapping_add(b as u16))
+    }
+
+    pub fn header_checksum_ok(&self) -> bool {
+        self.header_checksum == self.computed_header_checksum
+    }
+
+    pub fn global_checksum_ok(&self) -> bool {
+        self.global_checksum == self.computed_global_checksum
+    }
+
+    fn ascii(bytes: &[u8]) -> String {
+        bytes
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| {
+                if c.is_ascii_graphic() || c == b' ' {
+                    c as char
+                } else {
+                    '?'
+                }
+            })
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+}
+
+impl fmt::Display for Cartridge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let checksum_status = |ok: bool| if ok { "ok" } else { "MISMATCH" };
+        writeln!(f, "Title:            {}", self.title)?;
+        if let Some(code) = &self.manufacturer_code {
+            writeln!(f, "Manufacturer:     {code}")?;
+        }
+        writeln!(
+            f,
+            "Cartridge type:   {:?} ({:#04x}) {:?}",
+            self.cartidge_type, self.cartridge_type_code, self.features
+        )?;
+        writeln!(f, "ROM size:         {}KB", self.rom_size / KB)?;
+        writeln!(f, "RAM size:         {}KB", self.ram_size / KB)?;
+        writeln!(f, "CGB support:      {:?}", self.cgb_support)?;
+        writeln!(f, "SGB support:      {}", self.sgb_support)?;
+        match &self.licensee {
+            Licensee::Old(code) => writeln!(f, "Licensee:         old {code:#04x}")?,
+            Licensee::New(code) => writeln!(f, "Licensee:         new \"{code}\"")?,
+        }
+        writeln!(f, "Destination:      {:?}", self.destination)?;
+        writeln!(f, "Mask ROM version: {}", self.mask_rom_version)?;
+        writeln!(
+            f,
+            "Header checksum:  {:#04x} ({}, computed {:#04x})",
+            self.header_checksum,
+            checksum_status(self.header_checksum_ok()),
+            self.computed_header_checksum
+        )?;
+        write!(
+            f,
+            "Global checksum:  {:#06x} ({}, computed {:#06x})",
+            self.global_checksum,
+            checksum_status(self.global_checksum_ok()),
+            self.computed_global_checksum
+        )
     }
 }
@@ -0,0 +1,119 @@
+#[cfg(test)]
+mod tests {
+    use crate::cartridge::{
+        Cartridge, CartridgeError, CartridgeType, CgbSupport, Destination, Licensee,
+    };
+
+    fn make_rom(title: &str, cartridge_type: u8, rom_size: u8, ram_size: u8) -> Vec<u8> {
+        let mut rom = vec![0; 32 * 1024];
+        rom[0x134..0x134 + title.len()].copy_from_slice(title.as_bytes());
+        rom[0x147] = cartridge_type;
+        rom[0x148] = rom_size;
+        rom[0x149] = ram_size;
+        fix_checksums(&mut rom);
+        rom
+    }
+
+    fn fix_checksums(rom: &mut [u8]) {
+        rom[0x14D] = Cartridge::compute_header_checksum(rom);
+        let global = Cartridge::compute_global_checksum(rom).to_be_bytes();
+        rom[0x14E] = global[0];
+        rom[0x14F] = global[1];
+    }
+
+    #[test]
+    fn test_parse_dmg_header() {
+        let mut rom = make_rom("TETRIS", 0x03, 0x01, 0x02);
+        rom[0x14A] = 0x01;
+        rom[0x14B] = 0x01;
+        rom[0x14C] = 0x02;
+        fix_checksums(&mut rom);
+
+        let cartridge = Cartridge::new(&rom).unwrap();
+        assert_eq!(cartridge.title, "TETRIS");
+        assert_eq!(cartridge.manufacturer_code, None);
+        assert_eq!(cartridge.cartidge_type, CartridgeType::Mbc1);
+        assert!(cartridge.features.ram && cartridge.features.battery);
+        assert_eq!(cartridge.rom_size, 64 * 1024);
+        assert_eq!(cartridge.ram_size, 8 * 1024);
+        assert_eq!(cartridge.cgb_support, CgbSupport::None);
+        assert!(!cartridge.sgb_support);
+        assert_eq!(cartridge.licensee, Licensee::Old(0x01));
+        assert_eq!(cartridge.destination, Destination::Overseas);
+        assert_eq!(cartridge.mask_rom_version, 2);
+        assert!(cartridge.header_checksum_ok());
+        assert!(cartridge.global_checksum_ok());
+    }
+
+    #[test]
+    fn test_parse_cgb_header() {
+        let mut rom = make_rom("POKEMON", 0x1B, 0x06, 0x03);
+        rom[0x13F..0x143].copy_from_slice(b"AAXE");
+        rom[0x143] = 0xC0;
+        rom[0x144..0x146].copy_from_slice(b"01");
+        rom[0x146] = 0x03;
+        rom[0x14B] = 0x33;
+        fix_checksums(&mut rom);
+
+        let cartridge = Cartridge::new(&rom).unwrap();
+        assert_eq!(cartridge.title, "POKEMON");
+        assert_eq!(cartridge.manufacturer_code, Some("AAXE".to_string()));
+        assert_eq!(cartridge.cgb_support, CgbSupport::Only);
+        assert!(cartridge.sgb_support);
+        assert_eq!(cartridge.licensee, Licensee::New("01".to_string()));
+        assert_eq!(cartridge.cartidge_type, CartridgeType::Mbc5);
+        assert_eq!(cartridge.rom_size, 2 * 1024 * 1024);
+        assert_eq!(cartridge.ram_size, 32 * 1024);
+    }
+
+    #[test]
+    fn test_all_cartridge_types() {
+        for (code, expected) in [
+            (0x00, CartridgeType::RomOnly),
+            (0x0B, CartridgeType::Mmm01),
+            (0x10, CartridgeType::Mbc3),
+            (0x20, CartridgeType::Mbc6),
+            (0x22, CartridgeType::Mbc7),
+            (0xFC, CartridgeType::PocketCamera),
+            (0xFD, CartridgeType::BandaiTama5),
+            (0xFE, CartridgeType::HuC3),
+            (0xFF, CartridgeType::HuC1),
+        ] {
+            let rom = make_rom("TYPES", code, 0, 0);
+            assert_eq!(Cartridge::new(&rom).unwrap().cartidge_type, expected);
+        }
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let mut rom = make_rom("TETRIS", 0x00, 0x00, 0x00);
+        rom[0x14D] ^= 0xFF;
+        rom[0x1000] = 0x42;
+        let cartridge = Cartridge::new(&rom).unwrap();
+        assert!(!cartridge.header_checksum_ok());
+        assert!(!cartridge.global_checksum_ok());
+    }
+
+    #[test]
+    fn test_invalid_headers() {
+        assert_eq!(
+            Cartridge::new(&[0; 0x100]).err(),
+            Some(CartridgeError::TooSmall(0x100))
+        );
+        let rom = make_rom("BAD", 0x04, 0x00, 0x00);
+        assert_eq!(
+            Cartridge::new(&rom).err(),
+            Some(CartridgeError::UnknownCartridgeType(0x04))
+        );
+        let rom = make_rom("BAD", 0x00, 0x09, 0x00);
+        assert_eq!(
+            Cartridge::new(&rom).err(),
+            Some(CartridgeError::UnknownRomSize(0x09))
+        );
+        let rom = make_rom("BAD", 0x00, 0x00, 0x06);
+        assert_eq!(
+            Cartridge::new(&rom).err(),
+            Some(CartridgeError::UnknownRamSize(0x06))
+        );
+    }
+}
@@ -0,0 +1,64 @@
+// Bounds memory use when a game calls without ever returning
+const MAX_DEPTH: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FrameKind {
+    Call,
+    Rst,
+    // Interrupt dispatch, the caller is the instruction that was interrupted
+    Interrupt,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Frame {
+    pub kind: FrameKind,
+    pub caller: u16,
+    pub caller_bank: u16,
+    pub target: u16,
+    pub target_bank: u16,
+    // SP right after the return address was pushed
+    pub sp: u16,
+}
+
+// Shadow of the real stack built from CALL/RST/interrupts and RET/RETI. Games pop return
+// addresses, jump through pushed addresses and move SP, so frames are matched by SP
+// instead of trusting every RET to pair with the last CALL.
+#[derive(Default, Clone)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+}
+
+impl CallStack {
+    pub fn push(&mut self, frame: Frame) {
+        // Anything at or below the new return address has been overwritten
+        while self.frames.last().is_some_and(|top| top.sp <= frame.sp) {
+            self.frames.pop();
+        }
+        if self.frames.len() == MAX_DEPTH {
+            self.frames.remove(0);
+        }
+        self.frames.push(frame);
+    }
+
+    // sp is the stack pointer before the return address is popped
+    pub fn ret(&mut self, sp: u16) -> Option<Frame> {
+        // Frames the game unwound by hand
+        while self.frames.last().is_some_and(|top| top.sp < sp) {
+            self.frames.pop();
+        }
+        // A RET above the top frame is a jump through a pushed address, not a return
+        if self.frames.last()?.sp == sp {
+            return self.frames.pop();
+        }
+        None
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    // Innermost frame first
+    pub fn frames(&self) -> impl Iterator<Item = &Frame> {
+        self.frames.iter().rev()
+    }
+}
@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+    use crate::cpu::call_stack::{CallStack, Frame, FrameKind};
+
+    fn frame(caller: u16, sp: u16) -> Frame {
+        Frame {
+            kind: FrameKind::Call,
+            caller,
+            caller_bank: 0,
+            target: 0x0200,
+            target_bank: 0,
+            sp,
+        }
+    }
+
+    fn callers(stack: &CallStack) -> Vec<u16> {
+        stack.frames().map(|frame| frame.caller).collect()
+    }
+
+    #[test]
+    fn test_call_and_return() {
+        let mut stack = CallStack::default();
+        stack.push(frame(0x0150, 0xfffc));
+        stack.push(frame(0x0210, 0xfffa));
+        assert_eq!(callers(&stack), vec![0x0210, 0x0150]);
+        assert_eq!(stack.ret(0xfffa).map(|f| f.caller), Some(0x0210));
+        assert_eq!(stack.ret(0xfffc).map(|f| f.caller), Some(0x0150));
+        assert_eq!(stack.ret(0xfffe), None);
+    }
+
+    #[test]
+    fn test_jump_through_pushed_address() {
+        let mut stack = CallStack::default();
+        stack.push(frame(0x0150, 0xfffc));
+        // PUSH HL, RET
+        assert_eq!(stack.ret(0xfffa), None);
+        assert_eq!(callers(&stack), vec![0x0150]);
+    }
+
+    #[test]
+    fn test_manually_unwound_frames_are_dropped() {
+        let mut stack = CallStack::default();
+        stack.push(frame(0x0150, 0xfffc));
+        stack.push(frame(0x0210, 0xfffa));
+        // The inner function popped its return address and jumped away
+        assert_eq!(stack.ret(0xfffc).map(|f| f.caller), Some(0x0150));
+        assert_eq!(callers(&stack), Vec::<u16>::new());
+
+        stack.push(frame(0x0150, 0xfffc));
+        stack.push(frame(0x0210, 0xfffa));
+        // Same stack slot reused after SP was reset
+        stack.push(frame(0x0300, 0xfffc));
+        assert_eq!(callers(&stack), vec![0x0300]);
+    }
+
+    #[test]
+    fn test_depth_is_bounded() {
+        let mut stack = CallStack::default();
+        for i in 0..1000u16 {
+            stack.push(frame(i, 0xfffe - i * 2));
+        }
+        assert_eq!(stack.frames().count(), 256);
+        assert_eq!(stack.frames().next().map(|f| f.caller), Some(999));
+    }
+}
@@ -33,14 +33,9 @@ mod tests {
                 mem: Memory::new(),
                 rand: rand::thread_rng(),
             };
-            t.mem.load(
-                vec![0; 32768],
-                &Cartridge {
-                    cartidge_type: crate::cartridge::CartridgeType::RomOnly,
-                    rom_size: 32768,
-                    ram_size: 0,
-                },
-            );
+            let rom = vec![0; 32768];
+            let cartridge = Cartridge::new(&rom).unwrap();
+            t.mem.load(rom, &cartridge).unwrap();
             t
         }
 
@@ -673,4 +668,115 @@ mod tests {
         assert_eq!(0x69, t.mem.read_byte(INTERNAL_RAM + 100));
         assert_eq!(t.cpu.HL, INTERNAL_RAM + 99);
     }
+
+    #[test]
+    fn test_illegal_opcode_locks_up() {
+        for opcode in [
+            0xd3, 0xdb, 0xdd, 0xe3, 0xe4, 0xeb, 0xec, 0xed, 0xf4, 0xfc, 0xfd,
+        ] {
+            let mut t = Tester::new();
+            t.run(opcode);
+            assert_eq!(t.cpu.locked_up(), Some((opcode, 0xc000)));
+            assert_eq!(t.cpu.PC(), 0xc000);
+
+            // Pending interrupts can't wake a locked up cpu
+            t.cpu.IME = true;
+            t.mem.write_byte(0xffff, 0x1f);
+            t.mem.write_byte(0xff0f, 0x1f);
+            t.cpu.tick(&mut t.mem);
+            assert_eq!(t.cpu.PC(), 0xc000);
+            assert_eq!(t.cpu.get_clock_t(), 4);
+
+            t.cpu.reset();
+            assert_eq!(t.cpu.locked_up(), None);
+        }
+    }
+
+    #[test]
+    fn test_stop_enters_stop_mode() {
+        let mut t = Tester::new();
+        for _ in 0..4 {
+            t.mem.update_timers(250);
+        }
+        assert_ne!(t.mem.read_byte(0xff04), 0);
+
+        t.run(0x10);
+        assert!(t.cpu.is_stopped());
+        assert_eq!(t.cpu.PC(), 0xc002);
+        assert_eq!(t.mem.read_byte(0xff04), 0);
+
+        t.cpu.tick(&mut t.mem);
+        assert!(t.cpu.is_stopped());
+        assert_eq!(t.cpu.PC(), 0xc002);
+
+        // Pressing a button on a selected line wakes the cpu up
+        t.mem.write_byte(0xff00, 0x10);
+        t.mem.set_pressed_buttons(memory::JOYPAD_A);
+        assert_eq!(t.mem.read_byte(0xff0f) & 0x10, 0x10);
+        t.cpu.tick(&mut t.mem);
+        assert!(!t.cpu.is_stopped());
+        assert_eq!(t.cpu.PC(), 0xc003);
+    }
+
+    #[test]
+    fn test_stop_with_pending_interrupt() {
+        let mut t = Tester::new();
+        t.mem.write_byte(0xffff, 0x04);
+        t.mem.write_byte(0xff0f, 0x04);
+        t.run(0x10);
+        assert!(t.cpu.is_stopped());
+        assert_eq!(t.cpu.PC(), 0xc001);
+    }
+
+    #[test]
+    fn test_stop_with_button_held() {
+        let mut t = Tester::new();
+        t.mem.write_byte(0xff00, 0x20);
+        t.mem.set_pressed_buttons(memory::JOYPAD_LEFT);
+        for _ in 0..4 {
+            t.mem.update_timers(250);
+        }
+
+        t.run(0x10);
+        assert!(!t.cpu.is_stopped());
+        assert!(t.cpu.HALT);
+        assert_eq!(t.cpu.PC(), 0xc002);
+        assert_ne!(t.mem.read_byte(0xff04), 0);
+
+        let mut t = Tester::new();
+        t.mem.write_byte(0xff00, 0x20);
+        t.mem.set_pressed_buttons(memory::JOYPAD_LEFT);
+        t.mem.write_byte(0xffff, 0x01);
+        t.mem.write_byte(0xff0f, 0x01);
+        t.run(0x10);
+        assert!(!t.cpu.is_stopped());
+        assert!(!t.cpu.HALT);
+        assert_eq!(t.cpu.PC(), 0xc001);
+    }
+
+    #[test]
+    fn test_stop_speed_switch() {
+        let mut t = Tester::new();
+        let mut rom = vec![0; 32768];
+        rom[0x143] = 0x80;
+        let cartridge = Cartridge::new(&rom).unwrap();
+        t.mem.load(rom, &cartridge).unwrap();
+        t.mem.write_byte(0xffff, 0);
+        assert_eq!(t.mem.read_byte(0xff4d), 0x7e);
+
+        t.mem.write_byte(0xff4d, 0x01);
+        assert!(t.mem.speed_switch_armed());
+        t.run(0x10);
+        assert!(!t.cpu.is_stopped());
+        assert!(t.mem.double_speed());
+        assert_eq!(t.mem.read_byte(0xff4d), 0xfe);
+        assert_eq!(t.cpu.PC(), 0xc002);
+
+        // Without the CGB flag KEY1 doesn't exist
+        let mut t = Tester::new();
+        t.mem.write_byte(0xff4d, 0x01);
+        t.run(0x10);
+        assert!(t.cpu.is_stopped());
+        assert!(!t.mem.double_speed());
+    }
 }
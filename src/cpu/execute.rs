@@ -75,7 +75,10 @@ impl Cpu {
                 self.set_flag(Flag::C, (val & 1) == 1);
                 Instruction::Ok(opcode, 1, 4, "RRCA")
             }
-            0x10 => Instruction::Ok(opcode, 2, 4, "STOP"), // TODO: figure out what stop does
+            0x10 => {
+                let length = self.stop(mem);
+                Instruction::Ok(opcode, length, 4, "STOP")
+            }
             0x11 => {
                 self.DE = self.get_nn(mem);
                 Instruction::Ok(opcode, 3, 12, "LD DE,d16")
@@ -971,9 +974,7 @@ impl Cpu {
                 }
                 Instruction::Ok(opcode, 3, 12, "CALL C, a16")
             }
-            0xdd => {
-                panic!("invalid opcode {}", opcode);
-            }
+            0xdd => Instruction::Invalid(opcode),
             0xde => {
                 self.sbc_a(self.get_n(mem));
                 Instruction::Ok(opcode, 2, 8, "SBC A, d8")
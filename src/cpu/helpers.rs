@@ -1,7 +1,13 @@
 use crate::memory::{Memory, MemoryType};
 
+use super::call_stack::{Frame, FrameKind};
 use super::{Cpu, Flag, Register};
 
+// The cpu sits idle while the clock switches speed
+const SPEED_SWITCH_CYCLES: u32 = 8200;
+// A speed switch without pending interrupts also enters HALT for this long
+const SPEED_SWITCH_HALT_CYCLES: u32 = 0x20000;
+
 #[allow(dead_code)]
 impl Cpu {
     pub fn get_n(&self, mem: &mut Memory) -> u8 {
@@ -195,6 +201,7 @@ impl Cpu {
     }
 
     pub fn ret(&mut self, mem: &mut Memory) {
+        self.call_stack.ret(self.SP);
         self.PC = self.pop_sp(mem);
         if self.in_interrupt {
             self.in_interrupt = false;
@@ -202,21 +209,69 @@ impl Cpu {
     }
 
     pub fn call_a16(&mut self, mem: &mut Memory) {
+        let caller = self.PC;
         self.push_sp(mem, self.PC + 3);
         self.PC = self.get_nn(mem);
+        self.enter_frame(FrameKind::Call, caller, mem);
     }
 
     pub fn rst(&mut self, mem: &mut Memory, addr: u16) {
+        let caller = self.PC;
         self.push_sp(mem, self.PC + 1);
         self.PC = addr;
         self.IME = false;
         self.HALT = false;
+        self.enter_frame(FrameKind::Rst, caller, mem);
     }
     pub fn rst_interrupt(&mut self, mem: &mut Memory, addr: u16) {
+        let caller = self.PC;
         self.push_sp(mem, self.PC);
         self.PC = addr;
         self.IME = false;
         self.HALT = false;
+        self.enter_frame(FrameKind::Interrupt, caller, mem);
+    }
+    // Called once PC points at the target and the return address is on the stack
+    fn enter_frame(&mut self, kind: FrameKind, caller: u16, mem: &Memory) {
+        self.call_stack.push(Frame {
+            kind,
+            caller,
+            caller_bank: mem.bank_at(caller),
+            target: self.PC,
+            target_bank: mem.bank_at(self.PC),
+            sp: self.SP,
+        });
+    }
+
+    // Returns the instruction length, which depends on buttons and pending interrupts
+    // https://gbdev.io/pandocs/Reducing_Power_Consumption.html#using-the-stop-instruction
+    pub fn stop(&mut self, mem: &mut Memory) -> u16 {
+        let interrupt_pending = mem.read_byte(0xFFFF) & mem.read_byte(0xFF0F) & 0x1F != 0;
+        if mem.joypad_line_low() {
+            if interrupt_pending {
+                return 1;
+            }
+            self.HALT = true;
+            self.halt_timeout = 0;
+            self.entered_halt_without_IME = !self.IME;
+            return 2;
+        }
+
+        mem.write_byte(0xFF04, 0); // DIV
+        if !mem.speed_switch_armed() {
+            self.STOP = true;
+            return if interrupt_pending { 1 } else { 2 };
+        }
+
+        mem.switch_speed();
+        if interrupt_pending {
+            // Hardware glitches non-deterministically when IME is set, behave as if it wasn't
+            self.stall_cycles = SPEED_SWITCH_CYCLES;
+            return 1;
+        }
+        self.HALT = true;
+        self.halt_timeout = SPEED_SWITCH_HALT_CYCLES;
+        2
     }
 
     pub fn inc_8(&mut self, val: u8) -> u8 {
@@ -1,25 +1,30 @@
+mod call_stack;
+mod call_stack_test;
 mod cpu_test;
 mod execute;
 mod execute_cb;
 mod helpers;
 mod helpers_cb;
 
-use std::fs::OpenOptions;
-use std::io::Write;
 use std::ops::{Shl, Shr};
 
+pub use call_stack::{CallStack, FrameKind};
+
 use crate::memory::{self, Memory, MemoryType};
+use crate::savestate::StateBuffer;
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub enum Instruction {
     #[default]
     None,
+    // opcode, length, cycles, mnemonic. Traces use the disassembler instead of the mnemonic, which lacks operands
+    #[allow(dead_code)]
     Ok(u8, u16, u8, &'static str),
     Invalid(u8),
 }
 
 #[allow(non_snake_case)]
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Cpu {
     AF: u16,
     BC: u16,
@@ -31,9 +36,17 @@ pub struct Cpu {
     pub IME: bool,
     HALT: bool,
     entered_halt_without_IME: bool,
+    // Leaves HALT after this many cycles even without an interrupt, 0 waits forever
+    halt_timeout: u32,
+    STOP: bool,
+    // Cycles the cpu sits idle while switching speed
+    stall_cycles: u32,
     HALT_bug_at_operation: u128,
+    // Set by illegal opcodes, only a reset brings the cpu back
+    locked_up: Option<(u8, u16)>,
 
     in_interrupt: bool,
+    call_stack: CallStack,
     enable_IME_at_operation: u128,
     disable_IME_at_operation: u128,
     clock_m: u8,
@@ -41,12 +54,10 @@ pub struct Cpu {
 
     triggered_interruption: String,
     last_instruction: Instruction,
-    last_regs: String,
     pub operations: u128,
-    doctor_buffer: Vec<String>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 #[allow(dead_code)]
 pub enum Register {
     A,
@@ -59,6 +70,16 @@ pub enum Register {
     F,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Register16 {
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
 #[derive(PartialEq)]
 pub enum Flag {
     Z = 0x80,
@@ -85,11 +106,16 @@ impl Cpu {
         self.clock_t = 1;
         self.IME = false;
         self.HALT = false;
+        self.halt_timeout = 0;
+        self.STOP = false;
+        self.stall_cycles = 0;
+        self.locked_up = None;
+        self.HALT_bug_at_operation = u128::MAX;
         self.enable_IME_at_operation = u128::MAX;
         self.disable_IME_at_operation = u128::MAX;
         self.last_instruction = Instruction::None;
-        self.last_regs = "".to_string();
         self.triggered_interruption = "".to_string();
+        self.call_stack.clear();
     }
 
     pub fn tick(&mut self, mem: &mut memory::Memory) {
@@ -122,18 +148,19 @@ impl Cpu {
         }
         self.operations == p0
     }
-    pub fn print(&self) {
-        if self.HALT {
+    // The disassembly is decoded by the caller before the instruction runs, when its operands are still in memory
+    pub fn print(&self, disassembly: &str) {
+        if self.HALT || self.STOP {
             return;
         }
         match self.last_instruction {
             Instruction::None => {}
-            Instruction::Ok(opcode, _, _, description) => {
+            Instruction::Ok(opcode, _, _, _) => {
                 println!(
-                    "{0:010}|op:{1} {2}",
-                    description,
+                    "{0:016}|op:{1} {2}",
+                    disassembly,
                     Self::clean_hex_8(opcode),
-                    self.last_regs,
+                    self.registers_line(),
                 );
             }
             Instruction::Invalid(opcode) => {
@@ -141,27 +168,88 @@ impl Cpu {
             }
         }
     }
-    pub fn write_buffered_doctor_lines(&mut self) {
-        let mut log_file = OpenOptions::new()
-            .append(true)
-            .open("blargg_log_instr.txt")
-            .expect("cannot open file");
-
-        for x in &self.doctor_buffer {
-            log_file.write_all(x.as_bytes()).expect("!!!!");
+    #[cfg(test)]
+    pub fn is_halted(&self) -> bool {
+        self.HALT
+    }
+    pub fn is_stopped(&self) -> bool {
+        self.STOP
+    }
+    // Whether the next tick runs the instruction at PC, rather than idling in HALT or STOP
+    // or dispatching an interrupt. Leaving HALT with IME off runs it straight away.
+    pub(crate) fn executes_next(&self, mem: &Memory) -> bool {
+        if self.locked_up.is_some() || self.stall_cycles > 0 {
+            return false;
+        }
+        if self.STOP && !mem.joypad_line_low() {
+            return false;
         }
-        self.doctor_buffer.clear();
+        let pending = mem.peek(0xFFFF) & mem.peek(0xFF0F) & 0x1F != 0;
+        if pending && self.ime_for_next_tick() {
+            return false;
+        }
+        !self.HALT || pending
     }
-    pub fn write_doctor(&mut self) {
-        if self.HALT {
-            return;
+    // IME once the delayed EI or DI due at this operation took effect
+    fn ime_for_next_tick(&self) -> bool {
+        if self.enable_IME_at_operation == self.operations {
+            true
+        } else if self.disable_IME_at_operation == self.operations {
+            false
+        } else {
+            self.IME
         }
-        if let Instruction::Ok(_, _, _, _) = self.last_instruction {
-            self.doctor_buffer.push(format!("{}\n", self.last_regs));
-            if self.doctor_buffer.len() == 1000 {
-                self.write_buffered_doctor_lines();
-            }
+    }
+    // Everything that affects execution. The shadow call stack can't be rebuilt from a
+    // state, so it starts over after a load.
+    pub(crate) fn sync_state(&mut self, state: &mut StateBuffer) {
+        for register in [
+            &mut self.AF,
+            &mut self.BC,
+            &mut self.DE,
+            &mut self.HL,
+            &mut self.SP,
+            &mut self.PC,
+        ] {
+            state.u16(register);
+        }
+        for flag in [
+            &mut self.IME,
+            &mut self.HALT,
+            &mut self.entered_halt_without_IME,
+            &mut self.STOP,
+            &mut self.in_interrupt,
+        ] {
+            state.bool(flag);
         }
+        state.u32(&mut self.halt_timeout);
+        state.u32(&mut self.stall_cycles);
+        for operation in [
+            &mut self.HALT_bug_at_operation,
+            &mut self.enable_IME_at_operation,
+            &mut self.disable_IME_at_operation,
+            &mut self.operations,
+        ] {
+            state.u128(operation);
+        }
+        let mut locked_up = self.locked_up.is_some();
+        let (mut opcode, mut pc) = self.locked_up.unwrap_or_default();
+        state.bool(&mut locked_up);
+        state.u8(&mut opcode);
+        state.u16(&mut pc);
+        self.locked_up = locked_up.then_some((opcode, pc));
+        state.u8(&mut self.clock_m);
+        state.u8(&mut self.clock_t);
+        if state.is_loading() {
+            self.call_stack.clear();
+        }
+    }
+
+    pub(crate) fn call_stack(&self) -> &CallStack {
+        &self.call_stack
+    }
+    pub fn locked_up(&self) -> Option<(u8, u16)> {
+        self.locked_up
     }
     pub fn get_clock_t(&self) -> u8 {
         self.clock_t
@@ -197,7 +285,28 @@ impl Cpu {
         Self::get_lower(self.HL)
     }
 
-    fn set_reg(&mut self, register: Register, val: u8) {
+    pub(crate) fn get_register16(&self, register: Register16) -> u16 {
+        match register {
+            Register16::AF => self.AF,
+            Register16::BC => self.BC,
+            Register16::DE => self.DE,
+            Register16::HL => self.HL,
+            Register16::SP => self.SP,
+            Register16::PC => self.PC,
+        }
+    }
+    pub(crate) fn set_register16(&mut self, register: Register16, val: u16) {
+        match register {
+            Register16::AF => self.AF = val & 0xFFF0,
+            Register16::BC => self.BC = val,
+            Register16::DE => self.DE = val,
+            Register16::HL => self.HL = val,
+            Register16::SP => self.SP = val,
+            Register16::PC => self.PC = val,
+        }
+    }
+
+    pub(crate) fn set_reg(&mut self, register: Register, val: u8) {
         match register {
             Register::A => self.set_a(val),
             Register::F => self.set_f(val),
@@ -209,7 +318,7 @@ impl Cpu {
             Register::L => self.set_l(val),
         }
     }
-    fn get_reg(&self, register: Register) -> u8 {
+    pub(crate) fn get_reg(&self, register: Register) -> u8 {
         match register {
             Register::A => self.get_a(),
             Register::F => self.get_f(),
@@ -282,8 +391,33 @@ impl Cpu {
     }
 
     fn fetch_decode(&mut self, mem: &mut memory::Memory) {
-        self.check_interrupt_status(mem);
+        if self.locked_up.is_some() || self.stall_cycles > 0 {
+            // Time still passes so the rest of the hardware keeps running
+            self.stall_cycles = self.stall_cycles.saturating_sub(4);
+            self.reset_clock();
+            self.add_clock(4);
+            return;
+        }
+        if self.STOP {
+            if !mem.joypad_line_low() {
+                self.reset_clock();
+                self.add_clock(4);
+                return;
+            }
+            self.STOP = false;
+        }
+        // Dispatching takes a tick of its own, the handler's first instruction runs in the
+        // next one
+        if self.check_interrupt_status(mem) {
+            self.reset_clock();
+            self.add_clock(20);
+            return;
+        }
         if self.HALT {
+            if self.halt_timeout > 0 {
+                self.halt_timeout = self.halt_timeout.saturating_sub(4);
+                self.HALT = self.halt_timeout > 0;
+            }
             // Time still needs to pass even when halted so timers can tick
             self.reset_clock();
             self.add_clock(4);
@@ -291,11 +425,10 @@ impl Cpu {
         }
 
         let opcode = mem.read_byte(self.PC);
-        // self.last_regs = self.registers_doctor_str(mem);
 
         // --- NEW HALT BUG LOGIC ---
         if self.operations == self.HALT_bug_at_operation {
-            self.HALT_bug_at_operation = 0;
+            self.HALT_bug_at_operation = u128::MAX;
             // Shift PC back by 1.
             // This tricks `execute`'s `mem.read_byte(self.PC + 1)` into reading the opcode ITSELF as the first operand!
             self.PC = self.PC.wrapping_sub(1);
@@ -318,7 +451,9 @@ impl Cpu {
                 self.PC = self.PC.wrapping_add(length);
             }
             Instruction::Invalid(opcode) => {
-                println!("invalid opcode {}", Self::clean_hex_8(opcode))
+                self.locked_up = Some((opcode, self.PC));
+                self.reset_clock();
+                self.add_clock(4);
             }
         }
         self.operations += 1;
@@ -334,10 +469,12 @@ impl Cpu {
         } else {
             // Normal halt or IME=0 with nothing pending
             self.HALT = true;
+            self.halt_timeout = 0;
             self.entered_halt_without_IME = !self.IME;
         }
     }
-    fn check_interrupt_status(&mut self, mem: &mut memory::Memory) {
+    // True when an interrupt was dispatched
+    fn check_interrupt_status(&mut self, mem: &mut memory::Memory) -> bool {
         self.triggered_interruption = "".to_string();
 
         // 1. Handle delayed IME instructions (EI / DI)
@@ -361,13 +498,13 @@ impl Cpu {
                 self.HALT = false;
             } else {
                 // Stay halted. Do not process interrupts or execute instructions.
-                return;
+                return false;
             }
         }
 
         // 3. Check if we actually service the interrupt by jumping to the ISR
         if !self.IME || to_fire == 0 {
-            return;
+            return false;
         }
 
         // 4. Service the highest priority interrupt
@@ -394,8 +531,9 @@ impl Cpu {
                 // println!("Interrupt triggered: {}", self.triggered_interruption);
             }
 
-            return; // Only service ONE interrupt per tick
+            return true; // Only service ONE interrupt per tick
         }
+        false
     }
     fn clean_hex_8(v: u8) -> String {
         format!("{0:#04X}", v).replace("0x", "")
@@ -424,26 +562,20 @@ impl Cpu {
         s
     }
 
-    //A:00 F:11 B:22 C:33 D:44 E:55 H:66 L:77 SP:8888 PC:9999 PCMEM:AA,BB,CC,DD
-    fn registers_doctor_str(&mut self, mem: &memory::Memory) -> String {
-        let mut s;
-        s = format!("A:{0}", Self::clean_hex_8(self.get_a()));
-        s = format!("{s} F:{0}", Self::clean_hex_8(self.get_f()));
-        s = format!("{s} B:{0}", Self::clean_hex_8(self.get_b()));
-        s = format!("{s} C:{0}", Self::clean_hex_8(self.get_c()));
-        s = format!("{s} D:{0}", Self::clean_hex_8(self.get_d()));
-        s = format!("{s} E:{0}", Self::clean_hex_8(self.get_e()));
-        s = format!("{s} H:{0}", Self::clean_hex_8(self.get_h()));
-        s = format!("{s} L:{0}", Self::clean_hex_8(self.get_l()));
-        s = format!("{s} SP:{0}", Self::clean_hex_16(self.SP));
-        s = format!("{s} PC:{0}", Self::clean_hex_16(self.PC));
-        s = format!(
-            "{s} PCMEM:{0},{1},{2},{3}",
-            Self::clean_hex_8(mem.read_byte(self.PC)),
-            Self::clean_hex_8(mem.read_byte(self.PC + 1)),
-            Self::clean_hex_8(mem.read_byte(self.PC + 2)),
-            Self::clean_hex_8(mem.read_byte(self.PC + 3))
-        );
-        s
+    //A:00 F:11 B:22 C:33 D:44 E:55 H:66 L:77 SP:8888 PC:9999, the tracer adds PCMEM
+    fn registers_line(&self) -> String {
+        format!(
+            "A:{} F:{} B:{} C:{} D:{} E:{} H:{} L:{} SP:{} PC:{}",
+            Self::clean_hex_8(self.get_a()),
+            Self::clean_hex_8(self.get_f()),
+            Self::clean_hex_8(self.get_b()),
+            Self::clean_hex_8(self.get_c()),
+            Self::clean_hex_8(self.get_d()),
+            Self::clean_hex_8(self.get_e()),
+            Self::clean_hex_8(self.get_h()),
+            Self::clean_hex_8(self.get_l()),
+            Self::clean_hex_16(self.SP),
+            Self::clean_hex_16(self.PC)
+        )
     }
 }
@@ -0,0 +1,134 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::cpu::Cpu;
+use crate::memory::Memory;
+
+use super::condition::Expr;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Condition {
+    pub source: String,
+    pub expr: Expr,
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Condition, String> {
+        Ok(Condition {
+            source: text.trim().to_string(),
+            expr: Expr::parse(text)?,
+        })
+    }
+}
+
+// Breakpoint as written in the json config, e.g. `{ "at": "01:4a2f", "condition": "A == 0x3C" }`
+#[derive(Default, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BreakpointConfig {
+    // [bank:]address or a symbol name
+    pub at: String,
+    #[serde(default)]
+    pub condition: Option<String>,
+    #[serde(default)]
+    pub ignore_count: u32,
+    // Makes this a tracepoint
+    #[serde(default)]
+    pub log: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    // None matches whatever bank is mapped
+    pub bank: Option<u16>,
+    pub addr: u16,
+    pub condition: Option<Condition>,
+    // Hits to let pass before stopping
+    pub ignore_count: u32,
+    // Tracepoints print this instead of stopping, `{expr}` is replaced with the value of expr
+    pub log: Option<String>,
+    pub hits: u32,
+}
+
+pub enum Hit {
+    Miss,
+    Ignored,
+    Log(String),
+    Stop,
+}
+
+impl Breakpoint {
+    pub fn new(bank: Option<u16>, addr: u16) -> Breakpoint {
+        Breakpoint {
+            bank,
+            addr,
+            condition: None,
+            ignore_count: 0,
+            log: None,
+            hits: 0,
+        }
+    }
+
+    // Address, bank and condition only, without counting a hit
+    pub fn matches(&self, pc: u16, bank: u16, cpu: &Cpu, memory: &Memory) -> bool {
+        pc == self.addr
+            && self.bank.is_none_or(|b| b == bank)
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|condition| condition.expr.is_true(cpu, memory))
+    }
+
+    // Called once per instruction with the bank mapped at PC
+    pub fn check(&mut self, pc: u16, bank: u16, cpu: &Cpu, memory: &Memory) -> Hit {
+        if !self.matches(pc, bank, cpu, memory) {
+            return Hit::Miss;
+        }
+        self.hits += 1;
+        if self.hits <= self.ignore_count {
+            return Hit::Ignored;
+        }
+        match &self.log {
+            Some(message) => Hit::Log(Self::format_log(message, cpu, memory)),
+            None => Hit::Stop,
+        }
+    }
+
+    fn format_log(message: &str, cpu: &Cpu, memory: &Memory) -> String {
+        let mut result = String::new();
+        let mut rest = message;
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            result.push_str(&rest[..start]);
+            let source = &rest[start + 1..start + end];
+            match Expr::parse(source) {
+                Ok(expr) => result.push_str(&format!("${:X}", expr.evaluate(cpu, memory))),
+                Err(err) => result.push_str(&format!("<{err}>")),
+            }
+            rest = &rest[start + end + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bank {
+            Some(bank) => write!(f, "{bank:02X}:{:04X}", self.addr)?,
+            None => write!(f, "{:04X}", self.addr)?,
+        }
+        if let Some(condition) = &self.condition {
+            write!(f, " if {}", condition.source)?;
+        }
+        if self.ignore_count > 0 {
+            write!(f, ", ignore {}", self.ignore_count)?;
+        }
+        if let Some(message) = &self.log {
+            write!(f, ", log \"{message}\"")?;
+        }
+        write!(f, ", hit {} times", self.hits)
+    }
+}
@@ -0,0 +1,277 @@
+use crate::cpu::{Register, Register16};
+use crate::memory::{WatchCondition, WatchKind};
+use crate::symbols::SymbolTable;
+
+use super::breakpoint::Condition;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RegisterName {
+    Byte(Register),
+    Word(Register16),
+}
+
+// An address typed as a number, as bank:address or as a label from the symbol file
+#[derive(Debug, PartialEq, Clone)]
+pub enum Location {
+    Addr(u16),
+    Banked(u16, u16),
+    Symbol(String),
+}
+
+impl Location {
+    pub fn parse(arg: &str) -> Result<Location, String> {
+        if let Some((bank, addr)) = arg.split_once(':') {
+            let bank = parse_banked_part(bank).map_err(|_| format!("invalid bank: {bank}"))?;
+            let addr = parse_banked_part(addr).map_err(|_| format!("invalid address: {addr}"))?;
+            return Ok(Location::Banked(bank, addr));
+        }
+        if arg.starts_with(|c: char| c.is_ascii_digit() || c == '$') {
+            return Ok(Location::Addr(parse_u16(arg)?));
+        }
+        Ok(Location::Symbol(arg.to_string()))
+    }
+
+    // Symbols in switchable rom keep their bank, everything else matches any bank
+    pub fn resolve(&self, symbols: &SymbolTable) -> Result<(Option<u16>, u16), String> {
+        match self {
+            Location::Addr(addr) => Ok((None, *addr)),
+            Location::Banked(bank, addr) => Ok((Some(*bank), *addr)),
+            Location::Symbol(name) => match symbols.lookup(name) {
+                Some((bank, addr @ 0x4000..=0x7fff)) => Ok((Some(bank), addr)),
+                Some((_, addr)) => Ok((None, addr)),
+                None => Err(format!("no symbol named {name}")),
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+    Break(Location, Option<Condition>),
+    Trace(Location, String),
+    SetCondition(usize, Option<Condition>),
+    Ignore(usize, u32),
+    Delete(usize),
+    ListBreakpoints,
+    // start, inclusive end
+    Watch(Location, Option<Location>, WatchKind, WatchCondition),
+    Unwatch(usize),
+    ListWatchpoints,
+    Step(u32),
+    Next,
+    Finish,
+    Continue,
+    ReverseStep(u32),
+    ReverseContinue,
+    Until(Location),
+    Registers,
+    Backtrace,
+    SetRegister(RegisterName, u16),
+    Memory(Location, u16),
+    Poke(u16, u8),
+    Disassemble(Option<Location>, usize),
+    Help,
+    Quit,
+}
+
+pub const HELP: &str = "\
+break|b LOC [if COND] add a breakpoint, LOC is ADDR, BANK:ADDR or a symbol name
+                      BANK:ADDR is hex like in symbol files, 0x or $ are optional
+                      COND is like `A == 0x3C && [0xC000] != 0`, flags are ZF NF HF CF
+trace LOC MESSAGE     log MESSAGE without stopping, `{HL}` prints the value of HL
+cond N [COND]         change or clear the condition of breakpoint N
+ignore N COUNT        let breakpoint N pass COUNT times before stopping
+delete|d N            delete breakpoint N
+breakpoints|bl        list breakpoints
+watch|w ADDR[-END] [COND]
+                      break on writes, COND is `== VALUE`, `changes` or `& MASK == VALUE`
+rwatch|awatch ADDR[-END] [COND]
+                      break on reads, or on reads and writes
+unwatch N             delete watchpoint N
+watches|wl            list watchpoints
+step|s [N]            execute N instructions
+next|n                step over CALL and RST
+finish|fin            run until the current function returns
+until|u ADDR          run until PC reaches ADDR
+continue|c            resume normal execution
+rstep|rs [N]          undo N instructions
+rcontinue|rc          run backwards to the previous breakpoint or watchpoint hit
+regs|r                dump registers
+backtrace|bt          show the call stack
+set REG VALUE         edit a register (A, F, B, ..., AF, BC, DE, HL, SP, PC)
+mem|x ADDR [LEN]      hex dump memory
+poke ADDR VALUE       write a byte to memory
+disas|dis [ADDR] [N]  disassemble N instructions, around PC by default
+help|h                show this help
+quit|q                exit the emulator
+Numbers are decimal unless prefixed with 0x or $, an empty line repeats the last command";
+
+pub fn parse_number(arg: &str) -> Result<u32, String> {
+    let parsed = if let Some(hex) = arg.strip_prefix("0x").or_else(|| arg.strip_prefix('$')) {
+        u32::from_str_radix(hex, 16)
+    } else {
+        arg.parse::<u32>()
+    };
+    parsed.map_err(|_| format!("invalid number: {arg}"))
+}
+
+// Bare numbers in BANK:ADDR are hex as in symbol files, prefixed ones parse as usual
+fn parse_banked_part(arg: &str) -> Result<u16, String> {
+    if arg.starts_with("0x") || arg.starts_with('$') {
+        parse_u16(arg)
+    } else {
+        u16::from_str_radix(arg, 16).map_err(|_| format!("invalid number: {arg}"))
+    }
+}
+
+fn parse_u16(arg: &str) -> Result<u16, String> {
+    let val = parse_number(arg)?;
+    u16::try_from(val).map_err(|_| format!("{arg} does not fit in 16 bits"))
+}
+
+fn parse_u8(arg: &str) -> Result<u8, String> {
+    let val = parse_number(arg)?;
+    u8::try_from(val).map_err(|_| format!("{arg} does not fit in 8 bits"))
+}
+
+fn parse_location(arg: &str) -> Result<Location, String> {
+    Location::parse(arg)
+}
+
+fn parse_optional_condition(words: &[&str]) -> Result<Option<Condition>, String> {
+    if words.is_empty() {
+        return Ok(None);
+    }
+    Condition::parse(&words.join(" ")).map(Some)
+}
+
+fn parse_condition(words: &[&str]) -> Result<WatchCondition, String> {
+    let condition = match words {
+        [] => WatchCondition::Always,
+        ["changes"] => WatchCondition::Changes,
+        ["==", value] => WatchCondition::Equals(parse_u8(value)?),
+        ["&", mask, "==", value] => WatchCondition::Mask {
+            mask: parse_u8(mask)?,
+            value: parse_u8(value)?,
+        },
+        _ => return Err(format!("invalid watch condition: {}", words.join(" "))),
+    };
+    Ok(condition)
+}
+
+fn parse_watch(words: &[&str], kind: WatchKind) -> Result<Command, String> {
+    let Some(range) = words.first() else {
+        return Err("watch needs an address, see help".to_string());
+    };
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (parse_location(start)?, Some(parse_location(end)?)),
+        None => (parse_location(range)?, None),
+    };
+    Ok(Command::Watch(
+        start,
+        end,
+        kind,
+        parse_condition(&words[1..])?,
+    ))
+}
+
+pub(super) fn parse_register(arg: &str) -> Result<RegisterName, String> {
+    let register = match arg.to_ascii_uppercase().as_str() {
+        "A" => RegisterName::Byte(Register::A),
+        "F" => RegisterName::Byte(Register::F),
+        "B" => RegisterName::Byte(Register::B),
+        "C" => RegisterName::Byte(Register::C),
+        "D" => RegisterName::Byte(Register::D),
+        "E" => RegisterName::Byte(Register::E),
+        "H" => RegisterName::Byte(Register::H),
+        "L" => RegisterName::Byte(Register::L),
+        "AF" => RegisterName::Word(Register16::AF),
+        "BC" => RegisterName::Word(Register16::BC),
+        "DE" => RegisterName::Word(Register16::DE),
+        "HL" => RegisterName::Word(Register16::HL),
+        "SP" => RegisterName::Word(Register16::SP),
+        "PC" => RegisterName::Word(Register16::PC),
+        _ => return Err(format!("unknown register: {arg}")),
+    };
+    Ok(register)
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let arg = |i: usize| -> Result<&str, String> {
+            words
+                .get(i)
+                .copied()
+                .ok_or_else(|| format!("{} needs more arguments, see help", words[0]))
+        };
+        let Some(&name) = words.first() else {
+            return Err("empty command".to_string());
+        };
+        let command = match name {
+            "b" | "break" => {
+                let condition = match words.get(2) {
+                    Some(&"if") => parse_optional_condition(&words[3..])?,
+                    Some(word) => return Err(format!("expected `if` but found {word}")),
+                    None => None,
+                };
+                Command::Break(parse_location(arg(1)?)?, condition)
+            }
+            "trace" => {
+                arg(2)?;
+                Command::Trace(parse_location(arg(1)?)?, words[2..].join(" "))
+            }
+            "cond" => Command::SetCondition(
+                parse_number(arg(1)?)? as usize,
+                parse_optional_condition(&words[2..])?,
+            ),
+            "ignore" => Command::Ignore(parse_number(arg(1)?)? as usize, parse_number(arg(2)?)?),
+            "d" | "delete" => Command::Delete(parse_number(arg(1)?)? as usize),
+            "bl" | "breakpoints" => Command::ListBreakpoints,
+            "w" | "watch" => parse_watch(&words[1..], WatchKind::Write)?,
+            "rwatch" => parse_watch(&words[1..], WatchKind::Read)?,
+            "awatch" => parse_watch(&words[1..], WatchKind::Access)?,
+            "unwatch" => Command::Unwatch(parse_number(arg(1)?)? as usize),
+            "wl" | "watches" => Command::ListWatchpoints,
+            "s" | "step" => match words.get(1) {
+                Some(count) => Command::Step(parse_number(count)?),
+                None => Command::Step(1),
+            },
+            "n" | "next" => Command::Next,
+            "fin" | "finish" => Command::Finish,
+            "u" | "until" => Command::Until(parse_location(arg(1)?)?),
+            "c" | "continue" => Command::Continue,
+            "rs" | "rstep" => match words.get(1) {
+                Some(count) => Command::ReverseStep(parse_number(count)?),
+                None => Command::ReverseStep(1),
+            },
+            "rc" | "rcontinue" => Command::ReverseContinue,
+            "r" | "regs" => Command::Registers,
+            "bt" | "backtrace" => Command::Backtrace,
+            "set" => Command::SetRegister(parse_register(arg(1)?)?, parse_u16(arg(2)?)?),
+            "x" | "mem" => {
+                let len = match words.get(2) {
+                    Some(len) => parse_u16(len)?,
+                    None => 64,
+                };
+                Command::Memory(parse_location(arg(1)?)?, len)
+            }
+            "poke" => Command::Poke(parse_u16(arg(1)?)?, parse_u8(arg(2)?)?),
+            "dis" | "disas" => {
+                let addr = match words.get(1) {
+                    Some(addr) => Some(parse_location(addr)?),
+                    None => None,
+                };
+                let count = match words.get(2) {
+                    Some(count) => parse_number(count)? as usize,
+                    None => 10,
+                };
+                Command::Disassemble(addr, count)
+            }
+            "h" | "help" => Command::Help,
+            "q" | "quit" => Command::Quit,
+            _ => return Err(format!("unknown command: {name}, see help")),
+        };
+        Ok(command)
+    }
+}
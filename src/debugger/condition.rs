@@ -0,0 +1,212 @@
+use crate::cpu::{Cpu, Register};
+use crate::memory::Memory;
+
+use super::command::{RegisterName, parse_number, parse_register};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    BitAnd,
+    BitOr,
+}
+
+// Expressions over registers, flags and memory, e.g. `A == 0x3C && [0xC000] != 0`
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Number(i64),
+    Register(RegisterName),
+    // Mask of the flag in F
+    Flag(u8),
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+// Operators by precedence, loosest first
+const PRECEDENCE: [&[(&str, BinaryOp)]; 4] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[
+        ("==", BinaryOp::Eq),
+        ("!=", BinaryOp::Ne),
+        ("<=", BinaryOp::Le),
+        (">=", BinaryOp::Ge),
+        ("<", BinaryOp::Lt),
+        (">", BinaryOp::Gt),
+    ],
+    &[
+        ("+", BinaryOp::Add),
+        ("-", BinaryOp::Sub),
+        ("&", BinaryOp::BitAnd),
+        ("|", BinaryOp::BitOr),
+    ],
+];
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_alphanumeric() || c == '$' || c == '_' {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '$' || c == '_') {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(word);
+        } else {
+            chars.next();
+            let pair = chars.peek().map(|&next| format!("{c}{next}"));
+            match pair {
+                Some(pair) if ["||", "&&", "==", "!=", "<=", ">="].contains(&pair.as_str()) => {
+                    chars.next();
+                    tokens.push(pair);
+                }
+                _ if "!<>+-&|[]()".contains(c) => tokens.push(c.to_string()),
+                _ => return Err(format!("unexpected character '{c}' in condition")),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or("condition ended unexpectedly")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected '{expected}' but found '{token}'")),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&(_, op)) = PRECEDENCE[level]
+            .iter()
+            .find(|(token, _)| self.peek() == Some(token))
+        {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+            // Comparisons don't chain
+            if level == 2 {
+                break;
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let token = self.next()?;
+        match token.as_str() {
+            "!" => Ok(Expr::Not(Box::new(self.unary()?))),
+            "(" => {
+                let expr = self.binary(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            "[" => {
+                let expr = self.binary(0)?;
+                self.expect("]")?;
+                Ok(Expr::Memory(Box::new(expr)))
+            }
+            _ => match token.to_ascii_uppercase().as_str() {
+                "ZF" => Ok(Expr::Flag(0x80)),
+                "NF" => Ok(Expr::Flag(0x40)),
+                "HF" => Ok(Expr::Flag(0x20)),
+                "CF" => Ok(Expr::Flag(0x10)),
+                _ if token.starts_with(|c: char| c.is_ascii_digit() || c == '$') => {
+                    Ok(Expr::Number(parse_number(&token)? as i64))
+                }
+                _ => Ok(Expr::Register(parse_register(&token)?)),
+            },
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        };
+        let expr = parser.binary(0)?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected '{token}' in condition"));
+        }
+        Ok(expr)
+    }
+
+    // Comparisons evaluate to 0 or 1, memory is read without triggering watchpoints
+    pub fn evaluate(&self, cpu: &Cpu, memory: &Memory) -> i64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Register(RegisterName::Byte(register)) => cpu.get_reg(*register) as i64,
+            Expr::Register(RegisterName::Word(register)) => cpu.get_register16(*register) as i64,
+            Expr::Flag(mask) => (cpu.get_reg(Register::F) & mask != 0) as i64,
+            Expr::Memory(addr) => memory.peek(addr.evaluate(cpu, memory) as u16) as i64,
+            Expr::Not(expr) => (expr.evaluate(cpu, memory) == 0) as i64,
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.evaluate(cpu, memory);
+                // Short circuit so `HL != 0 && [HL] == 1` style guards work as expected
+                match op {
+                    BinaryOp::And if lhs == 0 => return 0,
+                    BinaryOp::Or if lhs != 0 => return 1,
+                    _ => {}
+                }
+                let rhs = rhs.evaluate(cpu, memory);
+                match op {
+                    BinaryOp::Or | BinaryOp::And => (rhs != 0) as i64,
+                    BinaryOp::Eq => (lhs == rhs) as i64,
+                    BinaryOp::Ne => (lhs != rhs) as i64,
+                    BinaryOp::Lt => (lhs < rhs) as i64,
+                    BinaryOp::Le => (lhs <= rhs) as i64,
+                    BinaryOp::Gt => (lhs > rhs) as i64,
+                    BinaryOp::Ge => (lhs >= rhs) as i64,
+                    BinaryOp::Add => lhs + rhs,
+                    BinaryOp::Sub => lhs - rhs,
+                    BinaryOp::BitAnd => lhs & rhs,
+                    BinaryOp::BitOr => lhs | rhs,
+                }
+            }
+        }
+    }
+
+    pub fn is_true(&self, cpu: &Cpu, memory: &Memory) -> bool {
+        self.evaluate(cpu, memory) != 0
+    }
+}
@@ -0,0 +1,306 @@
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{Cpu, Register, Register16};
+    use crate::debugger::breakpoint::{Breakpoint, Condition, Hit};
+    use crate::debugger::command::{Command, Location, RegisterName, parse_number};
+    use crate::debugger::condition::Expr;
+    use crate::emulator::{Emulator, RunConfig};
+    use crate::memory::{Memory, MemoryType, WatchCondition, WatchKind};
+    use crate::symbols::SymbolTable;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("42"), Ok(42));
+        assert_eq!(parse_number("0x150"), Ok(0x150));
+        assert_eq!(parse_number("$C000"), Ok(0xc000));
+        assert!(parse_number("zz").is_err());
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            Command::parse("b 0x150"),
+            Ok(Command::Break(Location::Addr(0x150), None))
+        );
+        assert_eq!(Command::parse("step"), Ok(Command::Step(1)));
+        assert_eq!(Command::parse("s 10"), Ok(Command::Step(10)));
+        assert_eq!(Command::parse("  n  "), Ok(Command::Next));
+        assert_eq!(
+            Command::parse("x $ff40"),
+            Ok(Command::Memory(Location::Addr(0xff40), 64))
+        );
+        assert_eq!(
+            Command::parse("poke 0xc000 0x3c"),
+            Ok(Command::Poke(0xc000, 0x3c))
+        );
+        assert_eq!(Command::parse("dis"), Ok(Command::Disassemble(None, 10)));
+        assert_eq!(
+            Command::parse("b Main.loop"),
+            Ok(Command::Break(
+                Location::Symbol("Main.loop".to_string()),
+                None
+            ))
+        );
+        assert_eq!(
+            Command::parse("set a 0x12"),
+            Ok(Command::SetRegister(RegisterName::Byte(Register::A), 0x12))
+        );
+        assert_eq!(
+            Command::parse("set SP 0xfffe"),
+            Ok(Command::SetRegister(
+                RegisterName::Word(Register16::SP),
+                0xfffe
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Command::parse("").is_err());
+        assert!(Command::parse("jump 0x100").is_err());
+        assert!(Command::parse("b").is_err());
+        assert!(Command::parse("poke 0xc000 0x100").is_err());
+        assert!(Command::parse("set IX 0").is_err());
+    }
+
+    #[test]
+    fn test_parse_watch() {
+        assert_eq!(
+            Command::parse("watch $c000"),
+            Ok(Command::Watch(
+                Location::Addr(0xc000),
+                None,
+                WatchKind::Write,
+                WatchCondition::Always
+            ))
+        );
+        assert_eq!(
+            Command::parse("rwatch 0xc000-0xc0ff == 0x3c"),
+            Ok(Command::Watch(
+                Location::Addr(0xc000),
+                Some(Location::Addr(0xc0ff)),
+                WatchKind::Read,
+                WatchCondition::Equals(0x3c)
+            ))
+        );
+        assert_eq!(
+            Command::parse("awatch wScore changes"),
+            Ok(Command::Watch(
+                Location::Symbol("wScore".to_string()),
+                None,
+                WatchKind::Access,
+                WatchCondition::Changes
+            ))
+        );
+        assert_eq!(
+            Command::parse("w $ff40 & $80 == 0"),
+            Ok(Command::Watch(
+                Location::Addr(0xff40),
+                None,
+                WatchKind::Write,
+                WatchCondition::Mask {
+                    mask: 0x80,
+                    value: 0
+                }
+            ))
+        );
+        assert!(Command::parse("watch $c000 >= 3").is_err());
+        assert!(Command::parse("watch").is_err());
+    }
+
+    #[test]
+    fn test_parse_breakpoints() {
+        assert_eq!(
+            Command::parse("b 01:4a2f if A == 0x3C"),
+            Ok(Command::Break(
+                Location::Banked(1, 0x4a2f),
+                Some(Condition::parse("A == 0x3C").unwrap())
+            ))
+        );
+        assert_eq!(
+            Command::parse("trace $0150 HL is {HL}"),
+            Ok(Command::Trace(
+                Location::Addr(0x150),
+                "HL is {HL}".to_string()
+            ))
+        );
+        assert_eq!(Command::parse("cond 2"), Ok(Command::SetCondition(2, None)));
+        assert_eq!(Command::parse("ignore 0 5"), Ok(Command::Ignore(0, 5)));
+        assert!(Command::parse("b $0150 when A == 1").is_err());
+        assert!(Command::parse("b $0150 if A =").is_err());
+    }
+
+    #[test]
+    fn test_resolve_location() {
+        let mut symbols = SymbolTable::default();
+        symbols.insert(2, 0x4100, "Bank2Func");
+        symbols.insert(0, 0x0150, "Init");
+        let resolve = |arg| Location::parse(arg).unwrap().resolve(&symbols);
+        assert_eq!(resolve("Bank2Func"), Ok((Some(2), 0x4100)));
+        assert_eq!(resolve("Init"), Ok((None, 0x0150)));
+        assert_eq!(resolve("03:4000"), Ok((Some(3), 0x4000)));
+        assert_eq!(resolve("1:0x4000"), Ok((Some(1), 0x4000)));
+        assert_eq!(resolve("$1:$4a2f"), Ok((Some(1), 0x4a2f)));
+        assert!(Location::parse("1:0x10000").is_err());
+        assert!(Location::parse("1:4g00").is_err());
+        assert_eq!(resolve("0x4000"), Ok((None, 0x4000)));
+        assert!(resolve("Missing").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_conditions() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        cpu.set_reg(Register::A, 0x3c);
+        cpu.set_register16(Register16::HL, 0xc000);
+        cpu.set_reg(Register::F, 0x80);
+        memory.write_byte(0xc000, 7);
+        let eval = |text| Expr::parse(text).unwrap().evaluate(&cpu, &memory);
+        assert_eq!(eval("A == 0x3C && [0xC000] != 0"), 1);
+        assert_eq!(eval("A == 0x3C && [0xC000] == 0"), 0);
+        assert_eq!(eval("[HL] + 1"), 8);
+        assert_eq!(eval("[HL + 1]"), 0);
+        assert_eq!(eval("ZF && !CF"), 1);
+        assert_eq!(eval("A < 0x10 || HL >= $C000"), 1);
+        assert_eq!(eval("(A & $0F) == $0C"), 1);
+        assert!(Expr::parse("A ==").is_err());
+        assert!(Expr::parse("[HL").is_err());
+        assert!(Expr::parse("A = 1").is_err());
+    }
+
+    #[test]
+    fn test_breakpoint_hits() {
+        let mut cpu = Cpu::new();
+        let memory = Memory::new();
+        cpu.set_reg(Register::A, 1);
+
+        let mut breakpoint = Breakpoint::new(Some(2), 0x4000);
+        breakpoint.condition = Some(Condition::parse("A == 1").unwrap());
+        breakpoint.ignore_count = 1;
+        assert!(matches!(
+            breakpoint.check(0x4000, 1, &cpu, &memory),
+            Hit::Miss
+        ));
+        assert!(matches!(
+            breakpoint.check(0x4001, 2, &cpu, &memory),
+            Hit::Miss
+        ));
+        assert!(matches!(
+            breakpoint.check(0x4000, 2, &cpu, &memory),
+            Hit::Ignored
+        ));
+        assert!(matches!(
+            breakpoint.check(0x4000, 2, &cpu, &memory),
+            Hit::Stop
+        ));
+        cpu.set_reg(Register::A, 2);
+        assert!(matches!(
+            breakpoint.check(0x4000, 2, &cpu, &memory),
+            Hit::Miss
+        ));
+        assert_eq!(breakpoint.hits, 2);
+
+        let mut tracepoint = Breakpoint::new(None, 0x150);
+        tracepoint.log = Some("A={A} [C000]={[0xC000]}".to_string());
+        match tracepoint.check(0x150, 0, &cpu, &memory) {
+            Hit::Log(message) => assert_eq!(message, "A=$2 [C000]=$0"),
+            _ => panic!("tracepoint should log"),
+        }
+    }
+
+    #[test]
+    fn test_backtrace() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("debugger_test_backtrace.gb");
+        let mut rom = vec![0; 32 * 1024];
+        // CALL $0200, which calls $0300
+        rom[0x100..0x103].copy_from_slice(&[0xcd, 0x00, 0x02]);
+        rom[0x200..0x203].copy_from_slice(&[0xcd, 0x00, 0x03]);
+        std::fs::write(&path, rom).unwrap();
+        std::fs::write(
+            dir.join("debugger_test_backtrace.sym"),
+            "00:0100 Entry\n00:0200 Outer\n00:0300 Inner\n",
+        )
+        .unwrap();
+
+        let mut emulator = Emulator::new(RunConfig::default());
+        emulator
+            .load_rom(&path.to_string_lossy().to_string())
+            .unwrap();
+        emulator.step().unwrap();
+        emulator.step().unwrap();
+        assert_eq!(
+            emulator.backtrace(),
+            vec![
+                "#0  $0300 <Inner>",
+                "#1  $0200 <Outer>",
+                "#2  $0100 <Entry>"
+            ]
+        );
+        assert_eq!(Command::parse("bt"), Ok(Command::Backtrace));
+    }
+
+    #[test]
+    fn test_breakpoint_after_halt() {
+        let path = std::env::temp_dir().join("debugger_test_halt.gb");
+        let mut rom = vec![0; 32 * 1024];
+        // DI, HALT, NOP
+        rom[0x100..0x103].copy_from_slice(&[0xf3, 0x76, 0x00]);
+        std::fs::write(&path, rom).unwrap();
+
+        let mut emulator = Emulator::new(RunConfig::default());
+        emulator
+            .load_rom(&path.to_string_lossy().to_string())
+            .unwrap();
+        emulator.add_breakpoint(Breakpoint::new(None, 0x102));
+        emulator.memory_mut().write_byte(0xFFFF, 0x00);
+        emulator.step().unwrap();
+        emulator.step().unwrap();
+        for _ in 0..100 {
+            assert!(emulator.cpu().is_halted());
+            assert!(!emulator.check_breakpoints());
+            emulator.step().unwrap();
+        }
+        assert_eq!(emulator.breakpoints()[0].hits, 0);
+
+        // A pending interrupt ends HALT while IME is off and the NOP runs in the same tick
+        emulator.memory_mut().write_byte(0xFFFF, 0x01);
+        emulator.memory_mut().write_byte(0xFF0F, 0x01);
+        assert!(emulator.check_breakpoints());
+        assert_eq!(emulator.breakpoints()[0].hits, 1);
+        emulator.step().unwrap();
+        assert!(!emulator.cpu().is_halted());
+        assert_eq!(emulator.cpu().PC(), 0x103);
+    }
+
+    #[test]
+    fn test_breakpoint_on_interrupt_vector() {
+        let path = std::env::temp_dir().join("debugger_test_vector.gb");
+        let mut rom = vec![0; 32 * 1024];
+        // EI, NOP, NOP, with the VBlank handler a run of NOPs at $0040
+        rom[0x100..0x103].copy_from_slice(&[0xfb, 0x00, 0x00]);
+        std::fs::write(&path, rom).unwrap();
+
+        let mut emulator = Emulator::new(RunConfig::default());
+        emulator
+            .load_rom(&path.to_string_lossy().to_string())
+            .unwrap();
+        emulator.add_breakpoint(Breakpoint::new(None, 0x40));
+        emulator.add_breakpoint(Breakpoint::new(None, 0x102));
+        emulator.memory_mut().write_byte(0xFFFF, 0x01);
+        emulator.memory_mut().write_byte(0xFF0F, 0x01);
+
+        let mut stops = Vec::new();
+        for _ in 0..5 {
+            if emulator.check_breakpoints() {
+                stops.push(emulator.cpu().PC());
+            }
+            emulator.step().unwrap();
+        }
+        // The interrupt is taken after the NOP behind EI, so $0102 never runs
+        assert_eq!(stops, vec![0x40]);
+        assert_eq!(emulator.breakpoints()[0].hits, 1);
+        assert_eq!(emulator.breakpoints()[1].hits, 0);
+        assert_eq!(emulator.cpu().PC(), 0x42);
+    }
+}
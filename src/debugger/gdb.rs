@@ -0,0 +1,349 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::cpu::Register16;
+use crate::emulator::{Emulator, ReverseStop};
+use crate::memory::{Access, WatchCondition, WatchKind, Watchpoint};
+
+use super::breakpoint::Breakpoint;
+
+pub const DEFAULT_PORT: u16 = 2345;
+
+// GDB has no SM83 target, so registers are sent in its z80 layout: AF BC DE HL SP PC then IX IY AF' BC' DE' HL' IR
+const REGISTERS: [Register16; 6] = [
+    Register16::AF,
+    Register16::BC,
+    Register16::DE,
+    Register16::HL,
+    Register16::SP,
+    Register16::PC,
+];
+const Z80_REGISTER_COUNT: usize = 13;
+
+pub enum Response {
+    Reply(String),
+    // Execution continues, the reply is sent once the emulator stops again
+    Resume,
+    Detach,
+}
+
+struct Client {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    running: bool,
+}
+
+// Remote serial protocol stub, polled from the main loop so it never blocks emulation
+pub struct GdbServer {
+    listener: TcpListener,
+    client: Option<Client>,
+}
+
+pub fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b))
+}
+
+fn frame(data: &str) -> String {
+    format!("${data}#{:02x}", checksum(data))
+}
+
+fn hex_u16(val: u16) -> String {
+    let [low, high] = val.to_le_bytes();
+    format!("{low:02x}{high:02x}")
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
+
+fn parse_le_u16(text: &str) -> Option<u16> {
+    let low = u8::from_str_radix(text.get(0..2)?, 16).ok()?;
+    let high = u8::from_str_radix(text.get(2..4)?, 16).ok()?;
+    Some(u16::from_le_bytes([low, high]))
+}
+
+fn stop_reply(emulator: &mut Emulator) -> String {
+    match emulator.take_last_watch_hit() {
+        Some(hit) => {
+            let kind = match (hit.access, emulator.memory().watchpoints().get(hit.index)) {
+                (_, Some(watchpoint)) if watchpoint.kind == WatchKind::Access => "awatch",
+                (Access::Read, _) => "rwatch",
+                (Access::Write, _) => "watch",
+            };
+            format!("T05{kind}:{:x};", hit.addr)
+        }
+        None => "S05".to_string(),
+    }
+}
+
+fn watch_kind(packet_type: char) -> Option<WatchKind> {
+    match packet_type {
+        '2' => Some(WatchKind::Write),
+        '3' => Some(WatchKind::Read),
+        '4' => Some(WatchKind::Access),
+        _ => None,
+    }
+}
+
+// Z and z packets: `Z0,addr,kind` for breakpoints, Z2-Z4 for watchpoints with a length
+fn set_point(packet: &str, insert: bool, emulator: &mut Emulator) -> Option<String> {
+    let mut fields = packet[1..].split(',');
+    let packet_type = fields.next()?.chars().next()?;
+    let addr = parse_hex(fields.next()?)? as u16;
+    let length = parse_hex(fields.next()?)? as u16;
+    match packet_type {
+        '0' | '1' => {
+            let existing = emulator.breakpoints().iter().position(|b| {
+                b.addr == addr && b.bank.is_none() && b.condition.is_none() && b.log.is_none()
+            });
+            match (insert, existing) {
+                (true, None) => {
+                    emulator.add_breakpoint(Breakpoint::new(None, addr));
+                }
+                (false, Some(index)) => {
+                    emulator.remove_breakpoint(index);
+                }
+                _ => {}
+            }
+        }
+        _ => {
+            let watchpoint = Watchpoint {
+                start: addr,
+                end: addr.wrapping_add(length.max(1) - 1),
+                kind: watch_kind(packet_type)?,
+                condition: WatchCondition::Always,
+            };
+            if insert {
+                emulator.memory_mut().add_watchpoint(watchpoint);
+            } else {
+                let memory = emulator.memory_mut();
+                if let Some(index) = memory.watchpoints().iter().position(|w| *w == watchpoint) {
+                    memory.remove_watchpoint(index);
+                }
+            }
+        }
+    }
+    Some("OK".to_string())
+}
+
+fn read_registers(emulator: &Emulator) -> String {
+    let mut reply: String = REGISTERS
+        .iter()
+        .map(|&register| hex_u16(emulator.cpu().get_register16(register)))
+        .collect();
+    reply.push_str(&"0000".repeat(Z80_REGISTER_COUNT - REGISTERS.len()));
+    reply
+}
+
+fn write_registers(data: &str, emulator: &mut Emulator) -> Option<String> {
+    for (i, &register) in REGISTERS.iter().enumerate() {
+        let val = parse_le_u16(data.get(i * 4..i * 4 + 4)?)?;
+        emulator.cpu_mut().set_register16(register, val);
+    }
+    Some("OK".to_string())
+}
+
+fn read_memory(args: &str, emulator: &Emulator) -> Option<String> {
+    let (addr, length) = args.split_once(',')?;
+    let addr = parse_hex(addr)? as u16;
+    let length = parse_hex(length)? as u16;
+    Some(
+        (0..length)
+            .map(|i| format!("{:02x}", emulator.memory().peek(addr.wrapping_add(i))))
+            .collect(),
+    )
+}
+
+fn write_memory(args: &str, emulator: &mut Emulator) -> Option<String> {
+    let (header, data) = args.split_once(':')?;
+    let (addr, length) = header.split_once(',')?;
+    let addr = parse_hex(addr)? as u16;
+    let length = parse_hex(length)? as u16;
+    for i in 0..length {
+        let offset = i as usize * 2;
+        let val = u8::from_str_radix(data.get(offset..offset + 2)?, 16).ok()?;
+        emulator.memory_mut().poke(addr.wrapping_add(i), val);
+    }
+    Some("OK".to_string())
+}
+
+pub fn handle_packet(packet: &str, emulator: &mut Emulator) -> Response {
+    let reply = match packet.chars().next() {
+        Some('?') => Some(stop_reply(emulator)),
+        Some('g') => Some(read_registers(emulator)),
+        Some('G') => write_registers(&packet[1..], emulator),
+        Some('p') => parse_hex(&packet[1..]).map(|index| match REGISTERS.get(index as usize) {
+            Some(&register) => hex_u16(emulator.cpu().get_register16(register)),
+            None => "0000".to_string(),
+        }),
+        // Registers past the ones the cpu has can't be written, so that's an error
+        Some('P') => packet[1..].split_once('=').and_then(|(index, val)| {
+            let &register = REGISTERS.get(parse_hex(index)? as usize)?;
+            emulator
+                .cpu_mut()
+                .set_register16(register, parse_le_u16(val)?);
+            Some("OK".to_string())
+        }),
+        Some('m') => read_memory(&packet[1..], emulator),
+        Some('M') => write_memory(&packet[1..], emulator),
+        Some('Z') => set_point(packet, true, emulator),
+        Some('z') => set_point(packet, false, emulator),
+        Some('s') => {
+            // Breakpoints only stop a continue, a single step always finishes
+            if let Err(err) = emulator.step() {
+                println!("{err}");
+                emulator.dump_trace();
+            }
+            emulator.take_stop_request();
+            Some(stop_reply(emulator))
+        }
+        Some('c') => {
+            emulator.take_last_watch_hit();
+            emulator.resume();
+            return Response::Resume;
+        }
+        Some('b') => match packet {
+            "bs" => Some(
+                emulator
+                    .reverse_step(1)
+                    .map_or_else(|_| "E01".to_string(), |()| stop_reply(emulator)),
+            ),
+            "bc" => Some(match emulator.reverse_continue() {
+                // gdb treats running out of history as reaching the start of the recording
+                Ok(ReverseStop::StartOfHistory) => "T05replaylog:begin;".to_string(),
+                Ok(_) => stop_reply(emulator),
+                Err(_) => "E01".to_string(),
+            }),
+            _ => Some(String::new()),
+        },
+        Some('D') => return Response::Detach,
+        Some('k') => return Response::Detach,
+        Some('H') => Some("OK".to_string()),
+        _ => match packet {
+            "qAttached" => Some("1".to_string()),
+            "qC" => Some("QC1".to_string()),
+            "qfThreadInfo" => Some("m1".to_string()),
+            "qsThreadInfo" => Some("l".to_string()),
+            _ if packet.starts_with("qSupported") => {
+                Some("PacketSize=1000;swbreak+;hwbreak+;ReverseStep+;ReverseContinue+".to_string())
+            }
+            // Empty reply means unsupported
+            _ => Some(String::new()),
+        },
+    };
+    Response::Reply(reply.unwrap_or_else(|| "E01".to_string()))
+}
+
+impl GdbServer {
+    pub fn bind(port: u16) -> std::io::Result<GdbServer> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        println!("Waiting for gdb on 127.0.0.1:{port}");
+        Ok(GdbServer {
+            listener,
+            client: None,
+        })
+    }
+
+    pub fn poll(&mut self, emulator: &mut Emulator) {
+        if self.client.is_none() {
+            self.accept(emulator);
+        }
+        let Some(client) = self.client.as_mut() else {
+            return;
+        };
+        if client.running && emulator.is_stepping() {
+            client.running = false;
+            emulator.dump_trace();
+            let reply = stop_reply(emulator);
+            Self::send(client, &reply);
+        }
+        let mut chunk = [0; 1024];
+        match client.stream.read(&mut chunk) {
+            Ok(0) => {
+                println!("gdb disconnected");
+                self.client = None;
+                return;
+            }
+            Ok(read) => client.buffer.extend_from_slice(&chunk[..read]),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+            Err(err) => {
+                println!("gdb connection failed: {err}");
+                self.client = None;
+                return;
+            }
+        }
+        while let Some(packet) = Self::next_packet(client, emulator) {
+            match handle_packet(&packet, emulator) {
+                Response::Reply(reply) => Self::send(client, &reply),
+                Response::Resume => client.running = true,
+                Response::Detach => {
+                    Self::send(client, "OK");
+                    emulator.resume();
+                    println!("gdb detached");
+                    self.client = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn accept(&mut self, emulator: &mut Emulator) {
+        match self.listener.accept() {
+            Ok((stream, addr)) => {
+                if stream.set_nonblocking(true).is_err() {
+                    return;
+                }
+                println!("gdb connected from {addr}");
+                // gdb expects the target to be halted when it attaches
+                emulator.pause();
+                self.client = Some(Client {
+                    stream,
+                    buffer: Vec::new(),
+                    running: false,
+                });
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+            Err(err) => println!("gdb accept failed: {err}"),
+        }
+    }
+
+    // Pulls the next complete `$packet#checksum` out of the buffer, acking it
+    fn next_packet(client: &mut Client, emulator: &mut Emulator) -> Option<String> {
+        loop {
+            match client.buffer.first()? {
+                b'+' | b'-' => {
+                    client.buffer.remove(0);
+                }
+                // Ctrl-C
+                0x03 => {
+                    client.buffer.remove(0);
+                    emulator.pause();
+                }
+                b'$' => break,
+                _ => {
+                    client.buffer.remove(0);
+                }
+            }
+        }
+        let end = client.buffer.iter().position(|&b| b == b'#')?;
+        if client.buffer.len() < end + 3 {
+            return None;
+        }
+        let packet = String::from_utf8_lossy(&client.buffer[1..end]).to_string();
+        let sent = String::from_utf8_lossy(&client.buffer[end + 1..end + 3]).to_string();
+        client.buffer.drain(..end + 3);
+        if parse_hex(&sent) != Some(checksum(&packet) as u32) {
+            client.stream.write_all(b"-").ok();
+            return None;
+        }
+        client.stream.write_all(b"+").ok();
+        Some(packet)
+    }
+
+    fn send(client: &mut Client, reply: &str) {
+        if let Err(err) = client.stream.write_all(frame(reply).as_bytes()) {
+            println!("gdb send failed: {err}");
+        }
+    }
+}
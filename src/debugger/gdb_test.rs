@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Register16;
+    use crate::debugger::gdb::{Response, checksum, handle_packet};
+    use crate::emulator::{Emulator, RunConfig};
+    use crate::memory::WatchKind;
+
+    fn reply(packet: &str, emulator: &mut Emulator) -> String {
+        match handle_packet(packet, emulator) {
+            Response::Reply(reply) => reply,
+            _ => panic!("{packet} should reply"),
+        }
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum("OK"), 0x9a);
+        assert_eq!(checksum(""), 0);
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut emulator = Emulator::new(RunConfig::default());
+        emulator.cpu_mut().set_register16(Register16::PC, 0x0150);
+        emulator.cpu_mut().set_register16(Register16::HL, 0xc0de);
+        let registers = reply("g", &mut emulator);
+        assert_eq!(registers.len(), 13 * 4);
+        assert_eq!(&registers[12..16], "dec0");
+        assert_eq!(&registers[20..24], "5001");
+        assert_eq!(reply("p5", &mut emulator), "5001");
+
+        assert_eq!(reply("P3=3412", &mut emulator), "OK");
+        assert_eq!(emulator.cpu().get_register16(Register16::HL), 0x1234);
+        assert_eq!(reply("Pc=3412", &mut emulator), "E01");
+        assert_eq!(reply("P3=zz", &mut emulator), "E01");
+        assert_eq!(emulator.cpu().get_register16(Register16::HL), 0x1234);
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut emulator = Emulator::new(RunConfig::default());
+        assert_eq!(reply("Mc000,3:0102ff", &mut emulator), "OK");
+        assert_eq!(reply("mc000,3", &mut emulator), "0102ff");
+        assert_eq!(reply("mc000", &mut emulator), "E01");
+    }
+
+    #[test]
+    fn test_breakpoints_and_watchpoints() {
+        let mut emulator = Emulator::new(RunConfig::default());
+        assert_eq!(reply("Z0,150,1", &mut emulator), "OK");
+        assert_eq!(reply("Z0,150,1", &mut emulator), "OK");
+        assert_eq!(emulator.breakpoints().len(), 1);
+        assert_eq!(reply("z0,150,1", &mut emulator), "OK");
+        assert!(emulator.breakpoints().is_empty());
+
+        assert_eq!(reply("Z3,c000,2", &mut emulator), "OK");
+        let watchpoint = emulator.memory().watchpoints()[0];
+        assert_eq!(
+            (watchpoint.start, watchpoint.end, watchpoint.kind),
+            (0xc000, 0xc001, WatchKind::Read)
+        );
+        assert_eq!(reply("z3,c000,2", &mut emulator), "OK");
+        assert!(emulator.memory().watchpoints().is_empty());
+    }
+
+    #[test]
+    fn test_step_and_continue() {
+        let mut emulator = Emulator::new(RunConfig::default());
+        let path = std::env::temp_dir().join("gdb_test_step.gb");
+        std::fs::write(&path, vec![0; 32 * 1024]).unwrap();
+        emulator
+            .load_rom(&path.to_string_lossy().to_string())
+            .unwrap();
+        let pc = emulator.cpu().PC();
+        assert_eq!(reply("s", &mut emulator), "S05");
+        assert_ne!(emulator.cpu().PC(), pc);
+        assert!(matches!(
+            handle_packet("c", &mut emulator),
+            Response::Resume
+        ));
+        assert!(!emulator.is_stepping());
+        assert_eq!(reply("vMustReplyEmpty", &mut emulator), "");
+    }
+}
@@ -0,0 +1,351 @@
+mod breakpoint;
+mod command;
+mod condition;
+mod debugger_test;
+pub mod gdb;
+mod gdb_test;
+
+use std::io::Write;
+
+use crate::cpu::{Register, Register16};
+use crate::disassembler::DecodedInstruction;
+use crate::emulator::{Emulator, ReverseStop};
+use crate::memory::Watchpoint;
+
+pub use self::breakpoint::{Breakpoint, BreakpointConfig, Condition, Hit};
+pub use self::command::Location;
+use self::command::{Command, RegisterName};
+
+// Upper bound for next/finish/until so a function that never returns can't hang the prompt forever
+const RUN_LIMIT: u32 = 50_000_000;
+
+const CALL_OPCODES: [u8; 5] = [0xc4, 0xcc, 0xcd, 0xd4, 0xdc];
+const RST_OPCODES: [u8; 8] = [0xc7, 0xcf, 0xd7, 0xdf, 0xe7, 0xef, 0xf7, 0xff];
+const RET_OPCODES: [u8; 6] = [0xc0, 0xc8, 0xc9, 0xd0, 0xd8, 0xd9];
+
+pub struct Debugger {
+    last_command: Option<Command>,
+    show_location: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            last_command: None,
+            show_location: true,
+        }
+    }
+
+    // Reads and runs one command from stdin, returns false when the user wants to quit
+    pub fn prompt(&mut self, emulator: &mut Emulator) -> bool {
+        if self.show_location {
+            Self::print_location(emulator);
+            self.show_location = false;
+        }
+        print!("(gbdb) ");
+        std::io::stdout().flush().ok();
+
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => return false,
+            Ok(_) => {}
+        }
+        let command = if line.trim().is_empty() {
+            match self.last_command.clone() {
+                Some(command) => command,
+                None => return true,
+            }
+        } else {
+            match Command::parse(&line) {
+                Ok(command) => command,
+                Err(err) => {
+                    println!("{err}");
+                    return true;
+                }
+            }
+        };
+        self.last_command = Some(command.clone());
+        self.execute(emulator, command)
+    }
+
+    fn resolve_banked(emulator: &Emulator, location: &Location) -> Option<(Option<u16>, u16)> {
+        match location.resolve(emulator.symbols()) {
+            Ok(resolved) => Some(resolved),
+            Err(err) => {
+                println!("{err}");
+                None
+            }
+        }
+    }
+
+    fn resolve(emulator: &Emulator, location: &Location) -> Option<u16> {
+        Self::resolve_banked(emulator, location).map(|(_, addr)| addr)
+    }
+
+    fn add_breakpoint(emulator: &mut Emulator, breakpoint: Breakpoint) {
+        let description = emulator.describe_addr(breakpoint.addr);
+        let index = emulator.add_breakpoint(breakpoint);
+        println!("Breakpoint {index} at {description}");
+    }
+
+    pub fn execute(&mut self, emulator: &mut Emulator, command: Command) -> bool {
+        match command {
+            Command::Break(location, condition) => {
+                if let Some((bank, addr)) = Self::resolve_banked(emulator, &location) {
+                    let mut breakpoint = Breakpoint::new(bank, addr);
+                    breakpoint.condition = condition;
+                    Self::add_breakpoint(emulator, breakpoint);
+                }
+            }
+            Command::Trace(location, message) => {
+                if let Some((bank, addr)) = Self::resolve_banked(emulator, &location) {
+                    let mut breakpoint = Breakpoint::new(bank, addr);
+                    breakpoint.log = Some(message);
+                    Self::add_breakpoint(emulator, breakpoint);
+                }
+            }
+            Command::SetCondition(index, condition) => match emulator.breakpoint_mut(index) {
+                Some(breakpoint) => breakpoint.condition = condition,
+                None => println!("No breakpoint {index}"),
+            },
+            Command::Ignore(index, count) => match emulator.breakpoint_mut(index) {
+                Some(breakpoint) => breakpoint.ignore_count = breakpoint.hits + count,
+                None => println!("No breakpoint {index}"),
+            },
+            Command::Delete(index) => match emulator.remove_breakpoint(index) {
+                Some(breakpoint) => println!("Deleted breakpoint {index}: {breakpoint}"),
+                None => println!("No breakpoint {index}"),
+            },
+            Command::ListBreakpoints => {
+                for (index, breakpoint) in emulator.breakpoints().iter().enumerate() {
+                    println!(
+                        "{index}: {breakpoint} {}",
+                        emulator.describe_addr(breakpoint.addr)
+                    );
+                }
+            }
+            Command::Watch(start, end, kind, condition) => {
+                let Some(start) = Self::resolve(emulator, &start) else {
+                    return true;
+                };
+                let end = match end {
+                    Some(end) => match Self::resolve(emulator, &end) {
+                        Some(end) => end,
+                        None => return true,
+                    },
+                    None => start,
+                };
+                if end < start {
+                    println!("Watch range ends before it starts");
+                    return true;
+                }
+                let watchpoint = Watchpoint {
+                    start,
+                    end,
+                    kind,
+                    condition,
+                };
+                let index = emulator.memory_mut().add_watchpoint(watchpoint);
+                println!("Watchpoint {index}: {watchpoint}");
+            }
+            Command::Unwatch(index) => match emulator.memory_mut().remove_watchpoint(index) {
+                Some(watchpoint) => println!("Deleted watchpoint {index}: {watchpoint}"),
+                None => println!("No watchpoint {index}"),
+            },
+            Command::ListWatchpoints => {
+                for (index, watchpoint) in emulator.memory().watchpoints().iter().enumerate() {
+                    println!("{index}: {watchpoint}");
+                }
+            }
+            Command::Step(count) => {
+                for _ in 0..count {
+                    if let Err(err) = emulator.step() {
+                        println!("{err}");
+                        break;
+                    }
+                    if emulator.take_stop_request() {
+                        break;
+                    }
+                }
+                Self::print_location(emulator);
+            }
+            Command::Next => {
+                let pc = emulator.cpu().PC();
+                let opcode = emulator.memory().peek(pc);
+                if CALL_OPCODES.contains(&opcode) || RST_OPCODES.contains(&opcode) {
+                    let return_addr =
+                        pc.wrapping_add(if RST_OPCODES.contains(&opcode) { 1 } else { 3 });
+                    let sp = emulator.cpu().get_register16(Register16::SP);
+                    Self::run(emulator, |emulator, _| {
+                        emulator.cpu().PC() == return_addr
+                            && emulator.cpu().get_register16(Register16::SP) >= sp
+                    });
+                } else if let Err(err) = emulator.step() {
+                    println!("{err}");
+                }
+                Self::print_location(emulator);
+            }
+            Command::Finish => {
+                let sp = emulator.cpu().get_register16(Register16::SP);
+                Self::run(emulator, |emulator, opcode| {
+                    RET_OPCODES.contains(&opcode)
+                        && emulator.cpu().get_register16(Register16::SP) > sp
+                });
+                Self::print_location(emulator);
+            }
+            Command::Until(location) => {
+                if let Some(addr) = Self::resolve(emulator, &location) {
+                    Self::run(emulator, |emulator, _| emulator.cpu().PC() == addr);
+                    Self::print_location(emulator);
+                }
+            }
+            Command::Continue => {
+                // A watchpoint that fired while stepping has already been reported
+                emulator.take_stop_request();
+                emulator.resume();
+                self.show_location = true;
+            }
+            Command::ReverseStep(count) => match emulator.reverse_step(count as u64) {
+                Ok(()) => Self::print_location(emulator),
+                Err(err) => println!("{err}"),
+            },
+            Command::ReverseContinue => match emulator.reverse_continue() {
+                Ok(stop) => {
+                    match stop {
+                        ReverseStop::Breakpoint(index) => println!("Breakpoint {index}"),
+                        ReverseStop::Watchpoint(hit) => println!(
+                            "Watchpoint {} at ${:04X}, ${:02X} -> ${:02X}",
+                            hit.index, hit.addr, hit.old, hit.new
+                        ),
+                        ReverseStop::StartOfHistory => println!("Reached the start of history"),
+                    }
+                    Self::print_location(emulator);
+                }
+                Err(err) => println!("{err}"),
+            },
+            Command::Registers => Self::print_registers(emulator),
+            Command::Backtrace => emulator.print_backtrace(),
+            Command::SetRegister(register, val) => {
+                match register {
+                    RegisterName::Byte(register) => emulator.cpu_mut().set_reg(register, val as u8),
+                    RegisterName::Word(register) => {
+                        emulator.cpu_mut().set_register16(register, val)
+                    }
+                }
+                Self::print_registers(emulator);
+            }
+            Command::Memory(location, len) => {
+                if let Some(addr) = Self::resolve(emulator, &location) {
+                    Self::print_memory(emulator, addr, len);
+                }
+            }
+            Command::Poke(addr, val) => emulator.memory_mut().poke(addr, val),
+            Command::Disassemble(location, count) => {
+                let pc = emulator.cpu().PC();
+                let instructions = match location {
+                    Some(location) => {
+                        let Some(mut current) = Self::resolve(emulator, &location) else {
+                            return true;
+                        };
+                        (0..count)
+                            .map(|_| {
+                                let instruction = emulator.disassemble(current);
+                                current = current.wrapping_add(instruction.length());
+                                instruction
+                            })
+                            .collect()
+                    }
+                    None => emulator.disassemble_around(pc, 4, count),
+                };
+                for instruction in instructions {
+                    if let Some(label) = emulator.label_at(instruction.addr) {
+                        println!("{label}:");
+                    }
+                    let marker = if instruction.addr == pc { "=>" } else { "  " };
+                    Self::print_instruction(marker, &instruction);
+                }
+            }
+            Command::Help => println!("{}", command::HELP),
+            Command::Quit => return false,
+        }
+        true
+    }
+
+    // Steps until `done` returns true for the state after an instruction, or a breakpoint is hit
+    fn run(emulator: &mut Emulator, mut done: impl FnMut(&Emulator, u8) -> bool) {
+        for _ in 0..RUN_LIMIT {
+            let opcode = emulator.memory().peek(emulator.cpu().PC());
+            if let Err(err) = emulator.step() {
+                println!("{err}");
+                emulator.dump_trace();
+                return;
+            }
+            let stopped = emulator.take_stop_request();
+            if stopped {
+                emulator.dump_trace();
+            }
+            if done(emulator, opcode) || stopped {
+                return;
+            }
+            if emulator.check_breakpoints() {
+                println!(
+                    "Breakpoint at {}",
+                    emulator.describe_addr(emulator.cpu().PC())
+                );
+                emulator.dump_trace();
+                return;
+            }
+        }
+        println!("Gave up after {RUN_LIMIT} instructions");
+    }
+
+    fn print_location(emulator: &Emulator) {
+        let pc = emulator.cpu().PC();
+        println!("{}", emulator.describe_addr(pc));
+        Self::print_instruction("=>", &emulator.disassemble(pc));
+    }
+
+    fn print_instruction(marker: &str, instruction: &DecodedInstruction) {
+        println!(
+            "{marker} {:#06x}: {:<9} {}",
+            instruction.addr,
+            instruction.bytes_str(),
+            instruction.text
+        );
+    }
+
+    fn print_registers(emulator: &Emulator) {
+        let cpu = emulator.cpu();
+        let f = cpu.get_reg(Register::F);
+        let flag = |mask: u8, name: char| if f & mask != 0 { name } else { '-' };
+        println!(
+            "AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X} PC={:04X} flags={}{}{}{} IME={}",
+            cpu.get_register16(Register16::AF),
+            cpu.get_register16(Register16::BC),
+            cpu.get_register16(Register16::DE),
+            cpu.get_register16(Register16::HL),
+            cpu.get_register16(Register16::SP),
+            cpu.get_register16(Register16::PC),
+            flag(0x80, 'Z'),
+            flag(0x40, 'N'),
+            flag(0x20, 'H'),
+            flag(0x10, 'C'),
+            cpu.IME as u8
+        );
+    }
+
+    fn print_memory(emulator: &Emulator, addr: u16, len: u16) {
+        for line_start in (0..len).step_by(16) {
+            let line_addr = addr.wrapping_add(line_start);
+            let bytes: Vec<u8> = (0..16.min(len - line_start))
+                .map(|i| emulator.memory().peek(line_addr.wrapping_add(i)))
+                .collect();
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02X}")).collect();
+            let ascii: String = bytes
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                .collect();
+            println!("{line_addr:04X}: {:<47} |{ascii}|", hex.join(" "));
+        }
+    }
+}
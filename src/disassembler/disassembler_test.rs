@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod tests {
+    use crate::disassembler::{decode, decode_around, decode_labeled, disassemble_bank};
+    use crate::symbols::SymbolTable;
+
+    fn decode_bytes(bytes: &[u8], addr: u16) -> String {
+        decode(|a| bytes[a.wrapping_sub(addr) as usize], addr).text
+    }
+
+    #[test]
+    fn test_resolves_immediates() {
+        assert_eq!(decode_bytes(&[0x01, 0x34, 0x12], 0), "LD BC,$1234");
+        assert_eq!(decode_bytes(&[0x3e, 0x3c], 0), "LD A,$3C");
+        assert_eq!(decode_bytes(&[0xe0, 0x40], 0), "LDH ($FF40),A");
+        assert_eq!(decode_bytes(&[0xea, 0x00, 0xc0], 0), "LD ($C000),A");
+        assert_eq!(decode_bytes(&[0xe8, 0xfe], 0), "ADD SP,-$02");
+        assert_eq!(decode_bytes(&[0xf8, 0x05], 0), "LD HL,SP+$05");
+        assert_eq!(decode_bytes(&[0xff], 0), "RST $38");
+    }
+
+    #[test]
+    fn test_resolves_relative_jumps() {
+        assert_eq!(decode_bytes(&[0x18, 0xfe], 0x150), "JR $0150");
+        assert_eq!(decode_bytes(&[0x20, 0x05], 0x150), "JR NZ,$0157");
+    }
+
+    #[test]
+    fn test_cb_and_illegal_opcodes() {
+        assert_eq!(decode_bytes(&[0xcb, 0x7c], 0), "BIT 7,H");
+        assert_eq!(decode_bytes(&[0xcb, 0x37], 0), "SWAP A");
+        let illegal = decode(|_| 0xd3, 0);
+        assert_eq!(illegal.text, "DB $D3");
+        assert_eq!(illegal.length(), 1);
+    }
+
+    #[test]
+    fn test_labels() {
+        let bytes = [0xcd, 0x00, 0x40];
+        let label = |addr| (addr == 0x4000).then(|| "UpdateJoypad".to_string());
+        let instruction = decode_labeled(|a| bytes[a as usize], 0, label);
+        assert_eq!(instruction.text, "CALL UpdateJoypad");
+        assert_eq!(instruction.target, Some(0x4000));
+    }
+
+    #[test]
+    fn test_decode_around_lines_up_with_addr() {
+        // NOP, LD A,$3C, JP $0150, NOP
+        let bytes = [0x00, 0x3e, 0x3c, 0xc3, 0x50, 0x01, 0x00];
+        let read = |a: u16| bytes.get(a as usize).copied().unwrap_or(0);
+        let around = decode_around(read, |_| None, 3, 2, 1);
+        let addrs: Vec<u16> = around.iter().map(|i| i.addr).collect();
+        assert_eq!(addrs, vec![0, 1, 3, 6]);
+    }
+
+    #[test]
+    fn test_disassemble_bank_generates_labels() {
+        let mut rom = vec![0; 0x8000];
+        // 0x4000: JR $4000 in bank 1
+        rom[0x4000] = 0x18;
+        rom[0x4001] = 0xfe;
+        let lines = disassemble_bank(&rom, 1, &SymbolTable::default());
+        assert_eq!(lines[0], "L01_4000:");
+        assert!(lines[1].ends_with("JR L01_4000"));
+    }
+
+    #[test]
+    fn test_disassemble_bank_uses_symbols() {
+        let mut rom = vec![0; 0x8000];
+        // 0x4000: CALL $0150, JR $4000
+        rom[0x4000..0x4005].copy_from_slice(&[0xcd, 0x50, 0x01, 0x18, 0xfb]);
+        let mut symbols = SymbolTable::default();
+        symbols.insert(0, 0x0150, "Init");
+        symbols.insert(1, 0x4000, "Bank1Loop");
+        let lines = disassemble_bank(&rom, 1, &symbols);
+        assert_eq!(lines[0], "Bank1Loop:");
+        assert!(lines[1].ends_with("CALL Init"));
+        assert!(lines[2].ends_with("JR Bank1Loop"));
+    }
+
+    #[test]
+    fn test_disassemble_bank_zero_operands_past_the_end() {
+        let mut rom = vec![0; 0x8000];
+        // 0x3FFF: LD A,n with n at 0x4000 in bank 1
+        rom[0x3fff] = 0x3e;
+        rom[0x4000] = 0x42;
+        let lines = disassemble_bank(&rom, 0, &SymbolTable::default());
+        assert!(lines.last().unwrap().ends_with("LD A,$42"));
+    }
+}
@@ -0,0 +1,225 @@
+mod disassembler_test;
+mod opcodes;
+
+use std::collections::BTreeSet;
+
+use crate::symbols::SymbolTable;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+
+pub struct DecodedInstruction {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub text: String,
+    // Destination of JP, JR, CALL and RST, used to generate labels
+    pub target: Option<u16>,
+}
+
+impl DecodedInstruction {
+    pub fn length(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    pub fn bytes_str(&self) -> String {
+        self.bytes
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn signed_hex(offset: i8) -> String {
+    if offset < 0 {
+        format!("-${:02X}", offset.unsigned_abs())
+    } else {
+        format!("+${offset:02X}")
+    }
+}
+
+// Replaces the operand placeholder of a template with the actual operand bytes
+fn resolve(
+    template: &str,
+    bytes: &[u8],
+    addr: u16,
+    label: &impl Fn(u16) -> Option<String>,
+) -> (String, Option<u16>) {
+    let is_jump = template.starts_with("JP") || template.starts_with("CALL");
+    let name = |target: u16, hex: String| label(target).unwrap_or(hex);
+    if template.contains("d16") || template.contains("a16") {
+        let word = u16::from_le_bytes([bytes[1], bytes[2]]);
+        if template.contains("d16") {
+            return (template.replace("d16", &format!("${word:04X}")), None);
+        }
+        let text = template.replace("a16", &name(word, format!("${word:04X}")));
+        return (text, is_jump.then_some(word));
+    }
+    if template.contains("d8") {
+        return (template.replace("d8", &format!("${:02X}", bytes[1])), None);
+    }
+    if template.contains("a8") {
+        let high = 0xff00 | bytes[1] as u16;
+        return (
+            template.replace("a8", &name(high, format!("${high:04X}"))),
+            None,
+        );
+    }
+    if template.contains("r8") {
+        let offset = bytes[1] as i8;
+        if template.starts_with("JR") {
+            let target = addr.wrapping_add(2).wrapping_add(offset as u16);
+            let text = template.replace("r8", &name(target, format!("${target:04X}")));
+            return (text, Some(target));
+        }
+        let text = match template {
+            "LD HL,SP+r8" => template.replace("+r8", &signed_hex(offset)),
+            _ => template.replace("r8", signed_hex(offset).trim_start_matches('+')),
+        };
+        return (text, None);
+    }
+    if template.starts_with("RST") {
+        let target = (bytes[0] & 0x38) as u16;
+        return (format!("RST ${target:02X}"), Some(target));
+    }
+    (template.to_string(), None)
+}
+
+pub fn decode(read: impl Fn(u16) -> u8, addr: u16) -> DecodedInstruction {
+    decode_labeled(read, addr, |_| None)
+}
+
+// Decodes the instruction at addr, reading bytes through `read` so it works on live memory and rom dumps alike.
+// `label` is asked for a name whenever an operand refers to an address
+pub fn decode_labeled(
+    read: impl Fn(u16) -> u8,
+    addr: u16,
+    label: impl Fn(u16) -> Option<String>,
+) -> DecodedInstruction {
+    let opcode = read(addr);
+    let length = match opcode {
+        0xcb => 2,
+        _ => opcodes::template(opcode).map_or(1, |template| opcodes::length(&template)),
+    };
+    let bytes: Vec<u8> = (0..length).map(|i| read(addr.wrapping_add(i))).collect();
+    let (text, target) = match opcode {
+        0xcb => (opcodes::template_cb(bytes[1]), None),
+        _ => match opcodes::template(opcode) {
+            Some(template) => resolve(&template, &bytes, addr, &label),
+            None => (format!("DB ${opcode:02X}"), None),
+        },
+    };
+    DecodedInstruction {
+        addr,
+        bytes,
+        text,
+        target,
+    }
+}
+
+// Decoding backwards is ambiguous, so pick the earliest start that lines up exactly with addr
+pub fn decode_around(
+    read: impl Fn(u16) -> u8,
+    label: impl Fn(u16) -> Option<String>,
+    addr: u16,
+    before: usize,
+    after: usize,
+) -> Vec<DecodedInstruction> {
+    let mut result = Vec::new();
+    for distance in (1..=(before as u16 * 3)).rev() {
+        let mut candidate = Vec::new();
+        let mut current = addr.wrapping_sub(distance);
+        while current != addr && addr.wrapping_sub(current) <= distance {
+            let instruction = decode(&read, current);
+            current = current.wrapping_add(instruction.length());
+            candidate.push(instruction);
+        }
+        if current == addr {
+            let skip = candidate.len().saturating_sub(before);
+            result = candidate.into_iter().skip(skip).collect();
+            break;
+        }
+    }
+    let mut result: Vec<DecodedInstruction> = result
+        .iter()
+        .map(|instruction| decode_labeled(&read, instruction.addr, &label))
+        .collect();
+    let mut current = addr;
+    for _ in 0..=after {
+        let instruction = decode_labeled(&read, current, &label);
+        current = current.wrapping_add(instruction.length());
+        result.push(instruction);
+    }
+    result
+}
+
+// Address a rom bank is visible at, bank 0 is fixed at 0x0000 and the others are switched into 0x4000
+pub fn bank_base(bank: usize) -> u16 {
+    if bank == 0 { 0x0000 } else { 0x4000 }
+}
+
+// Bank an address belongs to when disassembling `bank` statically, the rom file says nothing about SRAM banks
+fn static_bank(bank: usize, addr: u16) -> u16 {
+    match addr {
+        0x4000..=0x7fff => bank.max(1) as u16,
+        0xd000..=0xdfff => 1,
+        _ => 0,
+    }
+}
+
+fn auto_label(bank: usize, addr: u16) -> String {
+    format!("L{bank:02X}_{addr:04X}")
+}
+
+// Linear sweep over a whole rom bank. Jump and call targets without a symbol get generated labels
+pub fn disassemble_bank(rom: &[u8], bank: usize, symbols: &SymbolTable) -> Vec<String> {
+    let base = bank_base(bank);
+    // Bank 0 is never mapped at 0x4000, operands running past it read bank 1 like on hardware
+    let offset = bank.max(1) * ROM_BANK_SIZE;
+    let read = |addr: u16| -> u8 {
+        match addr {
+            0x0000..=0x3fff => rom.get(addr as usize).copied().unwrap_or(0xff),
+            0x4000..=0x7fff => rom
+                .get(offset + (addr as usize - 0x4000))
+                .copied()
+                .unwrap_or(0xff),
+            _ => 0xff,
+        }
+    };
+    let end = base as usize + ROM_BANK_SIZE;
+    let sweep = |label: &dyn Fn(u16) -> Option<String>| {
+        let mut instructions = Vec::new();
+        let mut addr = base as usize;
+        while addr < end {
+            let instruction = decode_labeled(read, addr as u16, label);
+            addr += instruction.length() as usize;
+            instructions.push(instruction);
+        }
+        instructions
+    };
+
+    let symbol = |addr: u16| symbols.label(static_bank(bank, addr), addr);
+    let in_bank = |addr: u16| (base as usize..end).contains(&(addr as usize));
+    let targets: BTreeSet<u16> = sweep(&|_| None)
+        .iter()
+        .filter_map(|instruction| instruction.target)
+        .filter(|&target| in_bank(target) && symbol(target).is_none())
+        .collect();
+    let label = |addr: u16| match symbol(addr) {
+        Some(name) => Some(name.to_string()),
+        None => targets.contains(&addr).then(|| auto_label(bank, addr)),
+    };
+
+    let mut lines = Vec::new();
+    for instruction in sweep(&label) {
+        if symbol(instruction.addr).is_some() || targets.contains(&instruction.addr) {
+            lines.push(format!("{}:", label(instruction.addr).unwrap_or_default()));
+        }
+        lines.push(format!(
+            "  {bank:02X}:{:04X}  {:<9} {}",
+            instruction.addr,
+            instruction.bytes_str(),
+            instruction.text
+        ));
+    }
+    lines
+}
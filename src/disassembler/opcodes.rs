@@ -0,0 +1,71 @@
+// Operand placeholders: d8/d16 immediates, a8/a16 addresses, r8 relative jump offsets
+#[rustfmt::skip]
+const OPCODES: [&str; 0x40] = [
+    "NOP", "LD BC,d16", "LD (BC),A", "INC BC", "INC B", "DEC B", "LD B,d8", "RLCA",
+    "LD (a16),SP", "ADD HL,BC", "LD A,(BC)", "DEC BC", "INC C", "DEC C", "LD C,d8", "RRCA",
+    "STOP", "LD DE,d16", "LD (DE),A", "INC DE", "INC D", "DEC D", "LD D,d8", "RLA",
+    "JR r8", "ADD HL,DE", "LD A,(DE)", "DEC DE", "INC E", "DEC E", "LD E,d8", "RRA",
+    "JR NZ,r8", "LD HL,d16", "LD (HL+),A", "INC HL", "INC H", "DEC H", "LD H,d8", "DAA",
+    "JR Z,r8", "ADD HL,HL", "LD A,(HL+)", "DEC HL", "INC L", "DEC L", "LD L,d8", "CPL",
+    "JR NC,r8", "LD SP,d16", "LD (HL-),A", "INC SP", "INC (HL)", "DEC (HL)", "LD (HL),d8", "SCF",
+    "JR C,r8", "ADD HL,SP", "LD A,(HL-)", "DEC SP", "INC A", "DEC A", "LD A,d8", "CCF",
+];
+
+#[rustfmt::skip]
+const OPCODES_HIGH: [&str; 0x40] = [
+    "RET NZ", "POP BC", "JP NZ,a16", "JP a16", "CALL NZ,a16", "PUSH BC", "ADD A,d8", "RST 00H",
+    "RET Z", "RET", "JP Z,a16", "PREFIX CB", "CALL Z,a16", "CALL a16", "ADC A,d8", "RST 08H",
+    "RET NC", "POP DE", "JP NC,a16", "", "CALL NC,a16", "PUSH DE", "SUB d8", "RST 10H",
+    "RET C", "RETI", "JP C,a16", "", "CALL C,a16", "", "SBC A,d8", "RST 18H",
+    "LDH (a8),A", "POP HL", "LD (C),A", "", "", "PUSH HL", "AND d8", "RST 20H",
+    "ADD SP,r8", "JP (HL)", "LD (a16),A", "", "", "", "XOR d8", "RST 28H",
+    "LDH A,(a8)", "POP AF", "LD A,(C)", "DI", "", "PUSH AF", "OR d8", "RST 30H",
+    "LD HL,SP+r8", "LD SP,HL", "LD A,(a16)", "EI", "", "", "CP d8", "RST 38H",
+];
+
+const REGISTERS: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const ALU: [&str; 8] = [
+    "ADD A,", "ADC A,", "SUB ", "SBC A,", "AND ", "XOR ", "OR ", "CP ",
+];
+const ROTATIONS: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+const BIT_OPS: [&str; 3] = ["BIT", "RES", "SET"];
+
+// None for the illegal opcodes that lock up the cpu
+pub fn template(opcode: u8) -> Option<String> {
+    let reg = REGISTERS[(opcode & 7) as usize];
+    let template = match opcode {
+        0x00..=0x3f => OPCODES[opcode as usize].to_string(),
+        0x76 => "HALT".to_string(),
+        0x40..=0x7f => format!("LD {},{reg}", REGISTERS[((opcode >> 3) & 7) as usize]),
+        0x80..=0xbf => format!("{}{reg}", ALU[((opcode >> 3) & 7) as usize]),
+        0xc0..=0xff => OPCODES_HIGH[(opcode - 0xc0) as usize].to_string(),
+    };
+    if template.is_empty() {
+        return None;
+    }
+    Some(template)
+}
+
+pub fn template_cb(opcode: u8) -> String {
+    let reg = REGISTERS[(opcode & 7) as usize];
+    let bit = (opcode >> 3) & 7;
+    match opcode {
+        0x00..=0x3f => format!("{} {reg}", ROTATIONS[bit as usize]),
+        _ => format!("{} {bit},{reg}", BIT_OPS[((opcode >> 6) - 1) as usize]),
+    }
+}
+
+pub fn length(template: &str) -> u16 {
+    if template.contains("d16") || template.contains("a16") {
+        3
+    } else if template.contains("d8")
+        || template.contains("a8")
+        || template.contains("r8")
+        || template == "STOP"
+        || template == "PREFIX CB"
+    {
+        2
+    } else {
+        1
+    }
+}
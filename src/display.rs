@@ -0,0 +1,70 @@
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::{FullscreenType, Window};
+use serde::Deserialize;
+
+use crate::video::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DisplayConfig {
+    // Initial window size in multiples of 160x144
+    pub scale: u32,
+    // Only grow the screen in whole multiples so every pixel is the same size
+    pub integer_scaling: bool,
+    pub fullscreen: bool,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            scale: 4,
+            integer_scaling: true,
+            fullscreen: false,
+        }
+    }
+}
+
+impl DisplayConfig {
+    pub fn window_size(&self) -> (u32, u32) {
+        let scale = self.scale.max(1);
+        (SCREEN_WIDTH as u32 * scale, SCREEN_HEIGHT as u32 * scale)
+    }
+
+    // Largest area of the output keeping the 160:144 aspect ratio, centered with black bars
+    // around it. Outputs smaller than the screen still get the whole screen squeezed in.
+    pub fn screen_rect(&self, (width, height): (u32, u32)) -> Rect {
+        let (screen_width, screen_height) = (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+        let (w, h) = if self.integer_scaling && width >= screen_width && height >= screen_height {
+            let scale = (width / screen_width).min(height / screen_height);
+            (screen_width * scale, screen_height * scale)
+        } else if width * screen_height > height * screen_width {
+            (height * screen_width / screen_height, height)
+        } else {
+            (width, width * screen_height / screen_width)
+        };
+        Rect::new(
+            ((width - w) / 2) as i32,
+            ((height - h) / 2) as i32,
+            w.max(1),
+            h.max(1),
+        )
+    }
+}
+
+// Desktop fullscreen keeps the desktop resolution, the screen is letterboxed into it
+pub fn set_fullscreen(canvas: &mut Canvas<Window>, fullscreen: bool) {
+    let mode = if fullscreen {
+        FullscreenType::Desktop
+    } else {
+        FullscreenType::Off
+    };
+    if let Err(err) = canvas.window_mut().set_fullscreen(mode) {
+        println!("Cannot change fullscreen: {err}");
+    }
+}
+
+pub fn toggle_fullscreen(canvas: &mut Canvas<Window>) {
+    let fullscreen = canvas.window().fullscreen_state() == FullscreenType::Off;
+    set_fullscreen(canvas, fullscreen);
+}
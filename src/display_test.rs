@@ -0,0 +1,42 @@
+#[cfg(test)]
+mod tests {
+    use sdl2::rect::Rect;
+
+    use crate::display::DisplayConfig;
+
+    #[test]
+    fn test_integer_scaling_letterboxes() {
+        let config = DisplayConfig::default();
+        assert_eq!(config.window_size(), (640, 576));
+        assert_eq!(config.screen_rect((640, 576)), Rect::new(0, 0, 640, 576));
+        // 3x fits, the rest becomes bars on every side
+        assert_eq!(config.screen_rect((600, 500)), Rect::new(60, 34, 480, 432));
+        assert_eq!(
+            config.screen_rect((1920, 1080)),
+            Rect::new(400, 36, 1120, 1008)
+        );
+    }
+
+    #[test]
+    fn test_smooth_scaling_keeps_aspect() {
+        let config = DisplayConfig {
+            integer_scaling: false,
+            ..DisplayConfig::default()
+        };
+        assert_eq!(
+            config.screen_rect((1920, 1080)),
+            Rect::new(360, 0, 1200, 1080)
+        );
+        assert_eq!(config.screen_rect((320, 1000)), Rect::new(0, 356, 320, 288));
+    }
+
+    #[test]
+    fn test_small_output_is_squeezed() {
+        let config = DisplayConfig {
+            scale: 0,
+            ..DisplayConfig::default()
+        };
+        assert_eq!(config.window_size(), (160, 144));
+        assert_eq!(config.screen_rect((80, 144)), Rect::new(0, 36, 80, 72));
+    }
+}
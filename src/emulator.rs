@@ -1,16 +1,27 @@
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
 use serde::Deserialize;
 use std::fs;
-use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
 
 use crate::cartridge::Cartridge;
-use crate::cpu::Cpu;
+use crate::cpu::{Cpu, FrameKind};
+use crate::debugger::{Breakpoint, BreakpointConfig, Condition, Hit, Location};
+use crate::disassembler::{self, DecodedInstruction};
+use crate::display::DisplayConfig;
+use crate::error::EmulatorError;
+use crate::filters::FilterConfig;
+use crate::gif_capture::{GifCapture, GifConfig, GifFrame};
+use crate::history::{self, History, Snapshot};
 use crate::input::{Button, Input};
-use crate::memory::{Memory, MemoryType};
-use crate::video;
-use crate::video::GBColor;
+use crate::memory::{self, Access, Memory, WatchHit};
+use crate::palette::{Palette, PaletteSettings};
+use crate::recording::{Recorder, RecordingConfig};
+use crate::rewind::{RewindBuffer, RewindConfig};
+use crate::savestate::StateBuffer;
+use crate::screenshot::{self, ScreenshotConfig};
+use crate::speed::SpeedConfig;
+use crate::symbols::SymbolTable;
+use crate::tracer::{TraceConfig, TraceRecord, Tracer};
+use crate::viewer::ViewerConfig;
 
 #[derive(PartialEq)]
 enum DebugMode {
@@ -25,7 +36,37 @@ pub struct Emulator {
     debug_mode: DebugMode,
     loaded_rom: String,
     step_one: bool,
-    draw_tiles: bool,
+    breakpoints: Vec<Breakpoint>,
+    symbols: SymbolTable,
+    // Set when a watchpoint fires, execution should pause before the next instruction
+    stop_requested: bool,
+    last_watch_hit: Option<WatchHit>,
+    tracer: Option<Tracer>,
+    // Lets execution resume from the breakpoint it is currently sitting on
+    ignore_breakpoint_once: bool,
+    // Instructions executed since the rom was loaded, including halted ones
+    steps: u64,
+    history: Option<History>,
+    rewind: Option<RewindBuffer>,
+    // Custom palettes from the config followed by the built in ones
+    palettes: Vec<Palette>,
+    palette_index: usize,
+    recorder: Option<Recorder>,
+    gif: GifCapture,
+    // Gpu frame count last seen by step, a change means a frame was just finished
+    frames_seen: u64,
+    // The last two emulated frames, oldest first, kept while ghosting is on so it blends
+    // with the frame before even when that one wasn't displayed
+    recent_frames: [Vec<u8>; 2],
+}
+
+const REVERSE_DISABLED: &str = "reverse execution is off, set reverseHistorySeconds in the config";
+
+// Where a reverse continue stopped
+pub(crate) enum ReverseStop {
+    Breakpoint(usize),
+    Watchpoint(WatchHit),
+    StartOfHistory,
 }
 
 #[allow(dead_code)]
@@ -37,8 +78,36 @@ pub struct RunConfig {
     breakpoint_at_pc: u16,
     breakpoint_at_instruction_count: u128,
     pub(crate) print_cpu: bool,
-    print_interrupts: bool,
     use_stepping: bool,
+    #[serde(default)]
+    pub(crate) use_debugger: bool,
+    #[serde(default)]
+    breakpoints: Vec<BreakpointConfig>,
+    #[serde(default)]
+    pub(crate) gdb_port: Option<u16>,
+    #[serde(default)]
+    pub(crate) trace: Option<TraceConfig>,
+    // Seconds of execution kept for reverse debugging, 0 disables it
+    #[serde(default)]
+    pub(crate) reverse_history_seconds: Option<u32>,
+    #[serde(default)]
+    rewind: RewindConfig,
+    #[serde(default)]
+    pub(crate) speed: SpeedConfig,
+    #[serde(default)]
+    palettes: PaletteSettings,
+    #[serde(default)]
+    pub(crate) display: DisplayConfig,
+    #[serde(default)]
+    screenshot: ScreenshotConfig,
+    #[serde(default)]
+    recording: RecordingConfig,
+    #[serde(default)]
+    gif: GifConfig,
+    #[serde(default)]
+    pub(crate) filters: FilterConfig,
+    #[serde(default)]
+    pub(crate) viewers: ViewerConfig,
 }
 
 impl RunConfig {
@@ -49,28 +118,92 @@ impl RunConfig {
 
 impl Emulator {
     pub(crate) fn new(config: RunConfig) -> Emulator {
-        if config.use_doctor {
-            OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true) // Truncate the file, removing existing content
-                .open("blargg_log_instr.txt")
-                .expect("cannot open file");
+        // use_doctor predates the trace config and means a plain gameboy-doctor log
+        let trace_config = match (&config.trace, config.use_doctor) {
+            (Some(trace), _) => Some(trace.clone()),
+            (None, true) => Some(TraceConfig::default()),
+            (None, false) => None,
+        };
+        let tracer = trace_config.and_then(|trace| {
+            let path = trace.path.clone();
+            Tracer::new(trace)
+                .map_err(|err| println!("Tracing disabled, cannot create {path}: {err}"))
+                .ok()
+        });
+        let breakpoints = match config.breakpoint_at_pc {
+            0 => vec![],
+            pc => vec![Breakpoint::new(None, pc)],
+        };
+        // Snapshots cost memory, so they are only taken when someone may want to go back
+        let history = match config.reverse_history_seconds {
+            Some(0) => None,
+            Some(seconds) => Some(History::new(seconds)),
+            None if config.use_debugger || config.gdb_port.is_some() => {
+                Some(History::new(history::DEFAULT_SECONDS))
+            }
+            None => None,
+        };
+        let rewind = config
+            .rewind
+            .enabled
+            .then(|| RewindBuffer::new(config.rewind.clone()));
+        let debug_mode = if config.use_stepping || config.use_debugger {
+            DebugMode::Stepping
+        } else {
+            DebugMode::None
+        };
+        let mut palettes = Vec::new();
+        for palette in &config.palettes.custom {
+            match Palette::from_config(palette) {
+                Ok(palette) => palettes.push(palette),
+                Err(err) => println!("Warning: ignoring {err}"),
+            }
         }
+        palettes.extend(Palette::builtin());
+        let palette_index = match &config.palettes.default {
+            Some(name) => palettes
+                .iter()
+                .position(|p| &p.name == name)
+                .unwrap_or_else(|| {
+                    println!("Warning: unknown palette {name}");
+                    0
+                }),
+            None => 0,
+        };
+        let gif = GifCapture::new(config.gif.clone());
+        let mut memory = Memory::new();
+        memory.set_palette(palettes[palette_index].clone());
         Emulator {
             cpu: Cpu::new(),
-            memory: Memory::new(),
+            memory,
             config,
-            debug_mode: DebugMode::None,
+            debug_mode,
             loaded_rom: "".to_string(),
             step_one: false,
-            draw_tiles: false,
+            breakpoints,
+            symbols: SymbolTable::default(),
+            stop_requested: false,
+            last_watch_hit: None,
+            tracer,
+            ignore_breakpoint_once: false,
+            steps: 0,
+            history,
+            rewind,
+            palettes,
+            palette_index,
+            recorder: None,
+            gif,
+            frames_seen: 0,
+            recent_frames: Default::default(),
         }
     }
-    pub fn load_rom(&mut self, file_path: &String) {
+    pub fn load_rom(&mut self, file_path: &String) -> Result<(), EmulatorError> {
         println!("Loading rom {file_path}");
-        let result = fs::read(file_path).expect("file not found");
-        let cartridge = Cartridge::new(&result);
+        let result = fs::read(file_path)?;
+        let cartridge = Cartridge::new(&result)?;
+        if !cartridge.header_checksum_ok() {
+            println!("Warning: header checksum mismatch, real hardware would refuse to boot");
+        }
         println!(
             "Success: Rom Size {0}KB Ram {1}KB, Cartridge {2:?}",
             cartridge.rom_size / 1024,
@@ -78,81 +211,268 @@ impl Emulator {
             cartridge.cartidge_type
         );
 
-        self.memory.load(result, &cartridge);
+        // Only on the first load, a reset keeps the palette picked since
+        if self.config.palettes.cgb_compatibility
+            && self.loaded_rom.is_empty()
+            && let Some(palette) = Palette::cgb_compatibility(&cartridge)
+            && let Some(index) = self.palettes.iter().position(|p| *p == palette)
+        {
+            println!("Using the CGB palette for this game, {}", palette.name);
+            self.palette_index = index;
+            self.memory.set_palette(palette);
+        }
+        self.memory.load(result, &cartridge)?;
+        let first_load = self.loaded_rom.is_empty();
         self.loaded_rom = file_path.to_string();
+        match SymbolTable::load_for_rom(file_path) {
+            Ok(symbols) => {
+                if !symbols.is_empty() {
+                    println!("Loaded {} symbols", symbols.len());
+                }
+                self.symbols = symbols;
+            }
+            Err(err) => println!("Warning: ignoring symbol file, {err}"),
+        }
+        // Config breakpoints can name symbols, so they are added once the symbols are known
+        if first_load {
+            for config in self.config.breakpoints.clone() {
+                match self.breakpoint_from_config(&config) {
+                    Ok(breakpoint) => self.breakpoints.push(breakpoint),
+                    Err(err) => println!("Warning: ignoring breakpoint {}, {err}", config.at),
+                }
+            }
+        }
+        Ok(())
     }
 
     fn reload_rom(&mut self) {
         let path = &self.loaded_rom.to_string();
         self.reset();
-        self.load_rom(path);
+        if let Err(err) = self.load_rom(path) {
+            println!("Reload failed: {err}");
+        }
     }
 
-    pub fn draw(&mut self, canvas: &mut Canvas<Window>) -> bool {
-        self.memory.draw(canvas)
+    // The finished frame as RGBA rows of SCREEN_WIDTH pixels, None when nothing changed
+    pub fn frame(&mut self) -> Option<&[u8]> {
+        self.memory.frame()
     }
 
-    #[allow(unused)]
-    pub fn draw_debug(&mut self, canvas: &mut Canvas<Window>) -> bool {
-        if !self.draw_tiles {
-            return false;
+    // The screen as it is now in the current palette, even mid frame
+    pub(crate) fn screen(&mut self) -> &[u8] {
+        self.memory.screen()
+    }
+
+    // Timestamped PNG in the configured directory
+    pub(crate) fn save_screenshot(&mut self) -> Result<PathBuf, String> {
+        let config = self.config.screenshot.clone();
+        screenshot::save(self.screen(), &config)
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+        self.memory.reset();
+        self.steps = 0;
+        self.resync_frames();
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.clear();
         }
+    }
 
-        let bg_tiles = self.memory.dump_tiles();
-        let mut draw_tile = |tile: &[[GBColor; 8]; 8] /* Type */, offset_x, offset_y| {
-            for x in 0..8 {
-                for y in 0..8 {
-                    let c = video::get_color(
-                        &tile[y as usize][x as usize],
-                        &video::ColorScheme::BlackWhite,
-                    );
-                    canvas.set_draw_color(c);
-                    match canvas.fill_rect(Rect::new(
-                        offset_x + x * video::PIXEL_SIZE as i32,
-                        offset_y + y * video::PIXEL_SIZE as i32,
-                        video::PIXEL_SIZE as u32,
-                        video::PIXEL_SIZE as u32,
-                    )) {
-                        Ok(_) => {}
-                        Err(err) => panic!("{err}"),
-                    }
-                }
-            }
+    pub(crate) fn save_state(&mut self) -> Vec<u8> {
+        let mut state = StateBuffer::for_saving();
+        self.cpu.sync_state(&mut state);
+        self.memory.sync_state(&mut state);
+        state.finish().unwrap_or_default()
+    }
+    // A state that doesn't fit leaves the machine as it was
+    pub(crate) fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let backup = self.save_state();
+        let mut state = StateBuffer::for_loading(data);
+        self.cpu.sync_state(&mut state);
+        self.memory.sync_state(&mut state);
+        if let Err(err) = state.finish() {
+            let mut state = StateBuffer::for_loading(&backup);
+            self.cpu.sync_state(&mut state);
+            self.memory.sync_state(&mut state);
+            return Err(err);
+        }
+        // Reverse debugging history belongs to the timeline that was just left
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+        self.resync_frames();
+        Ok(())
+    }
+
+    // Called by the frontend after every emulated frame
+    pub(crate) fn record_rewind_frame(&mut self) {
+        if !self.rewind.as_mut().is_some_and(RewindBuffer::frame_due) {
+            return;
+        }
+        let state = self.save_state();
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.push(state);
+        }
+    }
+    // Goes back one snapshot, false once the rewind buffer is used up
+    pub(crate) fn rewind_frame(&mut self) -> bool {
+        let Some(state) = self.rewind.as_mut().and_then(RewindBuffer::pop) else {
+            return false;
         };
+        match self.load_state(&state) {
+            Ok(()) => true,
+            Err(err) => {
+                println!("Rewind failed: {err}");
+                false
+            }
+        }
+    }
 
-        let tilemap = self.memory.debug_get_background_tilemap();
+    pub(crate) fn cycle_palette(&mut self) {
+        self.palette_index = (self.palette_index + 1) % self.palettes.len();
+        let palette = self.palettes[self.palette_index].clone();
+        println!("Palette: {}", palette.name);
+        self.memory.set_palette(palette);
+    }
 
-        let mut x_offset = 0;
-        let mut y_offset = 0;
-        for i in 0..tilemap.len() {
-            if i != 0 && (i % 32 == 0) {
-                println!();
-                x_offset = 0;
-                y_offset += 8 * video::PIXEL_SIZE as i32;
-            }
-            let tile_index = tilemap[i] as usize;
-            draw_tile(&bg_tiles[tile_index], x_offset, y_offset);
-            x_offset += 8 * video::PIXEL_SIZE as i32;
-            print!("{},", tile_index);
-        }
-
-        let mut x_offset = 0;
-        let mut y_offset = 0;
-        for tile in bg_tiles {
-            draw_tile(tile, x_offset, y_offset);
-            x_offset += 8 * video::PIXEL_SIZE as i32;
-            if x_offset > 384 * 2 {
-                x_offset = 0;
-                y_offset += 8 * video::PIXEL_SIZE as i32;
-            }
+    pub(crate) fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+    pub(crate) fn cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+    pub(crate) fn memory(&self) -> &Memory {
+        &self.memory
+    }
+    pub(crate) fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    pub(crate) fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+    // Label for addr in whichever bank is currently mapped there
+    pub(crate) fn label_at(&self, addr: u16) -> Option<String> {
+        self.symbols
+            .label(self.memory.bank_at(addr), addr)
+            .map(str::to_string)
+    }
+    // `$4a31 <Main+$3>` or just `$4a31` without symbols
+    pub(crate) fn describe_addr(&self, addr: u16) -> String {
+        self.describe_banked(self.memory.bank_at(addr), addr)
+    }
+    fn describe_banked(&self, bank: u16, addr: u16) -> String {
+        match self.symbols.describe(bank, addr) {
+            Some(symbol) => format!("${addr:04X} <{symbol}>"),
+            None => format!("${addr:04X}"),
         }
-        self.draw_tiles = false;
-        true
+    }
+    // Innermost first, each frame shows where the function below it was entered from
+    pub(crate) fn backtrace(&self) -> Vec<String> {
+        let mut lines = vec![format!("#0  {}", self.describe_addr(self.cpu.PC()))];
+        for (depth, frame) in self.cpu.call_stack().frames().enumerate() {
+            let entry = match frame.kind {
+                FrameKind::Call => String::new(),
+                FrameKind::Rst => format!(" (RST ${:02X})", frame.target),
+                FrameKind::Interrupt => format!(
+                    " (interrupted by {})",
+                    self.describe_banked(frame.target_bank, frame.target)
+                ),
+            };
+            lines.push(format!(
+                "#{:<2} {}{entry}",
+                depth + 1,
+                self.describe_banked(frame.caller_bank, frame.caller)
+            ));
+        }
+        lines
+    }
+    pub(crate) fn print_backtrace(&self) {
+        for line in self.backtrace() {
+            println!("{line}");
+        }
+    }
+    pub(crate) fn disassemble(&self, addr: u16) -> DecodedInstruction {
+        disassembler::decode_labeled(
+            |addr| self.memory.peek(addr),
+            addr,
+            |addr| self.label_at(addr),
+        )
+    }
+    pub(crate) fn disassemble_around(
+        &self,
+        addr: u16,
+        before: usize,
+        after: usize,
+    ) -> Vec<DecodedInstruction> {
+        disassembler::decode_around(
+            |addr| self.memory.peek(addr),
+            |addr| self.label_at(addr),
+            addr,
+            before,
+            after,
+        )
     }
 
-    pub fn reset(&mut self) {
-        self.cpu.reset();
-        self.memory.reset();
+    pub(crate) fn is_stepping(&self) -> bool {
+        self.debug_mode == DebugMode::Stepping
+    }
+    pub(crate) fn pause(&mut self) {
+        self.debug_mode = DebugMode::Stepping;
+    }
+    pub(crate) fn resume(&mut self) {
+        self.debug_mode = DebugMode::None;
+        self.ignore_breakpoint_once = true;
+    }
+
+    fn breakpoint_from_config(&self, config: &BreakpointConfig) -> Result<Breakpoint, String> {
+        let (bank, addr) = Location::parse(&config.at)?.resolve(&self.symbols)?;
+        let mut breakpoint = Breakpoint::new(bank, addr);
+        if let Some(condition) = &config.condition {
+            breakpoint.condition = Some(Condition::parse(condition)?);
+        }
+        breakpoint.ignore_count = config.ignore_count;
+        breakpoint.log = config.log.clone();
+        Ok(breakpoint)
+    }
+    pub(crate) fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+    pub(crate) fn breakpoint_mut(&mut self, index: usize) -> Option<&mut Breakpoint> {
+        self.breakpoints.get_mut(index)
+    }
+    pub(crate) fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.breakpoints.push(breakpoint);
+        self.breakpoints.len() - 1
+    }
+    pub(crate) fn remove_breakpoint(&mut self, index: usize) -> Option<Breakpoint> {
+        if index >= self.breakpoints.len() {
+            return None;
+        }
+        Some(self.breakpoints.remove(index))
+    }
+    // Counts hits and prints tracepoints, so call it exactly once before each instruction
+    pub(crate) fn check_breakpoints(&mut self) -> bool {
+        // A halted cpu sits on the next instruction for many ticks, it only counts once
+        // that instruction is about to run
+        if !self.cpu.executes_next(&self.memory) {
+            return false;
+        }
+        let pc = self.cpu.PC();
+        let bank = self.memory.bank_at(pc);
+        let mut stop = false;
+        for (index, breakpoint) in self.breakpoints.iter_mut().enumerate() {
+            match breakpoint.check(pc, bank, &self.cpu, &self.memory) {
+                Hit::Miss | Hit::Ignored => {}
+                Hit::Log(message) => println!("Tracepoint {index}: {message}"),
+                Hit::Stop => stop = true,
+            }
+        }
+        stop
     }
 
     fn tick_debug(&mut self) {
@@ -167,21 +487,20 @@ impl Emulator {
                     "Stepping: reached breakpoint instruction count {}",
                     self.config.breakpoint_at_instruction_count
                 );
-            } else if self.cpu.PC() == self.config.breakpoint_at_pc
-                && self.config.breakpoint_at_pc != 0
-            {
+            } else if !self.ignore_breakpoint_once && self.check_breakpoints() {
                 should_step = true;
                 println!(
-                    "Stepping: reached breakpoint PC {}",
-                    self.config.breakpoint_at_pc
+                    "Stepping: reached breakpoint at {}",
+                    self.describe_addr(self.cpu.PC())
                 );
+                self.print_backtrace();
             }
+            self.ignore_breakpoint_once = false;
             if should_step {
                 self.debug_mode = DebugMode::Stepping;
-                self.config.print_cpu = true;
-                if self.config.use_doctor {
-                    self.cpu.write_buffered_doctor_lines();
-                }
+                // The debugger prints registers on demand instead
+                self.config.print_cpu = !self.config.use_debugger;
+                self.dump_trace();
             }
         }
     }
@@ -190,17 +509,12 @@ impl Emulator {
             self.reload_rom();
             return false;
         }
-        if keys.is_new_down(&Button::DumpBgTiles) {
-            //self.memory.dump_bg_tiles();
-            self.draw_tiles = true;
-            return false;
-        }
         if keys.is_new_down(&Button::Step) {
             self.step_one = true;
             return false;
         }
         if keys.is_new_down(&Button::Continue) && self.debug_mode == DebugMode::Stepping {
-            self.debug_mode = DebugMode::None;
+            self.resume();
             self.step_one = false;
         }
         if keys.is_down(&Button::ToggleStepping) {
@@ -223,23 +537,110 @@ impl Emulator {
         true
     }
 
+    // Cycles in real time, which is half the cpu cycles in double speed mode
     pub fn get_last_clock_t(&self) -> u8 {
-        self.cpu.get_clock_t()
+        self.cpu.get_clock_t() >> self.memory.double_speed() as u8
+    }
+
+    fn pressed_buttons(keys: &Input) -> u8 {
+        [
+            (Button::Right, memory::JOYPAD_RIGHT),
+            (Button::Left, memory::JOYPAD_LEFT),
+            (Button::Up, memory::JOYPAD_UP),
+            (Button::Down, memory::JOYPAD_DOWN),
+            (Button::A, memory::JOYPAD_A),
+            (Button::B, memory::JOYPAD_B),
+            (Button::Select, memory::JOYPAD_SELECT),
+            (Button::Start, memory::JOYPAD_START),
+        ]
+        .iter()
+        .filter(|(button, _)| keys.is_down(button))
+        .fold(0, |pressed, (_, bit)| pressed | bit)
     }
 
-    pub fn tick(&mut self, keys: &Input) {
+    pub fn tick(&mut self, keys: &Input) -> Result<(), EmulatorError> {
         if !self.check_debug_input(keys) {
-            return;
+            return Ok(());
+        }
+        let buttons = Self::pressed_buttons(keys);
+        if let Some(history) = self.history.as_mut() {
+            history.record_input(self.steps, buttons);
         }
+        self.memory.set_pressed_buttons(buttons);
         self.tick_debug();
         if self.debug_mode == DebugMode::Stepping && !self.step_one {
-            return;
+            return Ok(());
+        }
+        self.step_one = false;
+        let result = self.step();
+        if self.take_stop_request() {
+            self.debug_mode = DebugMode::Stepping;
+            self.dump_trace();
+        }
+        if result.is_err() {
+            self.dump_trace();
+        }
+        result
+    }
+
+    // Writes out buffered trace lines, in ring buffer mode these are the instructions leading up to now
+    pub(crate) fn dump_trace(&mut self) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.dump(&self.symbols);
+        }
+    }
+
+    pub(crate) fn take_stop_request(&mut self) -> bool {
+        std::mem::take(&mut self.stop_requested)
+    }
+    pub(crate) fn take_last_watch_hit(&mut self) -> Option<WatchHit> {
+        self.last_watch_hit.take()
+    }
+
+    fn report_watch_hit(&self, hit: &WatchHit, pc: u16) {
+        let watchpoint = &self.memory.watchpoints()[hit.index];
+        let access = match hit.access {
+            Access::Read => format!("read ${:04X} = ${:02X}", hit.addr, hit.new),
+            Access::Write => format!(
+                "write ${:04X} ${:02X} -> ${:02X}",
+                hit.addr, hit.old, hit.new
+            ),
+        };
+        let source = if hit.by_dma { " by DMA" } else { "" };
+        println!(
+            "Watchpoint {} ({watchpoint}): {access}{source} at PC {}",
+            hit.index,
+            self.describe_addr(pc)
+        );
+    }
+
+    // One instruction and the time the rest of the hardware spends alongside it, nothing else
+    fn advance(&mut self) -> Vec<WatchHit> {
+        self.cpu.tick(&mut self.memory);
+        // STOP halts the whole system clock, including the PPU and DIV
+        if !self.cpu.is_stopped() {
+            self.memory.tick(self.cpu.get_clock_t());
         }
+        self.steps += 1;
+        self.memory.take_watch_hits()
+    }
+
+    // Executes a single instruction regardless of the debug mode
+    pub(crate) fn step(&mut self) -> Result<(), EmulatorError> {
+        if let Some(history) = self.history.as_mut()
+            && history.wants_snapshot(self.steps)
+        {
+            history.push(Snapshot {
+                step: self.steps,
+                cpu: self.cpu.clone(),
+                memory: self.memory.clone(),
+            });
+        }
+        let pc = self.cpu.PC();
         if self.cpu.operations % 1_000_000 == 0 {
-            let pc = self.cpu.PC();
-            let opcode = self.memory.read_byte(pc);
-            let irq_flag = self.memory.read_byte(0xFF0F);
-            let irq_enable = self.memory.read_byte(0xFFFF);
+            let opcode = self.memory.peek(pc);
+            let irq_flag = self.memory.peek(0xFF0F);
+            let irq_enable = self.memory.peek(0xFFFF);
 
             // NOTE: If your interrupt master enable flag is named something other than `ime`, change it here
             println!(
@@ -247,31 +648,245 @@ impl Emulator {
                 pc, opcode, self.cpu.IME, irq_flag, irq_enable
             );
         }
-        self.cpu.tick(&mut self.memory);
-        self.memory.tick(self.cpu.get_clock_t());
-        self.step_one = false;
+        let disassembly = self.config.print_cpu.then(|| {
+            match self.symbols.describe(self.memory.bank_at(pc), pc) {
+                Some(symbol) => format!("{symbol}: {}", self.disassemble(pc).text),
+                None => self.disassemble(pc).text,
+            }
+        });
+        let was_locked_up = self.cpu.locked_up().is_some();
+        // Ticks spent halted, stopped, locked up or dispatching an interrupt don't run the
+        // instruction at pc, the handler's first one is traced in the next tick
+        let trace_record = self
+            .tracer
+            .as_ref()
+            .filter(|tracer| {
+                self.cpu.executes_next(&self.memory)
+                    && tracer.wants(self.cpu.operations, pc, self.memory.bank_at(pc))
+            })
+            .map(|_| TraceRecord::capture(&self.cpu, &self.memory));
+        let hits = self.advance();
+        if self.memory.frames_finished() != self.frames_seen {
+            self.frames_seen = self.memory.frames_finished();
+            self.frame_finished();
+        }
+        if let (Some(mut record), Some(tracer)) = (trace_record, self.tracer.as_mut()) {
+            record.cycles = self.cpu.get_clock_t();
+            tracer.record(record, &self.symbols);
+        }
+        for hit in &hits {
+            self.report_watch_hit(hit, pc);
+        }
+        self.stop_requested |= !hits.is_empty();
+        if let Some(hit) = hits.last() {
+            self.last_watch_hit = Some(*hit);
+        }
 
-        if self.config.print_cpu {
-            self.cpu.print();
+        if let Some(disassembly) = disassembly {
+            self.cpu.print(&disassembly);
         }
-        if self.config.print_interrupts {
-            print!(
-                "IME: {} IF: {:#04b} IE: {:#04b} ",
-                self.cpu.IME,
-                self.memory.read_byte(0xff0f),
-                self.memory.read_byte(0xffff)
-            );
-            let tac = self.memory.read_byte(0xff07);
-            println!(
-                "TIMA: {} TMA: {} TimerEnabled: {} ClockSelect: {:#04b}",
-                self.memory.read_byte(0xff05),
-                self.memory.read_byte(0xff06),
-                tac & 4 == 4,
-                tac & 0b11
-            );
+        match self.cpu.locked_up() {
+            Some((opcode, pc)) if !was_locked_up => Err(EmulatorError::CpuLockedUp { opcode, pc }),
+            _ => Ok(()),
+        }
+    }
+
+    // After jumping to another point in time the frame counter went back or forth, so the
+    // frames, audio and ghosting history from before don't continue from there
+    fn resync_frames(&mut self) {
+        self.frames_seen = self.memory.frames_finished();
+        self.memory.take_samples();
+        self.recent_frames.iter_mut().for_each(Vec::clear);
+    }
+
+    // The raw frame before the latest finished one, what ghosting blends with
+    pub(crate) fn previous_frame(&self) -> Option<&[u8]> {
+        Some(self.recent_frames[0].as_slice()).filter(|frame| !frame.is_empty())
+    }
+
+    // Called for every emulated frame, however many of them get displayed
+    fn frame_finished(&mut self) {
+        if self.config.filters.ghosting > 0.0 {
+            self.recent_frames.rotate_left(1);
+            self.recent_frames[1].clear();
+            self.recent_frames[1].extend_from_slice(self.memory.screen());
+        }
+        let samples = self.memory.take_samples();
+        if let Some(recorder) = self.recorder.as_mut()
+            && let Err(err) = recorder.write_frame(self.memory.screen(), &samples)
+        {
+            println!("Recording stopped, {err}");
+            self.stop_recording();
         }
-        if self.config.use_doctor {
-            self.cpu.write_doctor();
+        let screen = self.gif.wants_frame().then(|| self.memory.screen());
+        if let Some(frames) = self.gif.frame_finished(screen) {
+            self.save_gif(&frames);
+        }
+    }
+
+    fn save_gif(&self, frames: &[GifFrame]) {
+        match self.gif.save(frames) {
+            Ok(path) => println!("Saved {} frames to {}", frames.len(), path.display()),
+            Err(err) => println!("GIF capture failed: {err}"),
+        }
+    }
+
+    // The last configured seconds, as far as they were kept
+    pub(crate) fn save_last_gif(&self) {
+        self.save_gif(&self.gif.last_seconds());
+    }
+
+    // Starts capturing the next configured seconds, or ends such a capture early
+    pub(crate) fn toggle_gif_capture(&mut self) {
+        if self.gif.is_capturing_next() {
+            let frames = self.gif.stop_next();
+            self.save_gif(&frames);
+        } else {
+            println!("Capturing a GIF");
+            self.gif.start_next();
+        }
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    // Records to path, or a timestamped file in the configured directory
+    pub(crate) fn start_recording(&mut self, path: Option<&Path>) -> Result<(), String> {
+        self.stop_recording();
+        let recorder = match path {
+            Some(path) => Recorder::start(path)?,
+            None => Recorder::start_in(&self.config.recording)?,
+        };
+        println!("Recording started");
+        // Audio from before the recording doesn't belong to its first frame
+        self.memory.take_samples();
+        self.recorder = Some(recorder);
+        Ok(())
+    }
+
+    pub(crate) fn stop_recording(&mut self) {
+        match self.recorder.take().map(Recorder::finish) {
+            Some(Ok((path, frames))) => {
+                println!("Saved recording {} with {frames} frames", path.display())
+            }
+            Some(Err(err)) => println!("Recording failed: {err}"),
+            None => {}
+        }
+    }
+
+    // Restores the latest snapshot at or before step and returns where it was taken
+    fn restore_snapshot(&mut self, step: u64) -> Result<u64, String> {
+        let history = self.history.as_ref().ok_or(REVERSE_DISABLED)?;
+        let snapshot =
+            history
+                .snapshot_before(step)
+                .ok_or_else(|| match history.oldest_step() {
+                    Some(oldest) => format!(
+                        "history only goes back {} instructions",
+                        self.steps - oldest
+                    ),
+                    None => "no history recorded yet".to_string(),
+                })?;
+        self.cpu = snapshot.cpu.clone();
+        self.memory.restore(&snapshot.memory);
+        self.steps = snapshot.step;
+        Ok(snapshot.step)
+    }
+
+    fn apply_recorded_input(&mut self) {
+        if let Some(buttons) = self.history.as_ref().and_then(|h| h.input_at(self.steps)) {
+            self.memory.set_pressed_buttons(buttons);
+        }
+    }
+
+    // Re-executes recorded history from a restored snapshot until target
+    fn replay_to(&mut self, target: u64) {
+        self.memory.set_quiet(true);
+        while self.steps < target {
+            self.apply_recorded_input();
+            self.advance();
+        }
+        self.apply_recorded_input();
+        self.memory.set_quiet(false);
+    }
+
+    fn seek(&mut self, target: u64) -> Result<(), String> {
+        self.restore_snapshot(target)?;
+        self.replay_to(target);
+        self.resync_frames();
+        if let Some(history) = self.history.as_mut() {
+            history.truncate(target);
+        }
+        self.stop_requested = false;
+        self.last_watch_hit = None;
+        Ok(())
+    }
+
+    // Undoes count instructions
+    pub(crate) fn reverse_step(&mut self, count: u64) -> Result<(), String> {
+        if self.steps == 0 {
+            return Err("already at the first instruction".to_string());
+        }
+        self.seek(self.steps.saturating_sub(count))
+    }
+
+    // Runs backwards to the last breakpoint or watchpoint hit before the current instruction.
+    // Each snapshot interval is replayed forwards, newest first, remembering the last stop in it.
+    pub(crate) fn reverse_continue(&mut self) -> Result<ReverseStop, String> {
+        if self.history.is_none() {
+            return Err(REVERSE_DISABLED.to_string());
+        }
+        let end = self.steps;
+        let mut segment_end = end;
+        while segment_end > 0 {
+            if self
+                .history
+                .as_ref()
+                .and_then(|h| h.snapshot_before(segment_end - 1))
+                .is_none()
+            {
+                break;
+            }
+            let start = self.restore_snapshot(segment_end - 1)?;
+            let mut found = None;
+            self.memory.set_quiet(true);
+            while self.steps < segment_end {
+                self.apply_recorded_input();
+                let pc = self.cpu.PC();
+                let bank = self.memory.bank_at(pc);
+                // Same as check_breakpoints, only ticks that run the instruction at pc count
+                if self.cpu.executes_next(&self.memory)
+                    && let Some(index) = self.breakpoints.iter().position(|b| {
+                        b.log.is_none() && b.matches(pc, bank, &self.cpu, &self.memory)
+                    })
+                {
+                    found = Some((self.steps, ReverseStop::Breakpoint(index)));
+                }
+                let hits = self.advance();
+                // A hit by the last instruction is what stopped execution where it is now
+                if let Some(hit) = hits.last()
+                    && self.steps < end
+                {
+                    found = Some((self.steps, ReverseStop::Watchpoint(*hit)));
+                }
+            }
+            self.memory.set_quiet(false);
+            if let Some((step, stop)) = found {
+                self.seek(step)?;
+                if let ReverseStop::Watchpoint(hit) = stop {
+                    self.last_watch_hit = Some(hit);
+                }
+                return Ok(stop);
+            }
+            segment_end = start;
         }
+        let oldest = self
+            .history
+            .as_ref()
+            .and_then(History::oldest_step)
+            .ok_or("no history recorded yet")?;
+        self.seek(oldest)?;
+        Ok(ReverseStop::StartOfHistory)
     }
 }
@@ -0,0 +1,55 @@
+use std::fmt;
+
+use crate::cartridge::{CartridgeError, CartridgeType};
+
+#[derive(Debug)]
+pub enum EmulatorError {
+    Io(std::io::Error),
+    Cartridge(CartridgeError),
+    RomSizeMismatch { header: usize, file: usize },
+    UnsupportedCartridge(CartridgeType),
+    // Illegal opcodes hang the real CPU until it is reset
+    CpuLockedUp { opcode: u8, pc: u16 },
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmulatorError::Io(err) => write!(f, "io error: {err}"),
+            EmulatorError::Cartridge(err) => write!(f, "invalid cartridge: {err}"),
+            EmulatorError::RomSizeMismatch { header, file } => write!(
+                f,
+                "rom file is {file} bytes but the header declares {header} bytes"
+            ),
+            EmulatorError::UnsupportedCartridge(cartridge_type) => {
+                write!(f, "unsupported cartridge type {cartridge_type:?}")
+            }
+            EmulatorError::CpuLockedUp { opcode, pc } => write!(
+                f,
+                "cpu locked up executing illegal opcode {opcode:#04x} at {pc:#06x}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EmulatorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EmulatorError::Io(err) => Some(err),
+            EmulatorError::Cartridge(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for EmulatorError {
+    fn from(err: std::io::Error) -> Self {
+        EmulatorError::Io(err)
+    }
+}
+
+impl From<CartridgeError> for EmulatorError {
+    fn from(err: CartridgeError) -> Self {
+        EmulatorError::Cartridge(err)
+    }
+}
@@ -0,0 +1,268 @@
+use serde::Deserialize;
+
+use crate::video::{BYTES_PER_PIXEL, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum Scaler {
+    #[default]
+    None,
+    // EPX style edge smoothing, keeps the flat colors of the original pixels
+    Scale2x,
+    Scale3x,
+    // Blends edges with their neighbours instead, so diagonals come out anti-aliased
+    Hq2x,
+    // Every pixel as a 3x3 dot with darker gaps, like the DMG's LCD up close
+    DotMatrix,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FilterConfig {
+    // Share of the previous emulated frame mixed into each frame, 0 is off and 0.5
+    // averages the last two frames so flickering sprites show up half transparent
+    pub ghosting: f32,
+    // Approximates how washed out colors looked on the CGB's screen
+    pub color_correction: bool,
+    pub scaler: Scaler,
+}
+
+// Post processing between the core's framebuffer and the screen texture, the core and
+// everything capturing from it keep seeing the plain 160x144 frame
+pub struct Filters {
+    config: FilterConfig,
+    output: Vec<u8>,
+}
+
+impl Filters {
+    pub fn new(config: FilterConfig) -> Filters {
+        Filters {
+            config,
+            output: Vec::new(),
+        }
+    }
+
+    pub fn scale(&self) -> u32 {
+        match self.config.scaler {
+            Scaler::None => 1,
+            Scaler::Scale2x | Scaler::Hq2x => 2,
+            Scaler::Scale3x | Scaler::DotMatrix => 3,
+        }
+    }
+
+    // Size of what apply returns
+    pub fn output_size(&self) -> (u32, u32) {
+        (
+            SCREEN_WIDTH as u32 * self.scale(),
+            SCREEN_HEIGHT as u32 * self.scale(),
+        )
+    }
+
+    // Takes an RGBA frame and the unfiltered one emulated before it, returns the RGBA
+    // image to show, output_size big
+    pub fn apply(&mut self, frame: &[u8], previous: Option<&[u8]>) -> &[u8] {
+        let mut frame = frame.to_vec();
+        if self.config.ghosting > 0.0
+            && let Some(previous) = previous.filter(|previous| previous.len() == frame.len())
+        {
+            blend(&mut frame, previous, self.config.ghosting);
+        }
+        if self.config.color_correction {
+            correct_colors(&mut frame);
+        }
+        self.output = match self.config.scaler {
+            Scaler::None => frame.clone(),
+            Scaler::Scale2x => scale2x(&to_pixels(&frame)),
+            Scaler::Scale3x => scale3x(&to_pixels(&frame)),
+            Scaler::Hq2x => hq2x(&to_pixels(&frame)),
+            Scaler::DotMatrix => dot_matrix(&frame),
+        };
+        &self.output
+    }
+}
+
+// Mixes a share of previous into frame, alpha untouched
+pub fn blend(frame: &mut [u8], previous: &[u8], share: f32) {
+    let share = (share.clamp(0.0, 1.0) * 256.0) as u32;
+    for (i, (current, previous)) in frame.iter_mut().zip(previous).enumerate() {
+        if i % BYTES_PER_PIXEL != 3 {
+            *current = ((*current as u32 * (256 - share) + *previous as u32 * share) / 256) as u8;
+        }
+    }
+}
+
+// The usual CGB LCD approximation, each channel bleeds a little into the others
+pub fn correct_colors(frame: &mut [u8]) {
+    for rgba in frame.chunks_exact_mut(BYTES_PER_PIXEL) {
+        let [r, g, b] = [rgba[0] as u32, rgba[1] as u32, rgba[2] as u32];
+        rgba[0] = ((r * 26 + g * 4 + b * 2) / 32) as u8;
+        rgba[1] = ((g * 24 + b * 8) / 32) as u8;
+        rgba[2] = ((r * 6 + g * 4 + b * 22) / 32) as u8;
+    }
+}
+
+fn to_pixels(frame: &[u8]) -> Vec<u32> {
+    frame
+        .chunks_exact(BYTES_PER_PIXEL)
+        .map(|rgba| u32::from_ne_bytes([rgba[0], rgba[1], rgba[2], rgba[3]]))
+        .collect()
+}
+
+fn to_bytes(pixels: &[u32]) -> Vec<u8> {
+    pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_ne_bytes())
+        .collect()
+}
+
+// Pixel at x + dx, y + dy with the edges repeated outwards
+fn neighbour(pixels: &[u32], x: usize, y: usize, dx: isize, dy: isize) -> u32 {
+    let x = x.saturating_add_signed(dx).min(SCREEN_WIDTH - 1);
+    let y = y.saturating_add_signed(dy).min(SCREEN_HEIGHT - 1);
+    pixels[x + y * SCREEN_WIDTH]
+}
+
+// Writes an n x n block per source pixel from the closure's n * n colors
+fn scale_with<const N: usize>(
+    pixels: &[u32],
+    block: impl Fn(&dyn Fn(isize, isize) -> u32) -> [u32; N],
+    n: usize,
+) -> Vec<u8> {
+    let width = SCREEN_WIDTH * n;
+    let mut out = vec![0; width * SCREEN_HEIGHT * n];
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let colors = block(&|dx, dy| neighbour(pixels, x, y, dx, dy));
+            for (i, color) in colors.into_iter().enumerate() {
+                out[x * n + i % n + (y * n + i / n) * width] = color;
+            }
+        }
+    }
+    to_bytes(&out)
+}
+
+pub fn scale2x(pixels: &[u32]) -> Vec<u8> {
+    scale_with::<4>(
+        pixels,
+        |at| {
+            let (e, b, d, f, h) = (at(0, 0), at(0, -1), at(-1, 0), at(1, 0), at(0, 1));
+            if b == h || d == f {
+                return [e; 4];
+            }
+            [
+                if d == b { d } else { e },
+                if b == f { f } else { e },
+                if d == h { d } else { e },
+                if h == f { f } else { e },
+            ]
+        },
+        2,
+    )
+}
+
+pub fn scale3x(pixels: &[u32]) -> Vec<u8> {
+    scale_with::<9>(
+        pixels,
+        |at| {
+            let (a, b, c) = (at(-1, -1), at(0, -1), at(1, -1));
+            let (d, e, f) = (at(-1, 0), at(0, 0), at(1, 0));
+            let (g, h, i) = (at(-1, 1), at(0, 1), at(1, 1));
+            if b == h || d == f {
+                return [e; 9];
+            }
+            let pick = |cond: bool, color: u32| if cond { color } else { e };
+            [
+                pick(d == b, d),
+                pick((d == b && e != c) || (b == f && e != a), b),
+                pick(b == f, f),
+                pick((d == b && e != g) || (d == h && e != a), d),
+                e,
+                pick((b == f && e != i) || (h == f && e != c), f),
+                pick(d == h, d),
+                pick((d == h && e != i) || (h == f && e != g), h),
+                pick(h == f, f),
+            ]
+        },
+        3,
+    )
+}
+
+// hqx's test for two colors being visibly different, by thresholds on Y, U and V
+fn distinct(a: u32, b: u32) -> bool {
+    let yuv = |pixel: u32| {
+        let [r, g, b, _] = pixel.to_ne_bytes().map(|c| c as i32);
+        [
+            (r * 299 + g * 587 + b * 114) / 1000,
+            (-r * 169 - g * 331 + b * 500) / 1000,
+            (r * 500 - g * 419 - b * 81) / 1000,
+        ]
+    };
+    let ([y1, u1, v1], [y2, u2, v2]) = (yuv(a), yuv(b));
+    (y1 - y2).abs() > 0x30 || (u1 - u2).abs() > 7 || (v1 - v2).abs() > 6
+}
+
+// Weighted average of RGB, alpha from the first pixel
+fn interpolate(pixels: &[(u32, u32)]) -> u32 {
+    let total: u32 = pixels.iter().map(|&(_, weight)| weight).sum();
+    let mut out = pixels[0].0.to_ne_bytes();
+    for (channel, out) in out.iter_mut().enumerate().take(3) {
+        let sum: u32 = pixels
+            .iter()
+            .map(|&(pixel, weight)| pixel.to_ne_bytes()[channel] as u32 * weight)
+            .sum();
+        *out = (sum / total) as u8;
+    }
+    u32::from_ne_bytes(out)
+}
+
+// The corner rules hq2x's 256 case pattern table is built from, rather than the table
+// itself, applied to each quarter of the pixel with its vertical, horizontal and
+// diagonal neighbour. An edge across the corner
+// blends the three, lightly where the diagonal neighbour matches and a line carries on
+// through the corner. A lone different diagonal neighbour is mixed in a quarter and
+// flat areas keep their color.
+pub fn hq2x(pixels: &[u32]) -> Vec<u8> {
+    scale_with::<4>(
+        pixels,
+        |at| {
+            let e = at(0, 0);
+            let corner = |dx, dy| {
+                let (v, h, c) = (at(0, dy), at(dx, 0), at(dx, dy));
+                if distinct(e, v) && distinct(e, h) && !distinct(v, h) {
+                    let weight = if distinct(e, c) { 2 } else { 6 };
+                    interpolate(&[(e, weight), (v, 1), (h, 1)])
+                } else if distinct(e, c) {
+                    interpolate(&[(e, 3), (c, 1)])
+                } else {
+                    e
+                }
+            };
+            [corner(-1, -1), corner(1, -1), corner(-1, 1), corner(1, 1)]
+        },
+        2,
+    )
+}
+
+// 3x3 per pixel, the right column and bottom row a quarter darker
+pub fn dot_matrix(frame: &[u8]) -> Vec<u8> {
+    let width = SCREEN_WIDTH * 3;
+    let mut out = vec![0; width * SCREEN_HEIGHT * 3 * BYTES_PER_PIXEL];
+    for (i, rgba) in frame.chunks_exact(BYTES_PER_PIXEL).enumerate() {
+        let (x, y) = (i % SCREEN_WIDTH, i / SCREEN_WIDTH);
+        for dy in 0..3 {
+            for dx in 0..3 {
+                let at = ((x * 3 + dx) + (y * 3 + dy) * width) * BYTES_PER_PIXEL;
+                let gap = dx == 2 || dy == 2;
+                for channel in 0..3 {
+                    out[at + channel] = if gap {
+                        (rgba[channel] as u32 * 3 / 4) as u8
+                    } else {
+                        rgba[channel]
+                    };
+                }
+                out[at + 3] = rgba[3];
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use crate::emulator::{Emulator, RunConfig};
+    use crate::filters::{
+        FilterConfig, Filters, Scaler, blend, correct_colors, dot_matrix, hq2x, scale2x, scale3x,
+    };
+    use crate::video::{FRAMEBUFFER_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    const WHITE: u32 = 0xFFFF_FFFF;
+    const BLACK: u32 = 0xFF00_0000;
+
+    // White screen with black pixels at the given coordinates
+    fn pixels(black: &[(usize, usize)]) -> Vec<u32> {
+        let mut pixels = vec![WHITE; SCREEN_WIDTH * SCREEN_HEIGHT];
+        for &(x, y) in black {
+            pixels[x + y * SCREEN_WIDTH] = BLACK;
+        }
+        pixels
+    }
+
+    fn pixel_at(image: &[u8], width: usize, x: usize, y: usize) -> u32 {
+        let at = (x + y * width) * 4;
+        u32::from_ne_bytes(image[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_scale2x_rounds_diagonals() {
+        // A diagonal step, the corner between the two black pixels gets filled in
+        let image = scale2x(&pixels(&[(10, 10), (11, 11)]));
+        let width = SCREEN_WIDTH * 2;
+        assert_eq!(image.len(), FRAMEBUFFER_SIZE * 4);
+        assert_eq!(pixel_at(&image, width, 20, 20), BLACK);
+        assert_eq!(pixel_at(&image, width, 21, 21), BLACK);
+        // Top right of (10, 11) touches both black pixels
+        assert_eq!(pixel_at(&image, width, 21, 22), BLACK);
+        assert_eq!(pixel_at(&image, width, 20, 23), WHITE);
+        // Flat areas are plain 2x
+        assert_eq!(pixel_at(&image, width, 0, 0), WHITE);
+    }
+
+    #[test]
+    fn test_scale3x_keeps_lone_pixels_square() {
+        let image = scale3x(&pixels(&[(5, 5)]));
+        let width = SCREEN_WIDTH * 3;
+        assert_eq!(image.len(), FRAMEBUFFER_SIZE * 9);
+        for y in 15..18 {
+            for x in 15..18 {
+                assert_eq!(pixel_at(&image, width, x, y), BLACK);
+            }
+        }
+        assert_eq!(pixel_at(&image, width, 18, 15), WHITE);
+    }
+
+    #[test]
+    fn test_hq2x_blends_diagonals() {
+        let image = hq2x(&pixels(&[(10, 10), (11, 11)]));
+        let width = SCREEN_WIDTH * 2;
+        assert_eq!(image.len(), FRAMEBUFFER_SIZE * 4);
+        // The line carries on through these corners, so they stay mostly black
+        assert_eq!(pixel_at(&image, width, 21, 21), 0xFF3F_3F3F);
+        assert_eq!(pixel_at(&image, width, 22, 22), 0xFF3F_3F3F);
+        // Its ends are rounded off
+        assert_eq!(pixel_at(&image, width, 20, 20), 0xFF7F_7F7F);
+        // The white corner between the two pixels is partly filled in
+        assert_eq!(pixel_at(&image, width, 21, 22), 0xFFBF_BFBF);
+        assert_eq!(pixel_at(&image, width, 20, 23), WHITE);
+        assert_eq!(pixel_at(&image, width, 0, 0), WHITE);
+
+        let filters = Filters::new(FilterConfig {
+            scaler: Scaler::Hq2x,
+            ..FilterConfig::default()
+        });
+        assert_eq!(filters.output_size(), (320, 288));
+    }
+
+    #[test]
+    fn test_dot_matrix_grid() {
+        let frame = vec![0x80; FRAMEBUFFER_SIZE];
+        let image = dot_matrix(&frame);
+        assert_eq!(image[..4], [0x80; 4]);
+        // Third column is the gap
+        assert_eq!(image[8..12], [0x60, 0x60, 0x60, 0x80]);
+    }
+
+    #[test]
+    fn test_blend_and_color_correction() {
+        let mut frame = vec![0xFF, 0x00, 0x00, 0xFF];
+        blend(&mut frame, &[0x00, 0x00, 0xFF, 0xFF], 0.5);
+        assert_eq!(frame, [0x7F, 0x00, 0x7F, 0xFF]);
+
+        let mut frame = vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0xFF];
+        correct_colors(&mut frame);
+        // White stays white, pure red bleeds into blue
+        assert_eq!(frame, [0xFF, 0xFF, 0xFF, 0xFF, 0xCF, 0x00, 0x2F, 0xFF]);
+    }
+
+    #[test]
+    fn test_ghosting_between_frames() {
+        let mut filters = Filters::new(FilterConfig {
+            ghosting: 0.5,
+            ..FilterConfig::default()
+        });
+        assert_eq!(filters.output_size(), (160, 144));
+        let white = vec![0xFF; FRAMEBUFFER_SIZE];
+        let black = vec![0x00; FRAMEBUFFER_SIZE];
+        assert_eq!(filters.apply(&white, None)[0], 0xFF);
+        // Half of the white frame is still visible, alpha comes from the new frame
+        assert_eq!(
+            filters.apply(&black, Some(&white))[..4],
+            [0x7F, 0x7F, 0x7F, 0x00]
+        );
+        // An average of the two raw frames, not a trail of everything shown before
+        assert_eq!(filters.apply(&black, Some(&black))[0], 0x00);
+
+        let filters = Filters::new(FilterConfig {
+            scaler: Scaler::DotMatrix,
+            ..FilterConfig::default()
+        });
+        assert_eq!(filters.output_size(), (480, 432));
+    }
+
+    #[test]
+    fn test_ghosting_follows_every_emulated_frame() {
+        let path = std::env::temp_dir().join("filters_test_ghosting.gb");
+        let mut rom = vec![0; 32 * 1024];
+        // JR -2
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xfe]);
+        std::fs::write(&path, rom).unwrap();
+        let mut config = RunConfig::default();
+        config.filters.ghosting = 0.5;
+        let mut emulator = Emulator::new(config);
+        emulator
+            .load_rom(&path.to_string_lossy().to_string())
+            .unwrap();
+
+        let run_frame = |emulator: &mut Emulator| {
+            let frames = emulator.memory().frames_finished();
+            while emulator.memory().frames_finished() == frames {
+                emulator.step().unwrap();
+            }
+        };
+        run_frame(&mut emulator);
+        assert!(emulator.previous_frame().is_none());
+        // Frames that are never displayed still move the previous frame along
+        run_frame(&mut emulator);
+        // The next frame comes out black
+        emulator.memory_mut().poke(0xFF47, 0xFF);
+        run_frame(&mut emulator);
+        assert_eq!(emulator.previous_frame().unwrap()[..3], [0xFF; 3]);
+        run_frame(&mut emulator);
+        assert_eq!(emulator.previous_frame().unwrap()[..3], [0x00; 3]);
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::Deserialize;
+
+use crate::recording::VBLANK_PERIOD;
+use crate::screenshot::{scale_pixels, timestamped_name};
+use crate::video::{BYTES_PER_PIXEL, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const CPU_HZ: f64 = 4_194_304.0;
+// GIF delays are in hundredths of a second and most viewers slow down anything under
+// 2, so every other frame is kept, about 30 per second
+const FRAME_STEP: u64 = 2;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GifConfig {
+    pub seconds: u32,
+    pub directory: String,
+    pub scale: u32,
+    // Keep the last seconds around all the time so they can be saved after the fact
+    pub keep_last: bool,
+}
+
+impl Default for GifConfig {
+    fn default() -> Self {
+        GifConfig {
+            seconds: 10,
+            directory: ".".to_string(),
+            scale: 1,
+            keep_last: true,
+        }
+    }
+}
+
+// One screen as indices into its own small color table. The DMG only has 4 shades per
+// layer, so even with separate object palettes this never gets near 256 colors.
+#[derive(Clone)]
+pub struct GifFrame {
+    pub indices: Vec<u8>,
+    pub palette: Vec<u8>,
+}
+
+impl GifFrame {
+    pub fn from_rgba(frame: &[u8]) -> GifFrame {
+        let mut colors: Vec<[u8; 3]> = Vec::new();
+        let indices = frame
+            .chunks_exact(BYTES_PER_PIXEL)
+            .map(|rgba| {
+                let rgb = [rgba[0], rgba[1], rgba[2]];
+                match colors.iter().position(|&c| c == rgb) {
+                    Some(index) => index as u8,
+                    None => {
+                        colors.push(rgb);
+                        (colors.len() - 1) as u8
+                    }
+                }
+            })
+            .collect();
+        GifFrame {
+            indices,
+            palette: colors.concat(),
+        }
+    }
+}
+
+// Delay of the nth kept frame in hundredths of a second, rounded so the total stays in
+// step with the real 59.73Hz refresh
+pub fn frame_delay(n: usize) -> u16 {
+    let centis_at = |n: usize| {
+        (n as f64 * FRAME_STEP as f64 * VBLANK_PERIOD as f64 * 100.0 / CPU_HZ).round() as u64
+    };
+    (centis_at(n + 1) - centis_at(n)) as u16
+}
+
+pub fn encode<W: Write>(out: W, frames: &[GifFrame], scale: u32) -> Result<W, String> {
+    let scale = scale.max(1);
+    let (width, height) = (
+        (SCREEN_WIDTH as u32 * scale) as u16,
+        (SCREEN_HEIGHT as u32 * scale) as u16,
+    );
+    let mut encoder = gif::Encoder::new(out, width, height, &[]).map_err(|err| err.to_string())?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(|err| err.to_string())?;
+    for (n, frame) in frames.iter().enumerate() {
+        let (indices, _, _) = scale_pixels(&frame.indices, 1, scale);
+        let mut gif_frame =
+            gif::Frame::from_palette_pixels(width, height, indices, frame.palette.clone(), None);
+        gif_frame.delay = frame_delay(n);
+        encoder
+            .write_frame(&gif_frame)
+            .map_err(|err| err.to_string())?;
+    }
+    encoder.into_inner().map_err(|err| err.to_string())
+}
+
+// Collects frames from the core for "save the last N seconds" and "record the next N
+// seconds"
+pub struct GifCapture {
+    config: GifConfig,
+    frames: VecDeque<GifFrame>,
+    frames_seen: u64,
+    // Kept frames still wanted by a capture of the next seconds
+    remaining: Option<usize>,
+}
+
+impl GifCapture {
+    pub fn new(config: GifConfig) -> GifCapture {
+        GifCapture {
+            config,
+            frames: VecDeque::new(),
+            frames_seen: 0,
+            remaining: None,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        let per_second = CPU_HZ / (VBLANK_PERIOD as f64 * FRAME_STEP as f64);
+        (self.config.seconds as f64 * per_second).round().max(1.0) as usize
+    }
+
+    // Whether the next finished frame gets kept, so the caller can skip building it
+    pub fn wants_frame(&self) -> bool {
+        (self.config.keep_last || self.remaining.is_some())
+            && self.frames_seen.is_multiple_of(FRAME_STEP)
+    }
+
+    // Called for every finished frame, with the screen when wants_frame said so. Returns
+    // the frames of a capture of the next seconds once it is complete.
+    pub fn frame_finished(&mut self, frame: Option<&[u8]>) -> Option<Vec<GifFrame>> {
+        self.frames_seen += 1;
+        let frame = frame?;
+        self.frames.push_back(GifFrame::from_rgba(frame));
+        if self.frames.len() > self.capacity() {
+            self.frames.pop_front();
+        }
+        let remaining = self.remaining.as_mut()?;
+        *remaining -= 1;
+        if *remaining > 0 {
+            return None;
+        }
+        self.remaining = None;
+        Some(self.take())
+    }
+
+    fn take(&mut self) -> Vec<GifFrame> {
+        self.frames.drain(..).collect()
+    }
+
+    pub fn last_seconds(&self) -> Vec<GifFrame> {
+        self.frames.iter().cloned().collect()
+    }
+
+    pub fn is_capturing_next(&self) -> bool {
+        self.remaining.is_some()
+    }
+
+    pub fn start_next(&mut self) {
+        self.frames.clear();
+        self.remaining = Some(self.capacity());
+    }
+
+    // Ends a capture of the next seconds early with what it has so far
+    pub fn stop_next(&mut self) -> Vec<GifFrame> {
+        self.remaining = None;
+        self.take()
+    }
+
+    pub fn save(&self, frames: &[GifFrame]) -> Result<PathBuf, String> {
+        if frames.is_empty() {
+            return Err("no frames captured yet".to_string());
+        }
+        fs::create_dir_all(&self.config.directory)
+            .map_err(|err| format!("{}: {err}", self.config.directory))?;
+        let path = Path::new(&self.config.directory).join(timestamped_name(
+            "capture",
+            "gif",
+            SystemTime::now(),
+        ));
+        let file = File::create(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+        encode(BufWriter::new(file), frames, self.config.scale)
+            .and_then(|mut out| out.flush().map_err(|err| err.to_string()))
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::gif_capture::{GifCapture, GifConfig, GifFrame, encode, frame_delay};
+    use crate::video::{FRAMEBUFFER_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    fn frame(shade: u8) -> Vec<u8> {
+        let mut frame = vec![shade; FRAMEBUFFER_SIZE];
+        frame[..4].copy_from_slice(&[0xFF, 0x00, 0x00, 0xFF]);
+        frame
+    }
+
+    // Feeds n finished frames the way the emulator does
+    fn run(capture: &mut GifCapture, n: usize) -> Option<Vec<GifFrame>> {
+        let mut done = None;
+        for i in 0..n {
+            let screen = frame(i as u8);
+            let screen = capture.wants_frame().then_some(&screen[..]);
+            if let Some(frames) = capture.frame_finished(screen) {
+                done = Some(frames);
+            }
+        }
+        done
+    }
+
+    #[test]
+    fn test_indexed_frame() {
+        let gif_frame = GifFrame::from_rgba(&frame(0x30));
+        assert_eq!(gif_frame.palette, [0xFF, 0x00, 0x00, 0x30, 0x30, 0x30]);
+        assert_eq!(gif_frame.indices[..3], [0, 1, 1]);
+        assert_eq!(gif_frame.indices.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+    }
+
+    #[test]
+    fn test_frame_delays_follow_the_refresh_rate() {
+        // Two frames at 59.73Hz are 3.35 hundredths
+        let delays: Vec<u16> = (0..20).map(frame_delay).collect();
+        assert!(delays.iter().all(|&d| d == 3 || d == 4));
+        assert_eq!(delays.iter().map(|&d| d as u32).sum::<u32>(), 67);
+    }
+
+    #[test]
+    fn test_keeps_last_seconds() {
+        let mut capture = GifCapture::new(GifConfig {
+            seconds: 1,
+            ..GifConfig::default()
+        });
+        assert!(run(&mut capture, 200).is_none());
+        // Every other frame of the last second
+        let frames = capture.last_seconds();
+        assert_eq!(frames.len(), 30);
+        assert_eq!(frames.last().unwrap().palette[3], 198);
+    }
+
+    #[test]
+    fn test_capture_next_seconds() {
+        let mut capture = GifCapture::new(GifConfig {
+            seconds: 1,
+            keep_last: false,
+            ..GifConfig::default()
+        });
+        assert!(run(&mut capture, 10).is_none());
+        assert!(capture.last_seconds().is_empty());
+
+        capture.start_next();
+        assert!(capture.is_capturing_next());
+        let frames = run(&mut capture, 60).unwrap();
+        assert_eq!(frames.len(), 30);
+        assert!(!capture.is_capturing_next());
+        assert!(run(&mut capture, 10).is_none());
+
+        capture.start_next();
+        run(&mut capture, 10);
+        assert_eq!(capture.stop_next().len(), 5);
+    }
+
+    #[test]
+    fn test_encode() {
+        let frames = [
+            GifFrame::from_rgba(&frame(0xFF)),
+            GifFrame::from_rgba(&frame(0x00)),
+        ];
+        let data = encode(Cursor::new(Vec::new()), &frames, 2)
+            .unwrap()
+            .into_inner();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(&data[..]).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (320, 288));
+        let first = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!(first.delay, 3);
+        assert_eq!(
+            first.buffer[..8],
+            [0xFF, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0xFF]
+        );
+        assert_eq!(first.buffer[8..12], [0xFF; 4]);
+        let second = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!(second.buffer[8..12], [0x00, 0x00, 0x00, 0xFF]);
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+}
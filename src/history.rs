@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+
+use crate::cpu::Cpu;
+use crate::memory::Memory;
+
+// Roughly a tenth of a second of instructions, replaying one interval is instant
+pub const SNAPSHOT_INTERVAL: u64 = 100_000;
+pub const SNAPSHOTS_PER_SECOND: usize = 10;
+// Used when a debugger is attached and the config doesn't say otherwise
+pub const DEFAULT_SECONDS: u32 = 10;
+
+// Machine state before the instruction at `step` runs
+pub struct Snapshot {
+    pub step: u64,
+    pub cpu: Cpu,
+    pub memory: Memory,
+}
+
+// Periodic snapshots plus every joypad change between them. Execution is deterministic
+// otherwise, so any recent instruction can be reached again by restoring the snapshot
+// before it and re-executing.
+pub struct History {
+    interval: u64,
+    capacity: usize,
+    snapshots: VecDeque<Snapshot>,
+    // Buttons pressed before the instruction at that step
+    inputs: VecDeque<(u64, u8)>,
+}
+
+impl History {
+    pub fn new(seconds: u32) -> History {
+        History::with_interval(SNAPSHOT_INTERVAL, seconds as usize * SNAPSHOTS_PER_SECOND)
+    }
+
+    pub fn with_interval(interval: u64, capacity: usize) -> History {
+        History {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            snapshots: VecDeque::new(),
+            inputs: VecDeque::new(),
+        }
+    }
+
+    pub fn wants_snapshot(&self, step: u64) -> bool {
+        step.is_multiple_of(self.interval)
+            && self.snapshots.back().is_none_or(|last| last.step < step)
+    }
+
+    pub fn push(&mut self, snapshot: Snapshot) {
+        while self
+            .snapshots
+            .back()
+            .is_some_and(|s| s.step >= snapshot.step)
+        {
+            self.snapshots.pop_back();
+        }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+            // Inputs before the oldest snapshot can never be replayed
+            if let Some(oldest) = self.oldest_step() {
+                while self.inputs.front().is_some_and(|&(step, _)| step < oldest) {
+                    self.inputs.pop_front();
+                }
+            }
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn record_input(&mut self, step: u64, buttons: u8) {
+        if self.inputs.back().is_none_or(|&(_, last)| last != buttons) {
+            self.inputs.push_back((step, buttons));
+        }
+    }
+
+    pub fn input_at(&self, step: u64) -> Option<u8> {
+        self.inputs
+            .iter()
+            .rev()
+            .take_while(|&&(at, _)| at >= step)
+            .find(|&&(at, _)| at == step)
+            .map(|&(_, buttons)| buttons)
+    }
+
+    // Latest snapshot taken at or before step
+    pub fn snapshot_before(&self, step: u64) -> Option<&Snapshot> {
+        self.snapshots.iter().rev().find(|s| s.step <= step)
+    }
+
+    pub fn oldest_step(&self) -> Option<u64> {
+        self.snapshots.front().map(|s| s.step)
+    }
+
+    // Going back discards the recorded future, execution from here on is recorded anew
+    pub fn truncate(&mut self, step: u64) {
+        while self.snapshots.back().is_some_and(|s| s.step > step) {
+            self.snapshots.pop_back();
+        }
+        while self.inputs.back().is_some_and(|&(at, _)| at > step) {
+            self.inputs.pop_back();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.inputs.clear();
+    }
+}
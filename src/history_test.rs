@@ -0,0 +1,143 @@
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{Register, Register16};
+    use crate::debugger::{Breakpoint, Condition};
+    use crate::emulator::{Emulator, ReverseStop, RunConfig};
+    use crate::history::{History, Snapshot};
+    use crate::memory::{Memory, WatchCondition, WatchKind, Watchpoint};
+
+    fn snapshot(step: u64) -> Snapshot {
+        Snapshot {
+            step,
+            cpu: crate::cpu::Cpu::new(),
+            memory: Memory::new(),
+        }
+    }
+
+    // INC A; LD ($C000),A; JR -6
+    fn counting_emulator(name: &str) -> Emulator {
+        let path = std::env::temp_dir().join(name);
+        let mut rom = vec![0; 32 * 1024];
+        rom[0x100..0x106].copy_from_slice(&[0x3c, 0xea, 0x00, 0xc0, 0x18, 0xfa]);
+        std::fs::write(&path, rom).unwrap();
+        let mut config = RunConfig::default();
+        config.reverse_history_seconds = Some(1);
+        let mut emulator = Emulator::new(config);
+        emulator
+            .load_rom(&path.to_string_lossy().to_string())
+            .unwrap();
+        for _ in 0..30 {
+            emulator.step().unwrap();
+        }
+        emulator
+    }
+
+    #[test]
+    fn test_snapshots_and_inputs() {
+        let mut history = History::with_interval(10, 2);
+        assert!(history.wants_snapshot(0));
+        assert!(!history.wants_snapshot(5));
+        history.push(snapshot(0));
+        assert!(!history.wants_snapshot(0));
+        history.record_input(3, 0x01);
+        history.record_input(4, 0x01);
+        history.record_input(12, 0x00);
+        history.push(snapshot(10));
+        history.push(snapshot(20));
+        // Capacity is 2, so the first snapshot and the inputs before the oldest one are gone
+        assert_eq!(history.oldest_step(), Some(10));
+        assert_eq!(history.input_at(3), None);
+        assert_eq!(history.input_at(4), None);
+        assert_eq!(history.input_at(12), Some(0x00));
+        assert_eq!(history.snapshot_before(19).map(|s| s.step), Some(10));
+        assert!(history.snapshot_before(9).is_none());
+
+        history.truncate(11);
+        assert_eq!(history.snapshot_before(30).map(|s| s.step), Some(10));
+        assert_eq!(history.input_at(12), None);
+    }
+
+    #[test]
+    fn test_reverse_step() {
+        let mut emulator = counting_emulator("history_test_step.gb");
+        assert_eq!(emulator.cpu().get_reg(Register::A), 11);
+
+        emulator.reverse_step(3).unwrap();
+        assert_eq!(emulator.cpu().PC(), 0x100);
+        assert_eq!(emulator.cpu().get_reg(Register::A), 10);
+        assert_eq!(emulator.memory().peek(0xc000), 10);
+
+        // Replay is deterministic, stepping forwards ends up where we were
+        for _ in 0..3 {
+            emulator.step().unwrap();
+        }
+        assert_eq!(emulator.cpu().get_reg(Register::A), 11);
+        // Going back past the start stops at the first instruction
+        emulator.reverse_step(100).unwrap();
+        assert_eq!(emulator.cpu().get_reg(Register::A), 1);
+        assert!(emulator.reverse_step(1).is_err());
+    }
+
+    #[test]
+    fn test_reverse_step_keeps_recording_in_sync() {
+        let mut emulator = counting_emulator("history_test_recording.gb");
+        let path = std::env::temp_dir().join("history_test_recording.avi");
+        emulator.start_recording(Some(&path)).unwrap();
+        while emulator.memory().frames_finished() == 0 {
+            emulator.step().unwrap();
+        }
+        // Back before the end of the frame, which is recorded already
+        emulator.reverse_step(10).unwrap();
+        assert_eq!(emulator.memory().frames_finished(), 0);
+        emulator.step().unwrap();
+        emulator.stop_recording();
+
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(u32::from_le_bytes(data[48..52].try_into().unwrap()), 1);
+    }
+
+    #[test]
+    fn test_reverse_continue() {
+        let mut emulator = counting_emulator("history_test_continue.gb");
+        let sp = emulator.cpu().get_register16(Register16::SP);
+        emulator.memory_mut().add_watchpoint(Watchpoint {
+            start: 0xc000,
+            end: 0xc000,
+            kind: WatchKind::Write,
+            condition: WatchCondition::Equals(5),
+        });
+        let mut breakpoint = Breakpoint::new(None, 0x101);
+        breakpoint.condition = Some(Condition::parse("A == 7").unwrap());
+        emulator.add_breakpoint(breakpoint);
+
+        assert!(matches!(
+            emulator.reverse_continue(),
+            Ok(ReverseStop::Breakpoint(0))
+        ));
+        assert_eq!(emulator.cpu().PC(), 0x101);
+        assert_eq!(emulator.cpu().get_reg(Register::A), 7);
+        // Reverse execution doesn't count as hitting the breakpoint
+        assert_eq!(emulator.breakpoints()[0].hits, 0);
+
+        match emulator.reverse_continue() {
+            Ok(ReverseStop::Watchpoint(hit)) => assert_eq!((hit.addr, hit.new), (0xc000, 5)),
+            _ => panic!("expected the watchpoint"),
+        }
+        assert_eq!(emulator.cpu().PC(), 0x104);
+        assert_eq!(emulator.memory().peek(0xc000), 5);
+
+        assert!(matches!(
+            emulator.reverse_continue(),
+            Ok(ReverseStop::StartOfHistory)
+        ));
+        assert_eq!(emulator.cpu().PC(), 0x100);
+        assert_eq!(emulator.cpu().get_register16(Register16::SP), sp);
+    }
+
+    #[test]
+    fn test_reverse_needs_history() {
+        let mut emulator = Emulator::new(RunConfig::default());
+        assert!(emulator.reverse_continue().is_err());
+        assert!(emulator.reverse_step(1).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use sdl2::{event::Event, keyboard::Keycode};
 
@@ -14,10 +14,25 @@ pub enum Button {
     Up,
 
     Reset,
-    DumpBgTiles,
+    ToggleTileViewer,
+    ToggleTilemapViewer,
+    ToggleOamViewer,
+    ToggleRegisterViewer,
     Step,
     ToggleStepping,
     Continue,
+    Rewind,
+    FastForward,
+    ToggleFastForward,
+    ToggleSlowMotion,
+    Pause,
+    FrameAdvance,
+    CyclePalette,
+    ToggleFullscreen,
+    Screenshot,
+    ToggleRecording,
+    SaveGif,
+    CaptureGif,
 
     ToggleBackground,
     ToggleWindow,
@@ -41,10 +56,25 @@ fn get_default_config() -> HashMap<Keycode, Button> {
     keys.insert(Keycode::Return, Button::Start);
 
     keys.insert(Keycode::R, Button::Reset);
-    keys.insert(Keycode::D, Button::DumpBgTiles);
+    keys.insert(Keycode::D, Button::ToggleTileViewer);
+    keys.insert(Keycode::M, Button::ToggleTilemapViewer);
+    keys.insert(Keycode::O, Button::ToggleOamViewer);
+    keys.insert(Keycode::I, Button::ToggleRegisterViewer);
     keys.insert(Keycode::F8, Button::Step);
     keys.insert(Keycode::F9, Button::Continue);
     keys.insert(Keycode::F6, Button::ToggleStepping);
+    keys.insert(Keycode::Backspace, Button::Rewind);
+    keys.insert(Keycode::Tab, Button::FastForward);
+    keys.insert(Keycode::F, Button::ToggleFastForward);
+    keys.insert(Keycode::S, Button::ToggleSlowMotion);
+    keys.insert(Keycode::P, Button::Pause);
+    keys.insert(Keycode::N, Button::FrameAdvance);
+    keys.insert(Keycode::C, Button::CyclePalette);
+    keys.insert(Keycode::F11, Button::ToggleFullscreen);
+    keys.insert(Keycode::F12, Button::Screenshot);
+    keys.insert(Keycode::F10, Button::ToggleRecording);
+    keys.insert(Keycode::G, Button::SaveGif);
+    keys.insert(Keycode::H, Button::CaptureGif);
 
     keys.insert(Keycode::F1, Button::ToggleBackground);
     keys.insert(Keycode::F2, Button::ToggleWindow);
@@ -68,10 +98,25 @@ impl Input {
 
         //System
         keys.insert(Button::Reset, false);
-        keys.insert(Button::DumpBgTiles, false);
+        keys.insert(Button::ToggleTileViewer, false);
+        keys.insert(Button::ToggleTilemapViewer, false);
+        keys.insert(Button::ToggleOamViewer, false);
+        keys.insert(Button::ToggleRegisterViewer, false);
         keys.insert(Button::Step, false);
         keys.insert(Button::Continue, false);
         keys.insert(Button::ToggleStepping, false);
+        keys.insert(Button::Rewind, false);
+        keys.insert(Button::FastForward, false);
+        keys.insert(Button::ToggleFastForward, false);
+        keys.insert(Button::ToggleSlowMotion, false);
+        keys.insert(Button::Pause, false);
+        keys.insert(Button::FrameAdvance, false);
+        keys.insert(Button::CyclePalette, false);
+        keys.insert(Button::ToggleFullscreen, false);
+        keys.insert(Button::Screenshot, false);
+        keys.insert(Button::ToggleRecording, false);
+        keys.insert(Button::SaveGif, false);
+        keys.insert(Button::CaptureGif, false);
 
         keys.insert(Button::ToggleBackground, false);
         keys.insert(Button::ToggleWindow, false);
@@ -116,3 +161,22 @@ impl Input {
         };
     }
 }
+
+// Input reports a key as new until its next event, hotkeys polled once per frame track
+// their presses here instead
+#[derive(Default)]
+pub struct KeyEdges {
+    held: HashSet<Button>,
+}
+
+impl KeyEdges {
+    pub fn pressed(&mut self, input: &Input, button: Button) -> bool {
+        let down = input.is_down(&button);
+        if down {
+            self.held.insert(button)
+        } else {
+            self.held.remove(&button);
+            false
+        }
+    }
+}
@@ -1,22 +1,55 @@
+mod bench;
 mod cartridge;
+mod cartridge_test;
 mod cpu;
+mod debugger;
+mod disassembler;
+mod display;
+mod display_test;
 mod emulator;
+mod error;
+mod filters;
+mod filters_test;
+mod gif_capture;
+mod gif_capture_test;
+mod history;
+mod history_test;
 mod input;
 mod memory;
+mod palette;
+mod palette_test;
+mod recording;
+mod recording_test;
+mod rewind;
+mod rewind_test;
+mod savestate;
+mod screenshot;
+mod screenshot_test;
 mod sdl_wrapper;
+mod speed;
+mod speed_test;
+mod symbols;
+mod symbols_test;
+mod tracer;
+mod tracer_test;
 mod video;
+mod viewer;
 
 extern crate sdl2;
 extern crate serde;
 extern crate serde_json;
 
+use crate::cartridge::Cartridge;
 use crate::emulator::RunConfig;
+use crate::error::EmulatorError;
+use crate::symbols::SymbolTable;
 
-use sdl2::{event::Event, pixels::Color};
+use sdl2::event::{Event, WindowEvent};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
 
 use std::fs::read_to_string;
-use std::time::Duration;
+use std::time::Instant;
 
 pub const FRAME_LENGTH: u32 = 69905;
 
@@ -27,13 +60,105 @@ fn arg_to_bool(arg: &str) -> bool {
     }
 }
 
+fn print_rom_info(path_to_rom: &str) {
+    let data = std::fs::read(path_to_rom).unwrap_or_else(|err| panic!("{path_to_rom}: {err}"));
+    match Cartridge::new(&data) {
+        Ok(cartridge) => println!("{cartridge}"),
+        Err(err) => println!("invalid cartridge header: {err}"),
+    }
+}
+
+// disasm <rom> [first bank] [last bank], defaults to every bank in the rom
+fn print_disassembly(args: &[String]) {
+    let path_to_rom = args.first().expect("missing rom path");
+    let rom = std::fs::read(path_to_rom).unwrap_or_else(|err| panic!("{path_to_rom}: {err}"));
+    let bank_count = rom.len().div_ceil(disassembler::ROM_BANK_SIZE);
+    let parse_bank = |arg: &String| -> usize {
+        arg.parse()
+            .unwrap_or_else(|_| panic!("invalid bank number: {arg}"))
+    };
+    let first_bank = args.get(1).map_or(0, parse_bank);
+    let last_bank = args.get(2).map_or(
+        if args.len() > 1 {
+            first_bank
+        } else {
+            bank_count.saturating_sub(1)
+        },
+        parse_bank,
+    );
+    let symbols = SymbolTable::load_for_rom(path_to_rom).unwrap_or_else(|err| {
+        println!("; ignoring symbol file, {err}");
+        SymbolTable::default()
+    });
+    for bank in first_bank..=last_bank.min(bank_count.saturating_sub(1)) {
+        println!("; bank {bank}");
+        for line in disassembler::disassemble_bank(&rom, bank, &symbols) {
+            println!("{line}");
+        }
+    }
+}
+
+// Runs FRAME_LENGTH cycles, carrying the overshoot into the next frame. Returns false when
+// the debugger asks to quit.
+pub(crate) fn run_frame(
+    emulator: &mut emulator::Emulator,
+    input: &input::Input,
+    mut debugger: Option<&mut debugger::Debugger>,
+    clock_t: &mut u32,
+) -> bool {
+    let target = *clock_t + FRAME_LENGTH;
+    while *clock_t < target {
+        if let Some(debugger) = debugger.as_mut()
+            && emulator.is_stepping()
+            && !debugger.prompt(emulator)
+        {
+            return false;
+        }
+        // Panics inside the core still get a backtrace of the emulated program
+        match panic::catch_unwind(AssertUnwindSafe(|| emulator.tick(input))) {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => {
+                println!("{err}");
+                if let EmulatorError::CpuLockedUp { .. } = err {
+                    emulator.print_backtrace();
+                }
+            }
+            Err(payload) => {
+                emulator.dump_trace();
+                emulator.print_backtrace();
+                panic::resume_unwind(payload);
+            }
+        }
+        *clock_t += emulator.get_last_clock_t() as u32;
+    }
+    *clock_t %= FRAME_LENGTH;
+    true
+}
+
 pub fn main() {
     let first_argument = std::env::args().nth(1).expect("missing first argument");
+    if first_argument == "--info" {
+        let path_to_rom = std::env::args().nth(2).expect("missing rom path");
+        print_rom_info(&path_to_rom);
+        return;
+    }
+    if first_argument == "bench" {
+        bench::run(&std::env::args().skip(2).collect::<Vec<_>>());
+        return;
+    }
+    if first_argument == "screenshot" {
+        screenshot::run(&std::env::args().skip(2).collect::<Vec<_>>());
+        return;
+    }
+    if first_argument == "disasm" {
+        print_disassembly(&std::env::args().skip(2).collect::<Vec<_>>());
+        return;
+    }
     let path_to_arg = Path::new(&first_argument);
     if !path_to_arg.exists() {
         panic!("unknown file: {}", first_argument);
     }
-    let (path_to_rom, config_to_use) = match first_argument.as_str() {
+    let (path_to_rom, mut config_to_use) = match first_argument.as_str() {
         _ if first_argument.ends_with(".gb") => {
             let (p, mut r) = (first_argument, RunConfig::default());
             if let Some(x) = std::env::args().nth(2) {
@@ -52,56 +177,153 @@ pub fn main() {
         }
     };
 
+    if std::env::args().any(|arg| arg == "--debug") {
+        config_to_use.use_debugger = true;
+    }
+    // --gdb or --gdb=PORT
+    for arg in std::env::args() {
+        if arg == "--gdb" {
+            config_to_use.gdb_port = Some(debugger::gdb::DEFAULT_PORT);
+        } else if let Some(port) = arg.strip_prefix("--gdb=") {
+            config_to_use.gdb_port = Some(port.parse().expect("invalid gdb port"));
+        }
+    }
     config_to_use.validate();
 
+    let mut debugger = config_to_use.use_debugger.then(debugger::Debugger::new);
+    let mut gdb = config_to_use.gdb_port.map(|port| {
+        debugger::gdb::GdbServer::bind(port)
+            .unwrap_or_else(|err| panic!("cannot listen on port {port}: {err}"))
+    });
+    let mut speed = speed::SpeedControl::new(config_to_use.speed.clone());
+    let display = config_to_use.display.clone();
+    let mut filters = filters::Filters::new(config_to_use.filters.clone());
+    let open_viewers = config_to_use.viewers.clone();
     let mut sdl = sdl_wrapper::SdlWrapper::new();
     let mut emulator = emulator::Emulator::new(config_to_use);
-    emulator.load_rom(&path_to_rom);
-
-    let show_tiles = false;
-
-    let mut tiles_canvas = None;
-    if show_tiles {
-        let mut debug_canvas = sdl.get_window_canvas("tiles", 384 * 2, 500);
-        debug_canvas.clear();
-        debug_canvas.present();
-        tiles_canvas = Some(debug_canvas);
+    if let Err(err) = emulator.load_rom(&path_to_rom) {
+        println!("Failed to load {path_to_rom}: {err}");
+        return;
+    }
+    // --record=FILE.avi captures from the very first frame
+    if let Some(path) =
+        std::env::args().find_map(|arg| arg.strip_prefix("--record=").map(String::from))
+        && let Err(err) = emulator.start_recording(Some(Path::new(&path)))
+    {
+        println!("Cannot record: {err}");
     }
 
     let mut input = input::Input::new();
-    let mut canvas = sdl.get_window_canvas(
-        "Gameboy Emulator",
-        (video::SCREEN_WIDTH * video::PIXEL_SIZE) as u32,
-        (video::SCREEN_HEIGHT * video::PIXEL_SIZE) as u32,
-    );
+    let (width, height) = display.window_size();
+    let mut canvas = sdl.get_resizable_window_canvas("Gameboy Emulator", width, height);
+    display::set_fullscreen(&mut canvas, display.fullscreen);
+    let texture_creator = canvas.texture_creator();
+    let mut screen = video::create_screen_texture(&texture_creator, filters.output_size());
+    let mut viewers = viewer::Viewers::default();
+    if open_viewers.tiles {
+        viewers.open(&sdl, Box::new(viewer::TileViewer::new()));
+    }
+    if open_viewers.tilemaps {
+        viewers.open(&sdl, Box::new(viewer::TilemapViewer));
+    }
+    if open_viewers.oam {
+        viewers.open(&sdl, Box::new(viewer::OamViewer::new()));
+    }
+    if open_viewers.registers {
+        viewers.open(&sdl, Box::new(viewer::RegisterViewer));
+    }
 
     let mut clock_t: u32 = 0;
+    let mut hotkeys = input::KeyEdges::default();
+    // The last frame is shown again when the window changes while nothing new is drawn
+    let mut redraw = false;
     'running: loop {
         let events = sdl.get_events();
-        for e in events {
-            if let Event::Quit { .. } = e {
-                break 'running;
+        for e in events.into_iter().filter_map(|e| viewers.handle(e)) {
+            match e {
+                Event::Quit { .. }
+                | Event::Window {
+                    win_event: WindowEvent::Close,
+                    ..
+                } => break 'running,
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                } => redraw = true,
+                e => input.consume_keys(e),
             }
-            input.consume_keys(e)
-        }
-        let target = clock_t + FRAME_LENGTH;
-        while clock_t < target {
-            emulator.tick(&input);
-            clock_t += emulator.get_last_clock_t() as u32;
-        }
-        clock_t %= FRAME_LENGTH;
-        canvas.set_draw_color(Color::BLACK);
-        canvas.clear();
-        if emulator.draw(&mut canvas) {
-            canvas.present();
-        }
-        if let Some(mut debug_canvas) = tiles_canvas.as_mut() {
-            debug_canvas.set_draw_color(Color::BLACK);
-            debug_canvas.clear();
-            if emulator.draw_debug(&mut debug_canvas) {
-                debug_canvas.present();
+        }
+        if hotkeys.pressed(&input, input::Button::ToggleFullscreen) {
+            display::toggle_fullscreen(&mut canvas);
+            redraw = true;
+        }
+        if let Some(gdb) = gdb.as_mut() {
+            gdb.poll(&mut emulator);
+        }
+        // Held rewind plays snapshots backwards instead of emulating, and stays on the
+        // oldest one once they run out
+        let rewinding = input.is_down(&input::Button::Rewind);
+        if rewinding {
+            emulator.rewind_frame();
+        }
+        if hotkeys.pressed(&input, input::Button::CyclePalette) {
+            emulator.cycle_palette();
+        }
+        if hotkeys.pressed(&input, input::Button::ToggleRecording) {
+            if emulator.is_recording() {
+                emulator.stop_recording();
+            } else if let Err(err) = emulator.start_recording(None) {
+                println!("Cannot record: {err}");
             }
         }
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 100000));
+        if hotkeys.pressed(&input, input::Button::SaveGif) {
+            emulator.save_last_gif();
+        }
+        if hotkeys.pressed(&input, input::Button::CaptureGif) {
+            emulator.toggle_gif_capture();
+        }
+        if hotkeys.pressed(&input, input::Button::ToggleTileViewer) {
+            viewers.toggle(&sdl, Box::new(viewer::TileViewer::new()));
+        }
+        if hotkeys.pressed(&input, input::Button::ToggleTilemapViewer) {
+            viewers.toggle(&sdl, Box::new(viewer::TilemapViewer));
+        }
+        if hotkeys.pressed(&input, input::Button::ToggleOamViewer) {
+            viewers.toggle(&sdl, Box::new(viewer::OamViewer::new()));
+        }
+        if hotkeys.pressed(&input, input::Button::ToggleRegisterViewer) {
+            viewers.toggle(&sdl, Box::new(viewer::RegisterViewer));
+        }
+        if hotkeys.pressed(&input, input::Button::Screenshot) {
+            match emulator.save_screenshot() {
+                Ok(path) => println!("Saved screenshot {}", path.display()),
+                Err(err) => println!("Screenshot failed: {err}"),
+            }
+        }
+        speed.update(&input);
+        let started = Instant::now();
+        let mut frames = 0;
+        while !rewinding && speed.run_another(frames, started) {
+            if !run_frame(&mut emulator, &input, debugger.as_mut(), &mut clock_t) {
+                break 'running;
+            }
+            emulator.record_rewind_frame();
+            frames += 1;
+        }
+        if let Some(frame) = emulator.frame().map(<[u8]>::to_vec) {
+            video::upload_frame(
+                &mut screen,
+                filters.apply(&frame, emulator.previous_frame()),
+            );
+            redraw = true;
+        }
+        if redraw {
+            let target = display.screen_rect(canvas.output_size().unwrap_or((width, height)));
+            video::present_screen(&mut canvas, &screen, target);
+            redraw = false;
+        }
+        viewers.present(&emulator);
+        speed.wait();
     }
+    emulator.stop_recording();
 }
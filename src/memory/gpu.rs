@@ -1,6 +1,7 @@
 use super::MemoryType;
-use crate::video::{self, GBColor, SCREEN_HEIGHT, SCREEN_WIDTH};
-use sdl2::{rect::Rect, render::Canvas, video::Window};
+use crate::palette::Palette;
+use crate::savestate::StateBuffer;
+use crate::video::{self, GBColor, SCREEN_WIDTH};
 
 #[derive(Debug, PartialEq)]
 pub enum TickMode {
@@ -24,6 +25,9 @@ impl TickMode {
 
 type Tile16 = [[GBColor; 8]; 8];
 
+// Objects the OAM scan keeps for a line, the rest covering it are not drawn
+pub(crate) const OBJECTS_PER_LINE: usize = 10;
+
 fn make_tile16() -> Tile16 {
     [[GBColor::White; 8]; 8]
 }
@@ -55,13 +59,14 @@ impl ObjData {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum PaletteType {
     Background,
     Object0,
     Object1,
 }
 
+#[derive(Clone)]
 pub struct Gpu {
     vram: [u8; 0x2000],
     objects: [ObjData; 40],
@@ -69,6 +74,8 @@ pub struct Gpu {
     tiles: [Tile16; 384],
     clock: u32,
     can_draw: bool,
+    // Counts vblanks, lets the core notice every finished frame
+    frames_finished: u64,
     //Video registers
     //FF40
     lcdc: u8,
@@ -101,6 +108,11 @@ pub struct Gpu {
     //FF49
     object_palette1: [GBColor; 4],
     pixels: [GBColor; video::SCREEN_WIDTH * video::SCREEN_HEIGHT],
+    // PaletteType each pixel was drawn with, so layers can be colored separately
+    sources: [u8; video::SCREEN_WIDTH * video::SCREEN_HEIGHT],
+    palette: Palette,
+    // RGBA copy of pixels handed to the frontend, rebuilt once per finished frame
+    framebuffer: Box<[u8]>,
     current_window_line: u8,
     show_background: bool,
     show_window: bool,
@@ -124,8 +136,8 @@ impl MemoryType for Gpu {
                 0x47 => self.bg_palette,
                 0x48 => self.obj_palette0,
                 0x49 => self.obj_palette1,
-                0x4a => self.window_x,
-                0x4b => self.window_y,
+                0x4a => self.window_y,
+                0x4b => self.window_x,
                 0x46 => 0, // DMA
                 _ => panic!("video flags"),
             },
@@ -209,6 +221,7 @@ impl Gpu {
             oam: [0; 0xA0],
             tiles: [make_tile16(); 384],
             can_draw: false,
+            frames_finished: 0,
             lcdc: 0x91,
             lcdc_stat: 0,
             scroll_x: 0,
@@ -222,6 +235,9 @@ impl Gpu {
             obj_palette1: 0,
             dma_write_addr: 0,
             pixels: [GBColor::White; (video::SCREEN_WIDTH * video::SCREEN_HEIGHT)],
+            sources: [PaletteType::Background as u8; video::SCREEN_WIDTH * video::SCREEN_HEIGHT],
+            palette: Palette::grayscale(),
+            framebuffer: vec![0; video::FRAMEBUFFER_SIZE].into_boxed_slice(),
             background_palette: [GBColor::White; 4],
             object_palette0: [GBColor::White; 4],
             object_palette1: [GBColor::White; 4],
@@ -235,6 +251,54 @@ impl Gpu {
         }
     }
 
+    // The layer toggles are viewer settings and stay as they are
+    pub(crate) fn sync_state(&mut self, state: &mut StateBuffer) {
+        state.bytes(&mut self.vram);
+        state.bytes(&mut self.oam);
+        for object in &mut self.objects {
+            state.u8(&mut object.x);
+            state.u8(&mut object.y);
+            state.u8(&mut object.pattern_num);
+            state.bool(&mut object.priority);
+            state.bool(&mut object.y_flip);
+            state.bool(&mut object.x_flip);
+            state.bool(&mut object.pal_num);
+            state.usize(&mut object.obj_index);
+        }
+        for row in self.tiles.iter_mut().flatten() {
+            state.colors(row);
+        }
+        state.u32(&mut self.clock);
+        for register in [
+            &mut self.lcdc,
+            &mut self.lcdc_stat,
+            &mut self.scroll_x,
+            &mut self.scroll_y,
+            &mut self.vert_line,
+            &mut self.vert_line_cp,
+            &mut self.dma_write_addr,
+            &mut self.window_y,
+            &mut self.window_x,
+            &mut self.bg_palette,
+            &mut self.obj_palette0,
+            &mut self.obj_palette1,
+            &mut self.current_window_line,
+        ] {
+            state.u8(register);
+        }
+        state.colors(&mut self.background_palette);
+        state.colors(&mut self.object_palette0);
+        state.colors(&mut self.object_palette1);
+        state.colors(&mut self.pixels);
+        state.bytes(&mut self.sources);
+        state.u128(&mut self.operations);
+        state.bool(&mut self.vram_checked);
+        // Show the loaded frame right away
+        if state.is_loading() {
+            self.can_draw = true;
+        }
+    }
+
     pub(crate) fn debug_toggle_background(&mut self) {
         self.show_background = !self.show_background;
     }
@@ -282,9 +346,6 @@ impl Gpu {
         Self::is_bit_set(self.lcdc, 7)
     }
 
-    pub(crate) fn get_pixel(&self, x: usize, y: usize) -> GBColor {
-        self.pixels[x + y * SCREEN_WIDTH]
-    }
     fn set_mode(&mut self, val: TickMode) {
         let old = self.mode();
         if old != val {
@@ -350,28 +411,50 @@ impl Gpu {
         };
     }
 
-    pub fn draw(&mut self, canvas: &mut Canvas<Window>) -> bool {
+    pub(crate) fn frames_finished(&self) -> u64 {
+        self.frames_finished
+    }
+
+    pub(crate) fn palette(&self) -> &Palette {
+        &self.palette
+    }
+    // Recolors the current frame too
+    pub(crate) fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.can_draw = true;
+    }
+
+    // None until a new frame is finished, and while the LCD is off
+    pub fn frame(&mut self) -> Option<&[u8]> {
         if !self.can_draw || !self.lcd_operation() {
-            return false;
+            return None;
         }
         self.can_draw = false;
-        let scheme = &video::ColorScheme::BlackWhite;
-        for i in 0..SCREEN_WIDTH * SCREEN_HEIGHT {
-            let color = video::get_color(&self.pixels[i], scheme);
-            canvas.set_draw_color(color);
-            let x = (i % SCREEN_WIDTH) * video::PIXEL_SIZE;
-            let y = (i / SCREEN_WIDTH) * video::PIXEL_SIZE;
-            match canvas.fill_rect(Rect::new(
-                x as i32,
-                y as i32,
-                video::PIXEL_SIZE as u32,
-                video::PIXEL_SIZE as u32,
-            )) {
-                Ok(_) => {}
-                Err(_err) => panic!("{_err}"),
-            }
+        self.render_framebuffer();
+        Some(&self.framebuffer)
+    }
+
+    // The frame as it is now, for screenshots
+    pub(crate) fn screen(&mut self) -> &[u8] {
+        self.render_framebuffer();
+        &self.framebuffer
+    }
+
+    fn render_framebuffer(&mut self) {
+        for ((pixel, source), rgba) in self
+            .pixels
+            .iter()
+            .zip(self.sources)
+            .zip(self.framebuffer.chunks_exact_mut(video::BYTES_PER_PIXEL))
+        {
+            let colors = match source {
+                s if s == PaletteType::Object0 as u8 => &self.palette.obj0,
+                s if s == PaletteType::Object1 as u8 => &self.palette.obj1,
+                _ => &self.palette.bg,
+            };
+            let [r, g, b] = colors[*pixel as usize];
+            rgba.copy_from_slice(&[r, g, b, 0xFF]);
         }
-        true
     }
 
     // returns true if LYC=LY interrupt is triggered
@@ -425,6 +508,7 @@ impl Gpu {
                 if self.vert_line >= 144 {
                     self.set_mode(TickMode::Vblank);
                     self.can_draw = true;
+                    self.frames_finished += 1;
                     interrupts |= 0x1;
                     //WriteTileDataToFile("../tiledata.txt");
                     //WriteTileMapToFile("../tilemap.txt");
@@ -502,6 +586,7 @@ impl Gpu {
 
             // Plot the pixel to canvas…
             self.pixels[canvasoffs as usize] = pal_color;
+            self.sources[canvasoffs as usize] = PaletteType::Background as u8;
 
             canvasoffs += 1;
 
@@ -547,6 +632,7 @@ impl Gpu {
                 || (pal_color != GBColor::White && !self.use_zero_as_window_solid())
             {
                 self.pixels[canvasoffs] = pal_color;
+                self.sources[canvasoffs] = PaletteType::Background as u8;
             }
 
             canvasoffs += 1;
@@ -569,10 +655,10 @@ impl Gpu {
         let height = if use_8x16 { 16 } else { 8 };
 
         let mut filtered: Vec<&ObjData> = self
-            .objects
-            .iter()
-            .filter(|&&o| o.x != 0 || o.y != 0)
-            .filter(|&&o| cur_line >= (o.y as i32 - 16) && cur_line < ((o.y as i32 - 16) + height))
+            .objects_on_line(self.vert_line)
+            .into_iter()
+            .take(OBJECTS_PER_LINE)
+            .map(|index| &self.objects[index])
             .collect();
 
         // 1. First, sort the sprites properly
@@ -611,10 +697,10 @@ impl Gpu {
                     let sprite_x = if obj.x_flip { 7 - x } else { x };
                     let mut sprite_y = (if obj.y_flip { (height - 1) - y } else { y }) as usize;
 
-                    let palette = match obj.pal_num {
-                        false => self.object_palette0,
+                    let (palette, source) = match obj.pal_num {
+                        false => (self.object_palette0, PaletteType::Object0),
 
-                        true => self.object_palette1,
+                        true => (self.object_palette1, PaletteType::Object1),
                     };
 
                     // Handle bottom half of 8x16 sprites
@@ -643,6 +729,7 @@ impl Gpu {
                     // Using self.background_palette[0] is more accurate to hardware than GBColor::White
                     if !obj.priority || self.pixels[pos] == self.background_palette[0] {
                         self.pixels[pos] = pal_color;
+                        self.sources[pos] = source as u8;
                     }
 
                     // We drew the highest priority sprite for this pixel, break out to the next line_x
@@ -653,24 +740,19 @@ impl Gpu {
         }
     }
 
-    pub(crate) fn get_tiles(&self) -> &[Tile16; 384] {
-        &self.tiles
+    // Indices of the objects covering a line in OAM order, of which the hardware only
+    // draws the first OBJECTS_PER_LINE
+    pub(crate) fn objects_on_line(&self, line: u8) -> Vec<usize> {
+        let height = if self.use_8x16_sprites() { 16 } else { 8 };
+        (0..self.objects.len())
+            .filter(|&index| {
+                let top = self.objects[index].y as i32 - 16;
+                (top..top + height).contains(&(line as i32))
+            })
+            .collect()
     }
 
-    pub(crate) fn debug_get_background_tilemap(&self) -> [u8; 32 * 32] {
-        let mut map = [0; 32 * 32];
-        let mut map_offs: usize = 0x1800;
-        if self.background_tile_table_address() {
-            map_offs = 0x1C00
-        }
-        for i in 0..(32 * 32) {
-            let raw_tile = self.vram[map_offs + i] as i16;
-            if !self.tile_pattern_table_address() && raw_tile < 128 {
-                map[i] = (raw_tile + 256) as u8;
-            } else {
-                map[i] = raw_tile as u8;
-            }
-        }
-        map
+    pub(crate) fn get_tiles(&self) -> &[Tile16; 384] {
+        &self.tiles
     }
 }
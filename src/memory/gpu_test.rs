@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod tests {
     use crate::{
-        memory::{gpu::TickMode, Memory, MemoryType},
+        memory::{gpu::TickMode, Memory, MemoryType, OBJECTS_PER_LINE},
         video::{self},
     };
 
@@ -59,7 +59,7 @@ mod tests {
         memory.write_byte(0x800E, 0b00000000);
         memory.write_byte(0x800F, 0b11111111);
 
-        let dumped = memory.dump_tiles();
+        let dumped = memory.tiles();
         let tile = dumped[0];
 
         let vals: Vec<u8> = t
@@ -80,32 +80,90 @@ mod tests {
         let mut memory = Memory::new();
         memory.write_byte(0x8000, 0xff);
         memory.write_byte(0x8001, 0xff);
-        let dumped = memory.dump_tiles();
+        let dumped = memory.tiles();
         for x in 0..8 {
             assert_eq!(dumped[0][0][x], video::GBColor::Black)
         }
 
         memory.write_byte(0x8000, 0xff);
         memory.write_byte(0x8001, 0x00);
-        let dumped = memory.dump_tiles();
+        let dumped = memory.tiles();
         for x in 0..8 {
             assert_eq!(dumped[0][0][x], video::GBColor::LightGray)
         }
 
         memory.write_byte(0x8000, 0x00);
         memory.write_byte(0x8001, 0xff);
-        let dumped = memory.dump_tiles();
+        let dumped = memory.tiles();
         for x in 0..8 {
             assert_eq!(dumped[0][0][x], video::GBColor::DarkGray)
         }
         memory.write_byte(0x8000, 0x00);
         memory.write_byte(0x8001, 0x00);
-        let dumped = memory.dump_tiles();
+        let dumped = memory.tiles();
         for x in 0..8 {
             assert_eq!(dumped[0][0][x], video::GBColor::White)
         }
     }
 
+    #[test]
+    fn test_frame_once_per_vblank() {
+        let mut memory = Memory::new();
+        memory.reset();
+        assert!(memory.frame().is_none());
+        let mut ticks = 0;
+        while memory.frame().is_none() {
+            memory.tick(4);
+            ticks += 1;
+            assert!(ticks < 100_000, "no frame finished");
+        }
+        // Only handed out once per finished frame
+        assert!(memory.frame().is_none());
+
+        memory.tick(4);
+        while memory.frame().is_none() {
+            memory.tick(4);
+        }
+        memory.write_byte(0xFF40, 0x11);
+        assert!(memory.frame().is_none(), "lcd is off");
+    }
+
+    #[test]
+    fn test_frame_layout() {
+        let mut memory = Memory::new();
+        memory.reset();
+        let frame = loop {
+            memory.tick(4);
+            if let Some(frame) = memory.frame() {
+                break frame.to_vec();
+            }
+        };
+        assert_eq!(frame.len(), video::FRAMEBUFFER_SIZE);
+        assert_eq!(frame[..4], [0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_objects_on_line() {
+        let mut memory = Memory::new();
+        memory.reset();
+        // 12 objects covering lines 0-7 and one below them
+        for obj in 0..13u16 {
+            let y = if obj == 5 { 40 } else { 16 };
+            memory.write_byte(0xFE00 + obj * 4, y);
+            memory.write_byte(0xFE01 + obj * 4, 8 + obj as u8);
+        }
+        let on_line = memory.gpu.objects_on_line(7);
+        assert_eq!(on_line.len(), 12);
+        assert!(!on_line.contains(&5));
+        assert_eq!(on_line[..OBJECTS_PER_LINE], [0, 1, 2, 3, 4, 6, 7, 8, 9, 10]);
+        assert!(memory.gpu.objects_on_line(8).is_empty());
+
+        // 8x16 objects reach 8 lines further down
+        memory.write_byte(0xFF40, 0x95);
+        assert_eq!(memory.gpu.objects_on_line(8).len(), 12);
+        assert_eq!(memory.gpu.objects_on_line(24), [5]);
+    }
+
     #[test]
     fn test_unsigned_to_tile_index() {
         let _some_value: u8 = 134;
@@ -3,19 +3,36 @@ mod gpu_test;
 mod mem_test;
 mod rom;
 mod sound;
+mod sound_test;
+mod watchpoint;
+mod watchpoint_test;
 
-use std::{fs::File, io::Write, ops::Shl};
-
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use std::ops::Shl;
 
 use crate::{
-    cartridge::Cartridge,
-    video::{self, GBColor, SCREEN_HEIGHT, SCREEN_WIDTH},
+    cartridge::{Cartridge, CgbSupport},
+    error::EmulatorError,
+    palette::Palette,
+    savestate::StateBuffer,
+    video,
 };
 
+pub(crate) use self::gpu::OBJECTS_PER_LINE;
+pub(crate) use self::sound::SAMPLE_RATE;
+use self::watchpoint::Watchpoints;
+pub use self::watchpoint::{Access, WatchCondition, WatchHit, WatchKind, Watchpoint};
 use self::{gpu::Gpu, rom::Rom, sound::Sound};
 
+// Button bits for Memory::set_pressed_buttons, low nibble matches the P1 direction lines
+pub const JOYPAD_RIGHT: u8 = 1 << 0;
+pub const JOYPAD_LEFT: u8 = 1 << 1;
+pub const JOYPAD_UP: u8 = 1 << 2;
+pub const JOYPAD_DOWN: u8 = 1 << 3;
+pub const JOYPAD_A: u8 = 1 << 4;
+pub const JOYPAD_B: u8 = 1 << 5;
+pub const JOYPAD_SELECT: u8 = 1 << 6;
+pub const JOYPAD_START: u8 = 1 << 7;
+
 pub trait MemoryType {
     fn read_byte(&self, addr: u16) -> u8;
     fn write_byte(&mut self, addr: u16, val: u8);
@@ -32,6 +49,7 @@ pub trait MemoryType {
     }
 }
 
+#[derive(Clone)]
 pub struct Memory {
     rom: Rom,
     gpu: Gpu,
@@ -41,6 +59,9 @@ pub struct Memory {
     bios: [u8; 0x0100],
     in_bios: bool,
 
+    cgb_mode: bool,
+    pressed_buttons: u8,
+
     //special registers
     // FF00
     joypad: u8,
@@ -56,12 +77,43 @@ pub struct Memory {
     timer_modulo: u8,
     //FF07
     timer_control: u8,
+    //FF4D
+    speed_switch: u8,
 
     timer_and_gate_previous: bool,
+
+    watchpoints: Watchpoints,
+    // Lets watchpoint hits tell OAM DMA apart from cpu accesses
+    dma_active: bool,
+    // Replayed instructions already printed their serial output the first time
+    quiet: bool,
 }
 
 impl MemoryType for Memory {
     fn read_byte(&self, addr: u16) -> u8 {
+        let val = self.peek(addr);
+        if self.watchpoints.watches(addr, Access::Read) {
+            self.watchpoints
+                .check(addr, Access::Read, val, val, self.dma_active);
+        }
+        val
+    }
+
+    fn write_byte(&mut self, addr: u16, val: u8) {
+        if !self.watchpoints.watches(addr, Access::Write) {
+            self.write_unwatched(addr, val);
+            return;
+        }
+        let old = self.peek(addr);
+        self.write_unwatched(addr, val);
+        self.watchpoints
+            .check(addr, Access::Write, old, val, self.dma_active);
+    }
+}
+
+impl Memory {
+    // Reads without triggering watchpoints, for the debugger and other tooling
+    pub(crate) fn peek(&self, addr: u16) -> u8 {
         match addr {
             0x0000..=0x00ff => {
                 if self.in_bios {
@@ -77,7 +129,7 @@ impl MemoryType for Memory {
                 //println!("Reading from empty but unusable for I/O: 0xfea0-0xff00 {}",addr);
                 0
             }
-            0xff00 => self.joypad,
+            0xff00 => self.joypad_register(),
             0xff01 => self.serial_transfer_data,
             0xff02 => self.serial_transfer_control,
             0xff04 => (self.div_register >> 8) as u8,
@@ -87,6 +139,7 @@ impl MemoryType for Memory {
             0xff0f => self.interupt_flag | 0xE0,
             0xff10..=0xff3f => self.snd.read_byte(addr),
             0xff40..=0xff4b => self.gpu.read_byte(addr),
+            0xff4d if self.cgb_mode => 0x7E | self.speed_switch,
             0xff80..=0xfffe => self.rom.read_byte(addr),
             0xff4c..=0xff7f => {
                 //println!("Reading from empty but unusable for I/O: 0xff4c-0xff80 {}",addr);
@@ -100,7 +153,7 @@ impl MemoryType for Memory {
         }
     }
 
-    fn write_byte(&mut self, addr: u16, val: u8) {
+    fn write_unwatched(&mut self, addr: u16, val: u8) {
         if addr >= 0x8000 && addr <= 0x97FF && val != 0x00 {
             // println!("SUCCESS: CPU wrote {:#04X} to VRAM at {:#06X}", val, addr);
         }
@@ -111,7 +164,9 @@ impl MemoryType for Memory {
             0xfe00..=0xfe9f => self.gpu.write_byte(addr, val),
             0xfea0..=0xfeff => {}
             0xff00 => {
-                //self.joypad = val
+                let before = self.joypad_register();
+                self.joypad = val & 0x30;
+                self.check_joypad_interrupt(before);
             }
             0xff01 => {
                 self.serial_transfer_data = val;
@@ -121,7 +176,9 @@ impl MemoryType for Memory {
                 self.serial_transfer_control = val;
                 // BLARGG
                 if val == 0x81 {
-                    print!("{}", self.serial_transfer_data as char);
+                    if !self.quiet {
+                        print!("{}", self.serial_transfer_data as char);
+                    }
                     self.serial_transfer_control = 0;
                 }
             }
@@ -134,13 +191,16 @@ impl MemoryType for Memory {
             0xff46 => {
                 // DMA transfer
                 let start = (val as u16) << 8;
+                self.dma_active = true;
                 for i in 0..140u16 {
                     let from_addr = start + i;
                     let to_addr = 0xfe00 + i;
                     self.write_byte(to_addr, self.read_byte(from_addr));
                 }
+                self.dma_active = false;
             }
             0xff40..=0xff4b => self.gpu.write_byte(addr, val),
+            0xff4d if self.cgb_mode => self.speed_switch = (self.speed_switch & 0x80) | (val & 1),
             0xff4c..=0xff7f => {}
             0xff80..=0xfffe => self.rom.write_byte(addr, val),
             0xffff => self.interupt_enable = val,
@@ -177,6 +237,8 @@ impl Memory {
                 0x3E, 0x01, 0xE0, 0x50,
             ],
             in_bios: true,
+            cgb_mode: false,
+            pressed_buttons: 0,
             interupt_enable: 0,
             interupt_flag: 0,
             joypad: 0xFF,
@@ -186,17 +248,23 @@ impl Memory {
             timer_counter: 0,
             timer_modulo: 0,
             timer_control: 0,
+            speed_switch: 0,
             timer_and_gate_previous: false,
+            watchpoints: Watchpoints::default(),
+            dma_active: false,
+            quiet: false,
         };
         mem.reset();
         mem
     }
 
-    pub fn load(&mut self, data: Vec<u8>, cartridge_info: &Cartridge) {
-        self.rom.load(&data, cartridge_info);
+    pub fn load(&mut self, data: Vec<u8>, cartridge_info: &Cartridge) -> Result<(), EmulatorError> {
+        self.cgb_mode = cartridge_info.cgb_support != CgbSupport::None;
+        self.rom.load(&data, cartridge_info)
     }
     pub fn reset(&mut self) {
         self.joypad = 0xFF;
+        self.speed_switch = 0;
         self.write_byte(0xFF00, 0xFF); //0x0F no buttons pressed
         self.write_byte(0xFF05, 0x00); //TIMA
         self.write_byte(0xFF06, 0x00); //TMA
@@ -231,16 +299,33 @@ impl Memory {
         self.write_byte(0xFFFF, 0x00); //IE
         self.write_byte(0xFF0F, 0xE1); //IF
     }
-    pub fn draw(&mut self, canvas: &mut Canvas<Window>) -> bool {
-        self.gpu.draw(canvas)
+    pub fn frame(&mut self) -> Option<&[u8]> {
+        self.gpu.frame()
+    }
+    pub(crate) fn frames_finished(&self) -> u64 {
+        self.gpu.frames_finished()
+    }
+    pub(crate) fn screen(&mut self) -> &[u8] {
+        self.gpu.screen()
+    }
+    pub(crate) fn take_samples(&mut self) -> Vec<i16> {
+        self.snd.take_samples()
+    }
+    pub(crate) fn palette(&self) -> &Palette {
+        self.gpu.palette()
+    }
+    pub(crate) fn set_palette(&mut self, palette: Palette) {
+        self.gpu.set_palette(palette)
     }
 
     pub fn tick(&mut self, clock_t: u8) {
         self.update_timers(clock_t);
-        let interrupts = self.gpu.tick(clock_t);
+        // The PPU keeps its real time speed when the cpu runs in double speed mode
+        let interrupts = self.gpu.tick(clock_t >> self.double_speed() as u8);
         if interrupts > 0 {
             self.interupt_flag |= interrupts;
         }
+        self.snd.tick(clock_t >> self.double_speed() as u8);
         // let has_graphics = self.gpu.get_tiles().iter().any(|tile| {
         //     tile.iter()
         //         .any(|row| row.iter().any(|&color| color != GBColor::White))
@@ -256,6 +341,48 @@ impl Memory {
     fn is_bit_set(val: u8, bit: u8) -> bool {
         (val & (1 << bit)) == (1 << bit)
     }
+
+    fn joypad_register(&self) -> u8 {
+        let mut lines = 0x0F;
+        if !Self::is_bit_set(self.joypad, 4) {
+            lines &= !(self.pressed_buttons & 0x0F);
+        }
+        if !Self::is_bit_set(self.joypad, 5) {
+            lines &= !(self.pressed_buttons >> 4);
+        }
+        0xC0 | (self.joypad & 0x30) | lines
+    }
+
+    // Any selected line going from high to low requests the joypad interrupt
+    fn check_joypad_interrupt(&mut self, before: u8) {
+        let after = self.joypad_register();
+        if before & !after & 0x0F != 0 {
+            self.interupt_flag |= 1 << 4;
+        }
+    }
+
+    pub(crate) fn set_pressed_buttons(&mut self, pressed: u8) {
+        let before = self.joypad_register();
+        self.pressed_buttons = pressed;
+        self.check_joypad_interrupt(before);
+    }
+
+    pub(crate) fn joypad_line_low(&self) -> bool {
+        self.joypad_register() & 0x0F != 0x0F
+    }
+
+    pub(crate) fn speed_switch_armed(&self) -> bool {
+        self.cgb_mode && Self::is_bit_set(self.speed_switch, 0)
+    }
+
+    pub(crate) fn switch_speed(&mut self) {
+        self.speed_switch = (self.speed_switch ^ 0x80) & 0x80;
+    }
+
+    pub(crate) fn double_speed(&self) -> bool {
+        Self::is_bit_set(self.speed_switch, 7)
+    }
+
     pub fn update_timers(&mut self, clock_t: u8) {
         for _ in 0..clock_t {
             // 1. Advance the single master 16-bit counter (make sure div_register is a u16!)
@@ -294,12 +421,68 @@ impl Memory {
         }
     }
 
-    fn color_to_char(color: &video::GBColor) -> String {
-        match color {
-            video::GBColor::White => "W".to_string(),
-            video::GBColor::LightGray => "L".to_string(),
-            video::GBColor::DarkGray => "D".to_string(),
-            video::GBColor::Black => "B".to_string(),
+    // Watchpoints and the bios are left alone
+    pub(crate) fn sync_state(&mut self, state: &mut StateBuffer) {
+        self.rom.sync_state(state);
+        self.gpu.sync_state(state);
+        self.snd.sync_state(state);
+        for register in [
+            &mut self.interupt_enable,
+            &mut self.interupt_flag,
+            &mut self.pressed_buttons,
+            &mut self.joypad,
+            &mut self.serial_transfer_data,
+            &mut self.serial_transfer_control,
+            &mut self.timer_counter,
+            &mut self.timer_modulo,
+            &mut self.timer_control,
+            &mut self.speed_switch,
+        ] {
+            state.u8(register);
+        }
+        state.u16(&mut self.div_register);
+        state.bool(&mut self.in_bios);
+        state.bool(&mut self.cgb_mode);
+        state.bool(&mut self.timer_and_gate_previous);
+    }
+
+    // Copies the machine state of a snapshot, watchpoints belong to the debugger and stay
+    pub(crate) fn restore(&mut self, snapshot: &Memory) {
+        let watchpoints = std::mem::take(&mut self.watchpoints);
+        let palette = self.gpu.palette().clone();
+        *self = snapshot.clone();
+        self.watchpoints = watchpoints;
+        self.gpu.set_palette(palette);
+    }
+    pub(crate) fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    // Writes without triggering watchpoints
+    pub(crate) fn poke(&mut self, addr: u16, val: u8) {
+        self.write_unwatched(addr, val);
+    }
+    pub(crate) fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        self.watchpoints.add(watchpoint)
+    }
+    pub(crate) fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        self.watchpoints.remove(index)
+    }
+    pub(crate) fn watchpoints(&self) -> &[Watchpoint] {
+        self.watchpoints.list()
+    }
+    pub(crate) fn take_watch_hits(&self) -> Vec<WatchHit> {
+        self.watchpoints.take_hits()
+    }
+
+    // Bank currently mapped at addr, numbered the way RGBDS numbers them in .sym files
+    pub(crate) fn bank_at(&self, addr: u16) -> u16 {
+        match addr {
+            0x4000..=0x7fff => self.rom.rom_bank(),
+            0xa000..=0xbfff => self.rom.ram_bank(),
+            // WRAMX, there is no SVBK switching so it is always bank 1
+            0xd000..=0xdfff => 1,
+            _ => 0,
         }
     }
 
@@ -312,73 +495,13 @@ impl Memory {
         self.in_bios = false;
     }
 
-    pub(crate) fn write_bg_tiles_to_file(&self) {
-        let bg_tiles = self.gpu.get_tiles();
-        let mut file = File::create("bg_tiles.txt").unwrap();
-        for i in (0..bg_tiles.len()).step_by(4) {
-            file.write_all(
-                format!(
-                    "Tile {0} \t Tile {1} \t Tile {2} \t Tile {3}\n",
-                    i,
-                    i + 1,
-                    i + 2,
-                    i + 3
-                )
-                .as_bytes(),
-            )
-            .unwrap();
-            for y in 0..8 {
-                for j in i..i + 4 {
-                    for x in 0..8 {
-                        file.write_all(
-                            Self::color_to_char(&bg_tiles[j][y][x])
-                                .to_string()
-                                .as_bytes(),
-                        )
-                        .unwrap();
-                    }
-                    file.write_all("\t".as_bytes()).unwrap();
-                }
-                file.write_all("\n".as_bytes()).unwrap();
-            }
-            file.write_all("\n".as_bytes()).unwrap();
-        }
-
-        file.write_all("tilemap 0x9800\n".as_bytes()).unwrap();
-        for i in (0x9800..=0x9fff - 10).step_by(10) {
-            for j in 0..10 {
-                file.write_all(format!("{0},", self.gpu.read_byte(i + j)).as_bytes())
-                    .unwrap();
-            }
-            if i + 10 < 0x9fff {
-                file.write_all(format!("\ntilemap {0:#0x}\n", i + 10).as_bytes())
-                    .unwrap();
-            }
-        }
-        file.write_all("\n".as_bytes()).unwrap();
-
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                file.write_all(
-                    Self::color_to_char(&self.gpu.get_pixel(x, y))
-                        .to_string()
-                        .as_bytes(),
-                )
-                .unwrap();
-            }
-            file.write_all("\n".as_bytes()).unwrap();
-        }
-
-        println!("tiles dumped to bg_tiles.txt");
+    pub(crate) fn objects_on_line(&self, line: u8) -> Vec<usize> {
+        self.gpu.objects_on_line(line)
     }
-
-    pub fn dump_tiles(&self) -> &[[[video::GBColor; 8]; 8]; 384] {
-        self.write_bg_tiles_to_file();
+    // Decoded tile data, for the viewers
+    pub(crate) fn tiles(&self) -> &[[[video::GBColor; 8]; 8]; 384] {
         self.gpu.get_tiles()
     }
-    pub fn debug_get_background_tilemap(&self) -> [u8; 32 * 32] {
-        self.gpu.debug_get_background_tilemap()
-    }
     pub(crate) fn debug_toggle_background(&mut self) {
         self.gpu.debug_toggle_background()
     }
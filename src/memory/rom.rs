@@ -1,8 +1,12 @@
+use std::rc::Rc;
+
 use crate::cartridge::{Cartridge, CartridgeType};
+use crate::error::EmulatorError;
+use crate::savestate::StateBuffer;
 
 use super::MemoryType;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 enum MbcMode {
     None,
     Mbc1_16mbRom8kbRam,
@@ -10,8 +14,10 @@ enum MbcMode {
     Invalid,
 }
 
+// Clones share the rom data, snapshots only copy the mutable state
+#[derive(Clone)]
 pub struct Rom {
-    rom: Vec<u8>,
+    rom: Rc<[u8]>,
     external_ram: [u8; 0x8000],
     internal_ram: [u8; 0x2000],
     high_ram: [u8; 0x7f],
@@ -55,7 +61,7 @@ impl MemoryType for Rom {
                 MbcMode::Mbc1_16mbRom8kbRam | MbcMode::Mbc1_4mbRom32kbRam => {
                     self.write_mbc1(addr, val);
                 }
-                MbcMode::Invalid => todo!(),
+                MbcMode::Invalid => {}
             },
             0xa000..=0xbfff => {
                 if self.ram_enabled {
@@ -73,7 +79,7 @@ impl MemoryType for Rom {
 impl Rom {
     pub fn new() -> Self {
         Self {
-            rom: Vec::new(),
+            rom: Rc::from([]),
             rom_offset: 0x4000,
             ram_offset: 0,
             internal_ram: [0; 0x2000],
@@ -84,6 +90,29 @@ impl Rom {
             log_bank_changes: false,
         }
     }
+    // The rom data itself isn't part of the state
+    pub(crate) fn sync_state(&mut self, state: &mut StateBuffer) {
+        state.bytes(&mut self.external_ram);
+        state.bytes(&mut self.internal_ram);
+        state.bytes(&mut self.high_ram);
+        state.usize(&mut self.rom_offset);
+        state.usize(&mut self.ram_offset);
+        let mut mbc_mode = self.mbc_mode.clone() as u8;
+        state.u8(&mut mbc_mode);
+        self.mbc_mode = match mbc_mode {
+            0 => MbcMode::None,
+            1 => MbcMode::Mbc1_16mbRom8kbRam,
+            2 => MbcMode::Mbc1_4mbRom32kbRam,
+            _ => MbcMode::Invalid,
+        };
+        state.bool(&mut self.ram_enabled);
+    }
+    pub fn rom_bank(&self) -> u16 {
+        (self.rom_offset / 0x4000) as u16
+    }
+    pub fn ram_bank(&self) -> u16 {
+        (self.ram_offset / 0x2000) as u16
+    }
     fn write_mbc1(&mut self, addr: u16, val: u8) {
         match addr {
             0x0000..=0x1fff => {
@@ -94,10 +123,13 @@ impl Rom {
                 if rom_bank == 0 {
                     rom_bank = 1;
                 }
+                // Unused upper bank bits are ignored by the mapper
+                let rom_banks = (self.rom.len() / 0x4000).max(1);
+                let rom_bank = rom_bank as usize % rom_banks;
                 let new_offset = match self.mbc_mode {
                     MbcMode::None => 0x4000,
-                    MbcMode::Mbc1_16mbRom8kbRam => (rom_bank as usize) * 0x4000,
-                    MbcMode::Mbc1_4mbRom32kbRam => (rom_bank as usize) * 4 * 0x1000,
+                    MbcMode::Mbc1_16mbRom8kbRam => rom_bank * 0x4000,
+                    MbcMode::Mbc1_4mbRom32kbRam => rom_bank * 4 * 0x1000,
                     _ => 0x4000,
                 };
                 if self.rom_offset != new_offset {
@@ -133,17 +165,32 @@ impl Rom {
         }
     }
 
-    pub fn load(&mut self, data: &[u8], cartridge_info: &Cartridge) {
-        self.rom = vec![0; cartridge_info.rom_size];
-        self.rom.copy_from_slice(data);
-
-        match cartridge_info.cartidge_type {
-            CartridgeType::RomOnly => self.mbc_mode = MbcMode::None,
-            CartridgeType::Mbc1 => self.mbc_mode = MbcMode::Mbc1_16mbRom8kbRam,
-            CartridgeType::Mbc2 => todo!(),
-            CartridgeType::Mbc3 => todo!(),
-            CartridgeType::Mbc5 => todo!(),
-            CartridgeType::Invalid => todo!(),
+    pub fn load(&mut self, data: &[u8], cartridge_info: &Cartridge) -> Result<(), EmulatorError> {
+        if data.len() != cartridge_info.rom_size {
+            return Err(EmulatorError::RomSizeMismatch {
+                header: cartridge_info.rom_size,
+                file: data.len(),
+            });
         }
+        self.mbc_mode = match cartridge_info.cartidge_type {
+            CartridgeType::RomOnly => MbcMode::None,
+            CartridgeType::Mbc1 => MbcMode::Mbc1_16mbRom8kbRam,
+            CartridgeType::Mbc2
+            | CartridgeType::Mmm01
+            | CartridgeType::Mbc3
+            | CartridgeType::Mbc5
+            | CartridgeType::Mbc6
+            | CartridgeType::Mbc7
+            | CartridgeType::PocketCamera
+            | CartridgeType::BandaiTama5
+            | CartridgeType::HuC3
+            | CartridgeType::HuC1 => {
+                return Err(EmulatorError::UnsupportedCartridge(
+                    cartridge_info.cartidge_type,
+                ));
+            }
+        };
+        self.rom = Rc::from(data);
+        Ok(())
     }
 }
@@ -1,5 +1,18 @@
 use super::MemoryType;
+use crate::savestate::StateBuffer;
 
+// Stereo samples per second synthesized from the channels
+pub const SAMPLE_RATE: u32 = 44_100;
+const CPU_HZ: u64 = 4_194_304;
+// The frame sequencer clocks length, sweep and envelope at 512Hz
+const SEQUENCER_PERIOD: u32 = 8192;
+// About a second of audio when nobody takes the samples, the rest is dropped
+const MAX_SAMPLES: usize = 2 * SAMPLE_RATE as usize;
+// High and low steps of the four square wave duty cycles, oldest step in bit 7
+const DUTY: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+const NOISE_DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+#[derive(Clone)]
 struct SoundRegister {
     sweep: u8,
     sound_len: u8,
@@ -20,6 +33,7 @@ impl SoundRegister {
     }
 }
 
+#[derive(Clone)]
 struct SoundRegister3 {
     on_off: bool,
     sound_len: u8,
@@ -40,6 +54,7 @@ impl SoundRegister3 {
     }
 }
 
+#[derive(Clone)]
 struct SoundRegister4 {
     sound_len: u8,
     envelope: u8,
@@ -58,6 +73,96 @@ impl SoundRegister4 {
     }
 }
 
+// What a channel is doing between register writes
+#[derive(Clone, Default)]
+struct Voice {
+    enabled: bool,
+    length: u16,
+    volume: u8,
+    envelope_timer: u8,
+    // Cycles until the waveform moves on to position + 1
+    timer: u32,
+    position: u8,
+    lfsr: u16,
+    sweep_timer: u8,
+    sweep_enabled: bool,
+    shadow_freq: u16,
+}
+
+impl Voice {
+    fn sync_state(&mut self, state: &mut StateBuffer) {
+        state.bool(&mut self.enabled);
+        state.u16(&mut self.length);
+        state.u8(&mut self.volume);
+        state.u8(&mut self.envelope_timer);
+        state.u32(&mut self.timer);
+        state.u8(&mut self.position);
+        state.u16(&mut self.lfsr);
+        state.u8(&mut self.sweep_timer);
+        state.bool(&mut self.sweep_enabled);
+        state.u16(&mut self.shadow_freq);
+    }
+
+    fn trigger(&mut self, envelope: u8, max_length: u16) {
+        self.enabled = true;
+        if self.length == 0 {
+            self.length = max_length;
+        }
+        self.volume = envelope >> 4;
+        self.envelope_timer = envelope & 0x07;
+    }
+
+    fn clock_length(&mut self, control: u8) {
+        if control & 0x40 != 0 && self.length > 0 {
+            self.length -= 1;
+            self.enabled &= self.length > 0;
+        }
+    }
+
+    fn clock_envelope(&mut self, envelope: u8) {
+        let period = envelope & 0x07;
+        if period == 0 {
+            return;
+        }
+        self.envelope_timer = self.envelope_timer.saturating_sub(1);
+        if self.envelope_timer == 0 {
+            self.envelope_timer = period;
+            if envelope & 0x08 != 0 {
+                self.volume = (self.volume + 1).min(15);
+            } else {
+                self.volume = self.volume.saturating_sub(1);
+            }
+        }
+    }
+
+    // Moves the waveform on by cycles and returns how many steps it took
+    fn run(&mut self, cycles: u32, period: u32) -> u32 {
+        let mut cycles = cycles;
+        let mut steps = 0;
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = period;
+            steps += 1;
+        }
+        self.timer -= cycles;
+        steps
+    }
+
+    // Square and noise channels swing between plus and minus their volume
+    fn level(&self, high: bool) -> i32 {
+        match (self.enabled, high) {
+            (false, _) => 0,
+            (true, true) => self.volume as i32,
+            (true, false) => -(self.volume as i32),
+        }
+    }
+}
+
+fn frequency(freq_lo: u8, freq_hi: u8) -> u16 {
+    ((freq_hi as u16 & 0x07) << 8) | freq_lo as u16
+}
+
+#[derive(Clone)]
 pub struct Sound {
     mode_1_reg: SoundRegister,
     mode_2_reg: SoundRegister,
@@ -66,7 +171,13 @@ pub struct Sound {
     channel_control: u8,
     output_terminal_selection: u8,
     on_off: u8,
-    wave_pattern_ram: [u8; 0xF],
+    wave_pattern_ram: [u8; 0x10],
+    voices: [Voice; 4],
+    sequencer_timer: u32,
+    sequencer_step: u8,
+    // CPU cycles times SAMPLE_RATE since the last sample
+    sample_clock: u64,
+    samples: Vec<i16>,
 }
 impl Sound {
     pub(crate) fn new() -> Sound {
@@ -78,9 +189,247 @@ impl Sound {
             channel_control: 0,
             output_terminal_selection: 0,
             on_off: 0xf1,
-            wave_pattern_ram: [0; 0xF],
+            wave_pattern_ram: [0; 0x10],
+            voices: Default::default(),
+            sequencer_timer: SEQUENCER_PERIOD,
+            sequencer_step: 0,
+            sample_clock: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    fn powered(&self) -> bool {
+        self.on_off & 0x80 != 0
+    }
+
+    // Interleaved left and right samples synthesized since the last call
+    pub(crate) fn take_samples(&mut self) -> Vec<i16> {
+        std::mem::take(&mut self.samples)
+    }
+
+    pub(crate) fn tick(&mut self, cycles: u8) {
+        let cycles = cycles as u32;
+        if self.powered() {
+            self.run_sequencer(cycles);
+            self.run_waveforms(cycles);
+        }
+        self.sample_clock += cycles as u64 * SAMPLE_RATE as u64;
+        while self.sample_clock >= CPU_HZ {
+            self.sample_clock -= CPU_HZ;
+            if self.samples.len() < MAX_SAMPLES {
+                let (left, right) = self.output();
+                self.samples.extend([left, right]);
+            }
+        }
+    }
+
+    fn run_sequencer(&mut self, cycles: u32) {
+        if cycles < self.sequencer_timer {
+            self.sequencer_timer -= cycles;
+            return;
+        }
+        self.sequencer_timer += SEQUENCER_PERIOD - cycles;
+        let step = self.sequencer_step;
+        self.sequencer_step = (step + 1) % 8;
+        if step.is_multiple_of(2) {
+            self.voices[0].clock_length(self.mode_1_reg.freq_hi);
+            self.voices[1].clock_length(self.mode_2_reg.freq_hi);
+            self.voices[2].clock_length(self.mode_3_reg.freq_hi);
+            self.voices[3].clock_length(self.mode_4_reg.counter_consec);
+        }
+        if step == 2 || step == 6 {
+            self.clock_sweep();
+        }
+        if step == 7 {
+            self.voices[0].clock_envelope(self.mode_1_reg.envelope);
+            self.voices[1].clock_envelope(self.mode_2_reg.envelope);
+            self.voices[3].clock_envelope(self.mode_4_reg.envelope);
+        }
+    }
+
+    fn run_waveforms(&mut self, cycles: u32) {
+        for (voice, reg) in self.voices[..2]
+            .iter_mut()
+            .zip([&self.mode_1_reg, &self.mode_2_reg])
+        {
+            let period = (2048 - frequency(reg.freq_lo, reg.freq_hi) as u32) * 4;
+            let steps = voice.run(cycles, period);
+            voice.position = ((voice.position as u32 + steps) % 8) as u8;
+        }
+        let reg = &self.mode_3_reg;
+        let period = (2048 - frequency(reg.freq_lo, reg.freq_hi) as u32) * 2;
+        let voice = &mut self.voices[2];
+        let steps = voice.run(cycles, period);
+        voice.position = ((voice.position as u32 + steps) % 32) as u8;
+
+        let poly = self.mode_4_reg.poly_counter;
+        let period = NOISE_DIVISORS[poly as usize & 0x07] << (poly >> 4);
+        let voice = &mut self.voices[3];
+        for _ in 0..voice.run(cycles, period) {
+            let bit = (voice.lfsr ^ (voice.lfsr >> 1)) & 1;
+            voice.lfsr = (voice.lfsr >> 1) | (bit << 14);
+            // The short mode repeats after 127 steps instead of 32767
+            if poly & 0x08 != 0 {
+                voice.lfsr = (voice.lfsr & !(1 << 6)) | (bit << 6);
+            }
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let sweep = self.mode_1_reg.sweep;
+        let freq = self.voices[0].shadow_freq;
+        let delta = freq >> (sweep & 0x07);
+        if sweep & 0x08 != 0 {
+            freq.wrapping_sub(delta)
+        } else {
+            freq + delta
+        }
+    }
+
+    fn clock_sweep(&mut self) {
+        let sweep = self.mode_1_reg.sweep;
+        let period = (sweep >> 4) & 0x07;
+        let voice = &mut self.voices[0];
+        voice.sweep_timer = voice.sweep_timer.saturating_sub(1);
+        if voice.sweep_timer > 0 {
+            return;
+        }
+        voice.sweep_timer = if period == 0 { 8 } else { period };
+        if !voice.sweep_enabled || period == 0 {
+            return;
+        }
+        let target = self.sweep_target();
+        if target > 0x7FF {
+            self.voices[0].enabled = false;
+        } else if sweep & 0x07 != 0 {
+            self.voices[0].shadow_freq = target;
+            self.mode_1_reg.freq_lo = target as u8;
+            self.mode_1_reg.freq_hi = (self.mode_1_reg.freq_hi & !0x07) | (target >> 8) as u8;
+            // The next step is checked for overflow right away
+            self.voices[0].enabled &= self.sweep_target() <= 0x7FF;
+        }
+    }
+
+    fn trigger(&mut self, channel: usize) {
+        if !self.powered() {
+            return;
+        }
+        match channel {
+            0 | 1 => {
+                let reg = if channel == 0 {
+                    &self.mode_1_reg
+                } else {
+                    &self.mode_2_reg
+                };
+                let (envelope, freq) = (reg.envelope, frequency(reg.freq_lo, reg.freq_hi));
+                let voice = &mut self.voices[channel];
+                voice.trigger(envelope, 64);
+                voice.timer = (2048 - freq as u32) * 4;
+                // Without the DAC powered the channel stays off
+                voice.enabled &= envelope & 0xF8 != 0;
+                if channel == 0 {
+                    let sweep = self.mode_1_reg.sweep;
+                    let period = (sweep >> 4) & 0x07;
+                    voice.shadow_freq = freq;
+                    voice.sweep_timer = if period == 0 { 8 } else { period };
+                    voice.sweep_enabled = period != 0 || sweep & 0x07 != 0;
+                    if sweep & 0x07 != 0 && self.sweep_target() > 0x7FF {
+                        self.voices[0].enabled = false;
+                    }
+                }
+            }
+            2 => {
+                let reg = &self.mode_3_reg;
+                let freq = frequency(reg.freq_lo, reg.freq_hi);
+                let voice = &mut self.voices[2];
+                voice.trigger(0xF0, 256);
+                voice.timer = (2048 - freq as u32) * 2;
+                voice.position = 0;
+                voice.enabled &= reg.on_off;
+            }
+            _ => {
+                let envelope = self.mode_4_reg.envelope;
+                let voice = &mut self.voices[3];
+                voice.trigger(envelope, 64);
+                voice.lfsr = 0x7FFF;
+                voice.enabled &= envelope & 0xF8 != 0;
+            }
         }
     }
+
+    // Each channel between -15 and 15, panned by NR51 and scaled by NR50
+    fn output(&self) -> (i16, i16) {
+        if !self.powered() {
+            return (0, 0);
+        }
+        let square = |channel: usize, reg: &SoundRegister| {
+            let voice = &self.voices[channel];
+            let duty = DUTY[reg.sound_len as usize >> 6];
+            voice.level((duty >> (7 - voice.position)) & 1 != 0)
+        };
+        let wave = {
+            let voice = &self.voices[2];
+            let byte = self.wave_pattern_ram[voice.position as usize / 2];
+            let sample = if voice.position.is_multiple_of(2) {
+                byte >> 4
+            } else {
+                byte & 0x0F
+            };
+            match (voice.enabled, (self.mode_3_reg.output_lvl >> 5) & 0x03) {
+                (false, _) | (_, 0) => 0,
+                (true, level) => (sample as i32 * 2 - 15) >> (level - 1),
+            }
+        };
+        let noise = self.voices[3].level(self.voices[3].lfsr & 1 == 0);
+        let channels = [
+            square(0, &self.mode_1_reg),
+            square(1, &self.mode_2_reg),
+            wave,
+            noise,
+        ];
+        let mix = |shift: u8, volume: u8| {
+            let sum: i32 = (0..4)
+                .filter(|i| (self.output_terminal_selection >> (shift + i)) & 1 != 0)
+                .map(|i| channels[i as usize])
+                .sum();
+            (sum * (volume as i32 + 1) * 64) as i16
+        };
+        let control = self.channel_control;
+        (mix(4, (control >> 4) & 0x07), mix(0, control & 0x07))
+    }
+}
+
+impl Sound {
+    pub(crate) fn sync_state(&mut self, state: &mut StateBuffer) {
+        for reg in [&mut self.mode_1_reg, &mut self.mode_2_reg] {
+            state.u8(&mut reg.sweep);
+            state.u8(&mut reg.sound_len);
+            state.u8(&mut reg.envelope);
+            state.u8(&mut reg.freq_lo);
+            state.u8(&mut reg.freq_hi);
+        }
+        let reg = &mut self.mode_3_reg;
+        state.bool(&mut reg.on_off);
+        state.u8(&mut reg.sound_len);
+        state.u8(&mut reg.output_lvl);
+        state.u8(&mut reg.freq_lo);
+        state.u8(&mut reg.freq_hi);
+        let reg = &mut self.mode_4_reg;
+        state.u8(&mut reg.sound_len);
+        state.u8(&mut reg.envelope);
+        state.u8(&mut reg.poly_counter);
+        state.u8(&mut reg.counter_consec);
+        state.u8(&mut self.channel_control);
+        state.u8(&mut self.output_terminal_selection);
+        state.u8(&mut self.on_off);
+        state.bytes(&mut self.wave_pattern_ram);
+        for voice in &mut self.voices {
+            voice.sync_state(state);
+        }
+        state.u32(&mut self.sequencer_timer);
+        state.u8(&mut self.sequencer_step);
+        state.u64(&mut self.sample_clock);
+    }
 }
 
 impl MemoryType for Sound {
@@ -106,7 +455,10 @@ impl MemoryType for Sound {
             0xff23 => self.mode_4_reg.counter_consec & 0x40,
             0xff24 => self.channel_control,
             0xff25 => self.output_terminal_selection,
-            0xff26 => self.on_off & 0xF0, // Bits 0 - 3 of this register are meant to be status bits to be read.
+            0xff26 => {
+                let status = (0..4).filter(|&i| self.voices[i].enabled);
+                (self.on_off & 0xF0) | status.fold(0, |bits, i| bits | 1 << i)
+            }
             0xff30..=0xff3f => self.wave_pattern_ram[(addr & 0xF) as usize],
             _ => {
                 println!("[Sound]: read from unused addresses of sound");
@@ -118,28 +470,64 @@ impl MemoryType for Sound {
     fn write_byte(&mut self, addr: u16, val: u8) {
         match addr {
             0xff10 => self.mode_1_reg.sweep = val,
-            0xff11 => self.mode_1_reg.sound_len = val,
+            0xff11 => {
+                self.mode_1_reg.sound_len = val;
+                self.voices[0].length = 64 - (val & 0x3F) as u16;
+            }
             0xff12 => self.mode_1_reg.envelope = val,
             0xff13 => self.mode_1_reg.freq_lo = val,
             0xff14 => self.mode_1_reg.freq_hi = val,
-            0xff16 => self.mode_2_reg.sound_len = val,
+            0xff16 => {
+                self.mode_2_reg.sound_len = val;
+                self.voices[1].length = 64 - (val & 0x3F) as u16;
+            }
             0xff17 => self.mode_2_reg.envelope = val,
             0xff18 => self.mode_2_reg.freq_lo = val,
             0xff19 => self.mode_2_reg.freq_hi = val,
             0xff1a => self.mode_3_reg.on_off = (val & (1 << 7)) > 0,
-            0xff1b => self.mode_3_reg.sound_len = val,
+            0xff1b => {
+                self.mode_3_reg.sound_len = val;
+                self.voices[2].length = 256 - val as u16;
+            }
             0xff1c => self.mode_3_reg.output_lvl = val,
             0xff1d => self.mode_3_reg.freq_lo = val,
             0xff1e => self.mode_3_reg.freq_hi = val,
-            0xff20 => self.mode_4_reg.sound_len = val,
+            0xff20 => {
+                self.mode_4_reg.sound_len = val;
+                self.voices[3].length = 64 - (val & 0x3F) as u16;
+            }
             0xff21 => self.mode_4_reg.envelope = val,
             0xff22 => self.mode_4_reg.poly_counter = val,
             0xff23 => self.mode_4_reg.counter_consec = val,
             0xff24 => self.channel_control = val,
             0xff25 => self.output_terminal_selection = val,
             0xff26 => self.on_off = val & 0xF0, // Bits 0 - 3 of this register are meant to be status bits to be read.
-            0xff30..=0xff3f => self.wave_pattern_ram[(addr & 0xE) as usize] = val,
+            0xff30..=0xff3f => self.wave_pattern_ram[(addr & 0xF) as usize] = val,
             _ => println!("[Sound]: wrote to unused addresses of sound"),
         }
+        // Switching the APU off silences every channel
+        if !self.powered() {
+            self.voices
+                .iter_mut()
+                .for_each(|voice| voice.enabled = false);
+        }
+        // Writing bit 7 of a channel's last register restarts it
+        if val & 0x80 != 0 {
+            match addr {
+                0xff14 => self.trigger(0),
+                0xff19 => self.trigger(1),
+                0xff1e => self.trigger(2),
+                0xff23 => self.trigger(3),
+                _ => {}
+            }
+        }
+        // Turning a DAC off stops its channel
+        match addr {
+            0xff12 if val & 0xF8 == 0 => self.voices[0].enabled = false,
+            0xff17 if val & 0xF8 == 0 => self.voices[1].enabled = false,
+            0xff1a if val & 0x80 == 0 => self.voices[2].enabled = false,
+            0xff21 if val & 0xF8 == 0 => self.voices[3].enabled = false,
+            _ => {}
+        }
     }
 }
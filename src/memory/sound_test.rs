@@ -0,0 +1,85 @@
+#[cfg(test)]
+mod tests {
+    use crate::memory::MemoryType;
+    use crate::memory::sound::{SAMPLE_RATE, Sound};
+
+    fn run(sound: &mut Sound, cycles: u32) {
+        for _ in 0..cycles / 4 {
+            sound.tick(4);
+        }
+    }
+
+    // Channel 1 at full volume with a 50% duty cycle, only on the left output
+    fn square_on_left() -> Sound {
+        let mut sound = Sound::new();
+        sound.write_byte(0xff26, 0x80);
+        sound.write_byte(0xff24, 0x77);
+        sound.write_byte(0xff25, 0x10);
+        sound.write_byte(0xff11, 0x80);
+        sound.write_byte(0xff12, 0xF0);
+        // Frequency 1024, 128Hz
+        sound.write_byte(0xff13, 0x00);
+        sound.write_byte(0xff14, 0x84);
+        sound
+    }
+
+    #[test]
+    fn test_square_wave_samples() {
+        let mut sound = square_on_left();
+        assert_eq!(sound.read_byte(0xff26) & 0x0F, 0x01);
+        run(&mut sound, 4_194_304 / 8);
+        let samples = sound.take_samples();
+        assert_eq!(samples.len(), 2 * (SAMPLE_RATE as usize / 8));
+        assert!(sound.take_samples().is_empty());
+
+        let (left, right): (Vec<i16>, Vec<i16>) =
+            samples.chunks_exact(2).map(|s| (s[0], s[1])).unzip();
+        assert!(right.iter().all(|&s| s == 0));
+        // Volume 15 at the loudest master volume swings both ways
+        assert!(left.iter().all(|&s| s == 15 * 8 * 64 || s == -15 * 8 * 64));
+        // 16 periods in an eighth of a second, so 32 edges
+        let edges = left.windows(2).filter(|w| w[0] != w[1]).count();
+        assert!((31..=33).contains(&edges), "{edges} edges");
+    }
+
+    #[test]
+    fn test_length_counter_stops_channel() {
+        let mut sound = square_on_left();
+        // One length step left, counting enabled
+        sound.write_byte(0xff11, 0x80 | 63);
+        sound.write_byte(0xff14, 0xC4);
+        assert_eq!(sound.read_byte(0xff26) & 0x01, 0x01);
+        run(&mut sound, 2 * 8192);
+        assert_eq!(sound.read_byte(0xff26) & 0x01, 0x00);
+        sound.take_samples();
+        run(&mut sound, 8192);
+        assert!(sound.take_samples().iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn test_dac_off_and_power_off() {
+        let mut sound = square_on_left();
+        sound.write_byte(0xff12, 0x00);
+        assert_eq!(sound.read_byte(0xff26) & 0x01, 0x00);
+        sound.write_byte(0xff14, 0x84);
+        assert_eq!(sound.read_byte(0xff26) & 0x01, 0x00);
+
+        let mut sound = square_on_left();
+        sound.write_byte(0xff26, 0x00);
+        assert_eq!(sound.read_byte(0xff26), 0x00);
+        // Triggers are ignored while the APU is off
+        sound.write_byte(0xff14, 0x84);
+        assert_eq!(sound.read_byte(0xff26), 0x00);
+    }
+
+    #[test]
+    fn test_wave_ram() {
+        let mut sound = Sound::new();
+        for addr in 0xff30..=0xff3f {
+            sound.write_byte(addr, addr as u8);
+        }
+        for addr in 0xff30..=0xff3f {
+            assert_eq!(sound.read_byte(addr), addr as u8);
+        }
+    }
+}
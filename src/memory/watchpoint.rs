@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+// Tested against the value being read or written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchCondition {
+    Always,
+    Equals(u8),
+    // Only writes that change the stored value
+    Changes,
+    Mask { mask: u8, value: u8 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub start: u16,
+    // Inclusive
+    pub end: u16,
+    pub kind: WatchKind,
+    pub condition: WatchCondition,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WatchHit {
+    pub index: usize,
+    pub addr: u16,
+    pub access: Access,
+    pub old: u8,
+    pub new: u8,
+    pub by_dma: bool,
+}
+
+impl Watchpoint {
+    fn covers(&self, addr: u16, access: Access) -> bool {
+        let kind_matches = match self.kind {
+            WatchKind::Read => access == Access::Read,
+            WatchKind::Write => access == Access::Write,
+            WatchKind::Access => true,
+        };
+        kind_matches && (self.start..=self.end).contains(&addr)
+    }
+
+    fn triggers(&self, old: u8, new: u8) -> bool {
+        match self.condition {
+            WatchCondition::Always => true,
+            WatchCondition::Equals(value) => new == value,
+            WatchCondition::Changes => new != old,
+            WatchCondition::Mask { mask, value } => new & mask == value,
+        }
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::Access => "access",
+        };
+        write!(f, "{kind} ${:04X}", self.start)?;
+        if self.end != self.start {
+            write!(f, "-${:04X}", self.end)?;
+        }
+        match self.condition {
+            WatchCondition::Always => Ok(()),
+            WatchCondition::Equals(value) => write!(f, " == ${value:02X}"),
+            WatchCondition::Changes => write!(f, " changes"),
+            WatchCondition::Mask { mask, value } => write!(f, " & ${mask:02X} == ${value:02X}"),
+        }
+    }
+}
+
+// Hits are collected behind a RefCell because reads only borrow memory immutably
+#[derive(Default, Clone)]
+pub struct Watchpoints {
+    list: Vec<Watchpoint>,
+    hits: RefCell<Vec<WatchHit>>,
+}
+
+impl Watchpoints {
+    pub fn add(&mut self, watchpoint: Watchpoint) -> usize {
+        self.list.push(watchpoint);
+        self.list.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Watchpoint> {
+        if index >= self.list.len() {
+            return None;
+        }
+        Some(self.list.remove(index))
+    }
+
+    pub fn list(&self) -> &[Watchpoint] {
+        &self.list
+    }
+
+    pub fn watches(&self, addr: u16, access: Access) -> bool {
+        self.list.iter().any(|w| w.covers(addr, access))
+    }
+
+    pub fn check(&self, addr: u16, access: Access, old: u8, new: u8, by_dma: bool) {
+        for (index, watchpoint) in self.list.iter().enumerate() {
+            if watchpoint.covers(addr, access) && watchpoint.triggers(old, new) {
+                self.hits.borrow_mut().push(WatchHit {
+                    index,
+                    addr,
+                    access,
+                    old,
+                    new,
+                    by_dma,
+                });
+            }
+        }
+    }
+
+    pub fn take_hits(&self) -> Vec<WatchHit> {
+        self.hits.take()
+    }
+}
@@ -0,0 +1,119 @@
+#[cfg(test)]
+mod tests {
+    use crate::memory::{
+        Access, Memory, MemoryType, WatchCondition, WatchHit, WatchKind, Watchpoint,
+    };
+
+    fn watch(start: u16, end: u16, kind: WatchKind, condition: WatchCondition) -> Watchpoint {
+        Watchpoint {
+            start,
+            end,
+            kind,
+            condition,
+        }
+    }
+
+    #[test]
+    fn test_write_watchpoint_reports_old_and_new() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xc000, 0x11);
+        memory.add_watchpoint(watch(
+            0xc000,
+            0xc0ff,
+            WatchKind::Write,
+            WatchCondition::Always,
+        ));
+        memory.write_byte(0xc010, 0x22);
+        memory.write_byte(0xc000, 0x33);
+        memory.read_byte(0xc000);
+        memory.write_byte(0xc100, 0x44);
+        let hits = memory.take_watch_hits();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(
+            hits[1],
+            WatchHit {
+                index: 0,
+                addr: 0xc000,
+                access: Access::Write,
+                old: 0x11,
+                new: 0x33,
+                by_dma: false,
+            }
+        );
+        assert!(memory.take_watch_hits().is_empty());
+    }
+
+    #[test]
+    fn test_read_watchpoint_ignores_peek() {
+        let mut memory = Memory::new();
+        memory.add_watchpoint(watch(
+            0xc000,
+            0xc000,
+            WatchKind::Read,
+            WatchCondition::Always,
+        ));
+        memory.peek(0xc000);
+        memory.write_byte(0xc000, 1);
+        assert!(memory.take_watch_hits().is_empty());
+        memory.read_byte(0xc000);
+        assert_eq!(memory.take_watch_hits()[0].access, Access::Read);
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut memory = Memory::new();
+        memory.add_watchpoint(watch(
+            0xc000,
+            0xc000,
+            WatchKind::Write,
+            WatchCondition::Changes,
+        ));
+        memory.add_watchpoint(watch(
+            0xc001,
+            0xc001,
+            WatchKind::Write,
+            WatchCondition::Equals(5),
+        ));
+        let mask = WatchCondition::Mask {
+            mask: 0x80,
+            value: 0x80,
+        };
+        memory.add_watchpoint(watch(0xc002, 0xc002, WatchKind::Access, mask));
+
+        memory.write_byte(0xc000, 7);
+        memory.write_byte(0xc000, 7);
+        memory.write_byte(0xc001, 4);
+        memory.write_byte(0xc001, 5);
+        memory.write_byte(0xc002, 0x7f);
+        memory.write_byte(0xc002, 0x81);
+        let hits: Vec<(usize, u8)> = memory
+            .take_watch_hits()
+            .iter()
+            .map(|hit| (hit.index, hit.new))
+            .collect();
+        assert_eq!(hits, vec![(0, 7), (1, 5), (2, 0x81)]);
+    }
+
+    #[test]
+    fn test_dma_is_caught() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xc005, 0x42);
+        memory.add_watchpoint(watch(
+            0xfe05,
+            0xfe05,
+            WatchKind::Write,
+            WatchCondition::Always,
+        ));
+        memory.add_watchpoint(watch(
+            0xc005,
+            0xc005,
+            WatchKind::Read,
+            WatchCondition::Always,
+        ));
+        memory.write_byte(0xff46, 0xc0);
+        let hits = memory.take_watch_hits();
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|hit| hit.by_dma));
+        assert_eq!(hits[1].new, 0x42);
+    }
+}
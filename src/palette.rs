@@ -0,0 +1,315 @@
+use serde::Deserialize;
+
+use crate::cartridge::{Cartridge, CgbSupport, Licensee};
+
+pub type Rgb = [u8; 3];
+
+// Shades 0-3 after the DMG palette registers, for the background and window and for
+// objects using OBP0 and OBP1
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    pub bg: [Rgb; 4],
+    pub obj0: [Rgb; 4],
+    pub obj1: [Rgb; 4],
+}
+
+// A palette from the config, either four colors for everything or one set per layer.
+// Layers left out fall back to colors.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PaletteConfig {
+    pub name: String,
+    pub colors: Option<[Rgb; 4]>,
+    pub bg: Option<[Rgb; 4]>,
+    pub obj0: Option<[Rgb; 4]>,
+    pub obj1: Option<[Rgb; 4]>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PaletteSettings {
+    pub custom: Vec<PaletteConfig>,
+    // Palette used at start, by name, the first custom one or grayscale otherwise
+    pub default: Option<String>,
+    // Pick the CGB boot rom's colors for DMG games it knows
+    pub cgb_compatibility: bool,
+}
+
+impl Default for PaletteSettings {
+    fn default() -> Self {
+        PaletteSettings {
+            custom: Vec::new(),
+            default: None,
+            cgb_compatibility: true,
+        }
+    }
+}
+
+const WHITE: Rgb = [0xFF, 0xFF, 0xFF];
+const BLACK: Rgb = [0x00, 0x00, 0x00];
+const RED: [Rgb; 4] = [WHITE, [0xFF, 0x84, 0x84], [0x94, 0x3A, 0x3A], BLACK];
+const BLUE: [Rgb; 4] = [WHITE, [0x63, 0xA5, 0xFF], [0x00, 0x00, 0xFF], BLACK];
+const BROWN: [Rgb; 4] = [WHITE, [0xFF, 0xAD, 0x63], [0x84, 0x31, 0x00], BLACK];
+const GREEN: [Rgb; 4] = [WHITE, [0x7B, 0xFF, 0x31], [0x00, 0x84, 0x00], BLACK];
+
+impl Palette {
+    pub fn uniform(name: &str, colors: [Rgb; 4]) -> Palette {
+        Palette {
+            name: name.to_string(),
+            bg: colors,
+            obj0: colors,
+            obj1: colors,
+        }
+    }
+
+    fn layered(name: &str, bg: [Rgb; 4], obj0: [Rgb; 4], obj1: [Rgb; 4]) -> Palette {
+        Palette {
+            name: name.to_string(),
+            bg,
+            obj0,
+            obj1,
+        }
+    }
+
+    pub fn grayscale() -> Palette {
+        Palette::uniform(
+            "grayscale",
+            [WHITE, [0x8C, 0x8C, 0x8C], [0x30, 0x30, 0x30], BLACK],
+        )
+    }
+
+    pub fn from_config(config: &PaletteConfig) -> Result<Palette, String> {
+        let layer = |colors: Option<[Rgb; 4]>| {
+            colors.or(config.colors).ok_or_else(|| {
+                format!(
+                    "palette {:?} needs colors or bg, obj0 and obj1",
+                    config.name
+                )
+            })
+        };
+        Ok(Palette::layered(
+            &config.name,
+            layer(config.bg)?,
+            layer(config.obj0)?,
+            layer(config.obj1)?,
+        ))
+    }
+
+    // The shades the original DMG screen and the CGB boot rom's manual selection offer
+    pub fn builtin() -> Vec<Palette> {
+        vec![
+            Palette::grayscale(),
+            Palette::uniform(
+                "dmg green",
+                [
+                    [0x9C, 0xBD, 0x0F],
+                    [0x8C, 0xAD, 0x0F],
+                    [0x30, 0x62, 0x30],
+                    [0x0F, 0x38, 0x0F],
+                ],
+            ),
+            Palette::uniform("cgb brown", BROWN),
+            Palette::uniform("cgb red", RED),
+            Palette::uniform(
+                "cgb dark brown",
+                [
+                    [0xFF, 0xE6, 0xC5],
+                    [0xCE, 0x9C, 0x84],
+                    [0x84, 0x6B, 0x29],
+                    [0x5A, 0x31, 0x08],
+                ],
+            ),
+            Palette::layered("cgb blue", BLUE, RED, BLUE),
+            Palette::layered(
+                "cgb dark blue",
+                [WHITE, [0x8C, 0x8C, 0xDE], [0x52, 0x52, 0x8C], BLACK],
+                RED,
+                BROWN,
+            ),
+            Palette::uniform(
+                "cgb gray",
+                [WHITE, [0xA5, 0xA5, 0xA5], [0x52, 0x52, 0x52], BLACK],
+            ),
+            Palette::uniform(
+                "cgb pale yellow",
+                [
+                    [0xFF, 0xFF, 0xA5],
+                    [0xFF, 0x94, 0x94],
+                    [0x94, 0x94, 0xFF],
+                    BLACK,
+                ],
+            ),
+            Palette::uniform(
+                "cgb orange",
+                [WHITE, [0xFF, 0xFF, 0x00], [0xFF, 0x00, 0x00], BLACK],
+            ),
+            Palette::layered(
+                "cgb yellow",
+                [WHITE, [0xFF, 0xFF, 0x00], [0x7B, 0x4A, 0x00], BLACK],
+                BLUE,
+                GREEN,
+            ),
+            Palette::uniform(
+                "cgb green",
+                [WHITE, [0x52, 0xFF, 0x00], [0xFF, 0x42, 0x00], BLACK],
+            ),
+            Palette::layered(
+                "cgb dark green",
+                [WHITE, [0x7B, 0xFF, 0x31], [0x00, 0x63, 0xC5], BLACK],
+                RED,
+                RED,
+            ),
+            Palette::uniform(
+                "cgb inverted",
+                [BLACK, [0x00, 0x84, 0x84], [0xFF, 0xDE, 0x00], WHITE],
+            ),
+        ]
+    }
+
+    // The CGB boot rom colorizes Nintendo published DMG games by the sum of their title
+    // bytes, with the fourth title letter telling apart games whose sums collide. Other
+    // games keep the chosen palette.
+    pub fn cgb_compatibility(cartridge: &Cartridge) -> Option<Palette> {
+        let nintendo = match &cartridge.licensee {
+            Licensee::Old(code) => *code == 0x01,
+            Licensee::New(code) => code == "01",
+        };
+        if cartridge.cgb_support != CgbSupport::None || !nintendo {
+            return None;
+        }
+        let letter = cartridge.title.as_bytes().get(3).copied().unwrap_or(0);
+        let entry = (0..CGB_TITLE_CHECKSUMS.len()).find(|&i| {
+            CGB_TITLE_CHECKSUMS[i] == cartridge.title_checksum
+                && (i < CGB_FIRST_DUPLICATE
+                    || CGB_FOURTH_LETTERS[i - CGB_FIRST_DUPLICATE] == letter)
+        })?;
+        let [obj0, obj1, bg] = CGB_COMBINATIONS[CGB_COMBINATION_PER_TITLE[entry]].map(|start| {
+            std::array::from_fn(|i| {
+                let color = CGB_COLORS[(start + i) / 4][(start + i) % 4];
+                [0, 5, 10].map(|shift| ((((color >> shift) & 0x1F) as u32 * 255 + 15) / 31) as u8)
+            })
+        });
+        // Name it after a manual selection with the same colors when there is one
+        let colors = Palette::layered("cgb compatibility", bg, obj0, obj1);
+        Some(
+            Palette::builtin()
+                .into_iter()
+                .find(|p| (p.bg, p.obj0, p.obj1) == (bg, obj0, obj1))
+                .unwrap_or(colors),
+        )
+    }
+}
+
+// The CGB boot rom's tables. Checksums from CGB_FIRST_DUPLICATE on are shared by several
+// titles and also need the matching fourth letter.
+const CGB_FIRST_DUPLICATE: usize = 65;
+
+const CGB_TITLE_CHECKSUMS: [u8; 94] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58, 0xC9, 0x3E, 0x70,
+    0x1D, 0x59, 0x69, 0x19, 0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95, 0x99, 0x34, 0x6F, 0x15, 0xFF, 0x97,
+    0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6, 0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE,
+    0x0C, 0x29, 0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD, 0x5D, 0x6D, 0x67, 0x3F,
+    0x6B, 0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, 0xB3,
+    0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, 0xB3,
+];
+
+const CGB_FOURTH_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+// Index into CGB_COMBINATIONS for each checksum above
+const CGB_COMBINATION_PER_TITLE: [usize; 94] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44, 21, 32, 31, 20, 5, 33, 13, 14, 5, 29,
+    5, 18, 9, 3, 2, 26, 25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34, 34, 5, 42, 6,
+    5, 33, 25, 42, 42, 40, 2, 16, 25, 42, 42, 5, 0, 39, 36, 22, 25, 6, 32, 12, 36, 11, 39, 18, 39,
+    24, 31, 50, 17, 46, 6, 27, 0, 47, 41, 41, 0, 0, 19, 34, 23, 18, 29,
+];
+
+// OBJ0, OBJ1 and BG as the index of their first color in CGB_COLORS. A few start part way
+// into a palette, as they do in the boot rom.
+const CGB_COMBINATIONS: [[usize; 3]; 51] = [
+    [16, 16, 116],
+    [72, 72, 72],
+    [80, 80, 80],
+    [96, 96, 96],
+    [36, 36, 36],
+    [0, 0, 0],
+    [108, 108, 108],
+    [20, 20, 20],
+    [48, 48, 48],
+    [104, 104, 104],
+    [64, 32, 32],
+    [16, 112, 112],
+    [16, 8, 8],
+    [12, 16, 16],
+    [16, 116, 116],
+    [112, 16, 112],
+    [8, 68, 8],
+    [64, 64, 32],
+    [16, 16, 28],
+    [16, 16, 72],
+    [16, 16, 80],
+    [76, 76, 36],
+    [15, 15, 44],
+    [68, 68, 8],
+    [16, 16, 8],
+    [16, 16, 12],
+    [112, 112, 0],
+    [12, 12, 0],
+    [0, 0, 4],
+    [72, 88, 72],
+    [80, 88, 80],
+    [96, 88, 96],
+    [64, 88, 32],
+    [68, 16, 52],
+    [111, 0, 56],
+    [111, 16, 60],
+    [76, 88, 36],
+    [64, 112, 40],
+    [16, 92, 112],
+    [68, 88, 8],
+    [16, 0, 8],
+    [16, 112, 12],
+    [112, 12, 0],
+    [12, 112, 16],
+    [84, 112, 16],
+    [12, 112, 0],
+    [100, 12, 112],
+    [0, 112, 32],
+    [16, 12, 112],
+    [112, 12, 24],
+    [16, 112, 116],
+];
+
+// BGR555, four colors per palette
+const CGB_COLORS: [[u16; 4]; 30] = [
+    [0x7FFF, 0x32BF, 0x00D0, 0x0000],
+    [0x639F, 0x4279, 0x15B0, 0x04CB],
+    [0x7FFF, 0x6E31, 0x454A, 0x0000],
+    [0x7FFF, 0x1BEF, 0x0200, 0x0000],
+    [0x7FFF, 0x421F, 0x1CF2, 0x0000],
+    [0x7FFF, 0x5294, 0x294A, 0x0000],
+    [0x7FFF, 0x03FF, 0x012F, 0x0000],
+    [0x7FFF, 0x03EF, 0x01D6, 0x0000],
+    [0x7FFF, 0x42B5, 0x3DC8, 0x0000],
+    [0x7E74, 0x03FF, 0x0180, 0x0000],
+    [0x67FF, 0x77AC, 0x1A13, 0x2D6B],
+    [0x7ED6, 0x4BFF, 0x2175, 0x0000],
+    [0x53FF, 0x4A5F, 0x7E52, 0x0000],
+    [0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0],
+    [0x03ED, 0x7FFF, 0x255F, 0x0000],
+    [0x036A, 0x021F, 0x03FF, 0x7FFF],
+    [0x7FFF, 0x01DF, 0x0112, 0x0000],
+    [0x231F, 0x035F, 0x00F2, 0x0009],
+    [0x7FFF, 0x03EA, 0x011F, 0x0000],
+    [0x299F, 0x001A, 0x000C, 0x0000],
+    [0x7FFF, 0x027F, 0x001F, 0x0000],
+    [0x7FFF, 0x03E0, 0x0206, 0x0120],
+    [0x7FFF, 0x7EEB, 0x001F, 0x7C00],
+    [0x7FFF, 0x3FFF, 0x7E00, 0x001F],
+    [0x7FFF, 0x03FF, 0x001F, 0x0000],
+    [0x03FF, 0x001F, 0x000C, 0x0000],
+    [0x7FFF, 0x033F, 0x0193, 0x0000],
+    [0x0000, 0x4200, 0x037F, 0x7FFF],
+    [0x7FFF, 0x7E8C, 0x7C00, 0x0000],
+    [0x7FFF, 0x1BEF, 0x6180, 0x0000],
+];
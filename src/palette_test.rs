@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use crate::cartridge::Cartridge;
+    use crate::palette::{Palette, PaletteConfig};
+
+    fn make_rom(title: &str, old_licensee: u8) -> Vec<u8> {
+        let mut rom = vec![0; 32 * 1024];
+        rom[0x134..0x134 + title.len()].copy_from_slice(title.as_bytes());
+        rom[0x14B] = old_licensee;
+        rom
+    }
+
+    #[test]
+    fn test_from_config() {
+        let white = [0xFF; 3];
+        let black = [0x00; 3];
+        let config = PaletteConfig {
+            name: "mine".to_string(),
+            colors: Some([white, white, black, black]),
+            obj1: Some([black; 4]),
+            ..PaletteConfig::default()
+        };
+        let palette = Palette::from_config(&config).unwrap();
+        assert_eq!(palette.bg, [white, white, black, black]);
+        assert_eq!(palette.obj0, palette.bg);
+        assert_eq!(palette.obj1, [black; 4]);
+
+        let config = PaletteConfig {
+            name: "partial".to_string(),
+            bg: Some([white; 4]),
+            ..PaletteConfig::default()
+        };
+        assert!(Palette::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_builtin_names_are_unique() {
+        let builtin = Palette::builtin();
+        for (i, palette) in builtin.iter().enumerate() {
+            assert!(
+                builtin[i + 1..].iter().all(|p| p.name != palette.name),
+                "{} listed twice",
+                palette.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_cgb_compatibility() {
+        let cartridge = Cartridge::new(&make_rom("POKEMON RED", 0x01)).unwrap();
+        assert_eq!(cartridge.title_checksum, 0x14);
+        let palette = Palette::cgb_compatibility(&cartridge).unwrap();
+        assert_eq!(palette.name, "cgb compatibility");
+        let red = [[0xFF; 3], [0xFF, 0x84, 0x84], [0x94, 0x3A, 0x3A], [0x00; 3]];
+        let green = [[0xFF; 3], [0x7B, 0xFF, 0x31], [0x00, 0x84, 0x00], [0x00; 3]];
+        assert_eq!((palette.bg, palette.obj0, palette.obj1), (red, green, red));
+
+        let mut rom = make_rom("POKEMON BLUE", 0x33);
+        rom[0x144..0x146].copy_from_slice(b"01");
+        let cartridge = Cartridge::new(&rom).unwrap();
+        assert_eq!(
+            Palette::cgb_compatibility(&cartridge).unwrap().name,
+            "cgb blue"
+        );
+
+        // The boot rom only colorizes Nintendo's own games
+        let cartridge = Cartridge::new(&make_rom("POKEMON RED", 0x02)).unwrap();
+        assert_eq!(Palette::cgb_compatibility(&cartridge), None);
+
+        let cartridge = Cartridge::new(&make_rom("UNKNOWN", 0x01)).unwrap();
+        assert_eq!(Palette::cgb_compatibility(&cartridge), None);
+    }
+
+    #[test]
+    fn test_cgb_compatibility_titles() {
+        let name = |title: &str| {
+            let cartridge = Cartridge::new(&make_rom(title, 0x01)).unwrap();
+            Palette::cgb_compatibility(&cartridge).map(|p| p.name)
+        };
+        assert_eq!(name("TETRIS").as_deref(), Some("cgb orange"));
+        assert_eq!(name("DR.MARIO").as_deref(), Some("cgb compatibility"));
+
+        // SUPER MARIOLAND shares its checksum with another title, the fourth letter decides
+        let cartridge = Cartridge::new(&make_rom("SUPER MARIOLAND", 0x01)).unwrap();
+        assert_eq!(cartridge.title_checksum, 0x46);
+        let mario = Palette::cgb_compatibility(&cartridge).unwrap();
+        assert_eq!(mario.bg[1], [0xFF, 0xFF, 0x94]);
+
+        // Same sum with R as the fourth letter picks the other entry
+        let cartridge = Cartridge::new(&make_rom("SUPR EMARIOLAND", 0x01)).unwrap();
+        assert_eq!(cartridge.title_checksum, 0x46);
+        let other = Palette::cgb_compatibility(&cartridge).unwrap();
+        assert_ne!(other, mario);
+
+        // A shared sum without a matching letter isn't colorized
+        let cartridge = Cartridge::new(&make_rom("GOLXZZX", 0x01)).unwrap();
+        assert_eq!(cartridge.title_checksum, 0x46);
+        assert_eq!(Palette::cgb_compatibility(&cartridge), None);
+    }
+}
@@ -0,0 +1,274 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::Deserialize;
+
+use crate::memory::SAMPLE_RATE;
+use crate::screenshot::timestamped_name;
+use crate::video::{BYTES_PER_PIXEL, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const CPU_HZ: u32 = 4_194_304;
+// 154 lines of 456 cycles, the LCD's real refresh of about 59.73Hz
+pub const VBLANK_PERIOD: u32 = 70_224;
+
+const FRAME_BYTES: u32 = (SCREEN_WIDTH * SCREEN_HEIGHT * 3) as u32;
+// 16 bit stereo PCM
+const SAMPLE_BYTES: u32 = 4;
+const AVIF_HASINDEX: u32 = 0x10;
+const AVIIF_KEYFRAME: u32 = 0x10;
+// Offsets into the header that are only known once recording stops
+const RIFF_SIZE_AT: u64 = 4;
+const TOTAL_FRAMES_AT: u64 = 48;
+const STREAM_LENGTH_AT: u64 = 140;
+const AUDIO_LENGTH_AT: u64 = 264;
+const MOVI_SIZE_AT: u64 = 316;
+const MOVI_START: u64 = 320;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RecordingConfig {
+    pub directory: String,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        RecordingConfig {
+            directory: ".".to_string(),
+        }
+    }
+}
+
+// Uncompressed 24 bit video with 16 bit PCM audio, readable by about every player and
+// editor
+pub struct AviWriter<W: Write + Seek> {
+    out: W,
+    // Chunk ids, offsets from the movi list and sizes, for the index written at the end
+    chunks: Vec<(&'static [u8; 4], u32, u32)>,
+    frames: usize,
+    samples: usize,
+    position: u64,
+}
+
+impl<W: Write + Seek> AviWriter<W> {
+    pub fn new(mut out: W) -> io::Result<AviWriter<W>> {
+        let mut header = Vec::with_capacity(MOVI_START as usize + 4);
+        let u32le = |header: &mut Vec<u8>, val: u32| header.extend(val.to_le_bytes());
+        header.extend(b"RIFF");
+        u32le(&mut header, 0);
+        header.extend(b"AVI LIST");
+        u32le(&mut header, 292);
+        header.extend(b"hdrlavih");
+        u32le(&mut header, 56);
+        for val in [
+            (VBLANK_PERIOD as u64 * 1_000_000 / CPU_HZ as u64) as u32,
+            FRAME_BYTES * 60 + SAMPLE_RATE * SAMPLE_BYTES,
+            0,
+            AVIF_HASINDEX,
+            0, // total frames
+            0,
+            2,
+            FRAME_BYTES,
+            SCREEN_WIDTH as u32,
+            SCREEN_HEIGHT as u32,
+            0,
+            0,
+            0,
+            0,
+        ] {
+            u32le(&mut header, val);
+        }
+        header.extend(b"LIST");
+        u32le(&mut header, 116);
+        header.extend(b"strlstrh");
+        u32le(&mut header, 56);
+        header.extend(b"vidsDIB ");
+        u32le(&mut header, 0);
+        u32le(&mut header, 0); // priority and language
+        for val in [0, VBLANK_PERIOD, CPU_HZ, 0, 0, FRAME_BYTES, u32::MAX, 0] {
+            u32le(&mut header, val);
+        }
+        for val in [0u16, 0, SCREEN_WIDTH as u16, SCREEN_HEIGHT as u16] {
+            header.extend(val.to_le_bytes());
+        }
+        header.extend(b"strf");
+        u32le(&mut header, 40);
+        // BITMAPINFOHEADER, a positive height means rows are stored bottom up
+        for val in [40, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32] {
+            u32le(&mut header, val);
+        }
+        header.extend(1u16.to_le_bytes());
+        header.extend(24u16.to_le_bytes());
+        for val in [0, FRAME_BYTES, 0, 0, 0, 0] {
+            u32le(&mut header, val);
+        }
+        header.extend(b"LIST");
+        u32le(&mut header, 92);
+        header.extend(b"strlstrh");
+        u32le(&mut header, 56);
+        header.extend(b"auds");
+        u32le(&mut header, 0);
+        u32le(&mut header, 0);
+        u32le(&mut header, 0); // priority and language
+        for val in [
+            0,
+            SAMPLE_BYTES,
+            SAMPLE_RATE * SAMPLE_BYTES,
+            0,
+            0, // length in samples
+            SAMPLE_RATE * SAMPLE_BYTES / 60,
+            u32::MAX,
+            SAMPLE_BYTES,
+        ] {
+            u32le(&mut header, val);
+        }
+        u32le(&mut header, 0);
+        u32le(&mut header, 0);
+        header.extend(b"strf");
+        u32le(&mut header, 16);
+        // WAVEFORMATEX without the extra size field, as plain PCM has none
+        for val in [1u16, 2] {
+            header.extend(val.to_le_bytes());
+        }
+        for val in [SAMPLE_RATE, SAMPLE_RATE * SAMPLE_BYTES] {
+            u32le(&mut header, val);
+        }
+        for val in [SAMPLE_BYTES as u16, 16] {
+            header.extend(val.to_le_bytes());
+        }
+        header.extend(b"LIST");
+        u32le(&mut header, 0);
+        header.extend(b"movi");
+        out.write_all(&header)?;
+        Ok(AviWriter {
+            out,
+            chunks: Vec::new(),
+            frames: 0,
+            samples: 0,
+            position: header.len() as u64,
+        })
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    // Appends a chunk to the movi list, data is padded to an even length
+    fn write_chunk(&mut self, id: &'static [u8; 4], data: &[u8]) -> io::Result<()> {
+        let size = data.len() as u64;
+        let chunk = 8 + size + size % 2;
+        let index = 16 * (self.chunks.len() as u64 + 1);
+        if self.position + chunk + 8 + index > u32::MAX as u64 {
+            return Err(io::Error::other(
+                "the recording reached the 4GB AVI size limit",
+            ));
+        }
+        self.out.write_all(id)?;
+        self.out.write_all(&(size as u32).to_le_bytes())?;
+        self.out.write_all(data)?;
+        if size % 2 == 1 {
+            self.out.write_all(&[0])?;
+        }
+        self.chunks
+            .push((id, (self.position - MOVI_START) as u32, size as u32));
+        self.position += chunk;
+        Ok(())
+    }
+
+    // Takes an RGBA framebuffer
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        let mut data = Vec::with_capacity(FRAME_BYTES as usize);
+        for row in frame.chunks_exact(SCREEN_WIDTH * BYTES_PER_PIXEL).rev() {
+            for rgba in row.chunks_exact(BYTES_PER_PIXEL) {
+                data.extend([rgba[2], rgba[1], rgba[0]]);
+            }
+        }
+        self.write_chunk(b"00db", &data)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    // Takes interleaved left and right samples
+    pub fn write_audio(&mut self, samples: &[i16]) -> io::Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.write_chunk(b"01wb", &data)?;
+        self.samples += samples.len() / 2;
+        Ok(())
+    }
+
+    // Writes the index and the sizes left open in the header
+    pub fn finish(mut self) -> io::Result<W> {
+        let movi_size = (self.position - MOVI_START) as u32;
+        let mut index = Vec::with_capacity(8 + 16 * self.chunks.len());
+        index.extend(b"idx1");
+        index.extend((16 * self.chunks.len() as u32).to_le_bytes());
+        for (id, offset, size) in &self.chunks {
+            index.extend(*id);
+            index.extend(AVIIF_KEYFRAME.to_le_bytes());
+            index.extend(offset.to_le_bytes());
+            index.extend(size.to_le_bytes());
+        }
+        self.out.write_all(&index)?;
+        let riff_size = (self.position + index.len() as u64 - 8) as u32;
+        let frames = self.frames as u32;
+        for (at, val) in [
+            (RIFF_SIZE_AT, riff_size),
+            (TOTAL_FRAMES_AT, frames),
+            (STREAM_LENGTH_AT, frames),
+            (AUDIO_LENGTH_AT, self.samples as u32),
+            (MOVI_SIZE_AT, movi_size),
+        ] {
+            self.out.seek(SeekFrom::Start(at))?;
+            self.out.write_all(&val.to_le_bytes())?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+// A recording in progress, fed every emulated frame by the core
+pub struct Recorder {
+    path: PathBuf,
+    writer: AviWriter<BufWriter<File>>,
+}
+
+impl Recorder {
+    pub fn start(path: &Path) -> Result<Recorder, String> {
+        let describe = |err: io::Error| format!("{}: {err}", path.display());
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(describe)?;
+        }
+        let file = File::create(path).map_err(describe)?;
+        let writer = AviWriter::new(BufWriter::new(file)).map_err(describe)?;
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            writer,
+        })
+    }
+
+    pub fn start_in(config: &RecordingConfig) -> Result<Recorder, String> {
+        let name = timestamped_name("recording", "avi", SystemTime::now());
+        Recorder::start(&Path::new(&config.directory).join(name))
+    }
+
+    // One emulated frame and the audio played during it
+    pub fn write_frame(&mut self, frame: &[u8], samples: &[i16]) -> Result<(), String> {
+        self.writer
+            .write_frame(frame)
+            .and_then(|()| self.writer.write_audio(samples))
+            .map_err(|err| format!("{}: {err}", self.path.display()))
+    }
+
+    // Returns the file and how many frames it holds
+    pub fn finish(self) -> Result<(PathBuf, usize), String> {
+        let frames = self.writer.frames();
+        self.writer
+            .finish()
+            .map_err(|err| format!("{}: {err}", self.path.display()))?;
+        Ok((self.path, frames))
+    }
+}
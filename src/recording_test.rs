@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::memory::SAMPLE_RATE;
+    use crate::recording::AviWriter;
+    use crate::video::{FRAMEBUFFER_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    fn frame(shade: u8) -> Vec<u8> {
+        let mut frame = vec![shade; FRAMEBUFFER_SIZE];
+        // Top left pixel pure red
+        frame[..4].copy_from_slice(&[0xFF, 0x00, 0x00, 0xFF]);
+        frame
+    }
+
+    #[test]
+    fn test_avi_layout() {
+        let mut writer = AviWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_frame(&frame(0xFF)).unwrap();
+        writer.write_frame(&frame(0x00)).unwrap();
+        assert_eq!(writer.frames(), 2);
+        let data = writer.finish().unwrap().into_inner();
+
+        let frame_bytes = SCREEN_WIDTH * SCREEN_HEIGHT * 3;
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(u32_at(&data, 4) as usize, data.len() - 8);
+        assert_eq!(&data[8..12], b"AVI ");
+        assert_eq!(&data[24..28], b"avih");
+        assert_eq!(u32_at(&data, 48), 2, "total frames");
+        assert_eq!(u32_at(&data, 56), 2, "streams");
+        assert_eq!(&data[108..116], b"vidsDIB ");
+        assert_eq!(u32_at(&data, 140), 2, "stream length");
+        assert_eq!(&data[232..236], b"auds");
+        assert_eq!(u32_at(&data, 264), 0, "audio length");
+        assert_eq!(&data[312..316], b"LIST");
+        assert_eq!(&data[320..324], b"movi");
+        assert_eq!(u32_at(&data, 316) as usize, 4 + 2 * (8 + frame_bytes));
+
+        let first = 324;
+        assert_eq!(&data[first..first + 4], b"00db");
+        assert_eq!(u32_at(&data, first + 4) as usize, frame_bytes);
+        // Bottom up BGR, so the red top left pixel starts the last row
+        let pixels = &data[first + 8..first + 8 + frame_bytes];
+        let last_row = (SCREEN_HEIGHT - 1) * SCREEN_WIDTH * 3;
+        assert_eq!(pixels[last_row..last_row + 3], [0x00, 0x00, 0xFF]);
+        assert_eq!(pixels[..3], [0xFF; 3]);
+
+        let index = first + 2 * (8 + frame_bytes);
+        assert_eq!(&data[index..index + 4], b"idx1");
+        assert_eq!(u32_at(&data, index + 4), 32);
+        assert_eq!(u32_at(&data, index + 16), 4);
+        assert_eq!(u32_at(&data, index + 32) as usize, 4 + 8 + frame_bytes);
+        assert_eq!(data.len(), index + 8 + 32);
+    }
+
+    #[test]
+    fn test_audio_stream() {
+        let mut writer = AviWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_frame(&frame(0xFF)).unwrap();
+        writer.write_audio(&[1, -1, 2, -2, 3, -3]).unwrap();
+        // A frame without audio doesn't get an empty chunk
+        writer.write_frame(&frame(0x00)).unwrap();
+        writer.write_audio(&[]).unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        // 16 bit stereo PCM at the APU's sample rate
+        assert_eq!(&data[288..292], b"strf");
+        assert_eq!(u32_at(&data, 296), 0x0002_0001, "PCM, two channels");
+        assert_eq!(u32_at(&data, 300), SAMPLE_RATE);
+        assert_eq!(u32_at(&data, 304), SAMPLE_RATE * 4);
+        assert_eq!(u32_at(&data, 264), 3, "audio length");
+
+        let frame_bytes = SCREEN_WIDTH * SCREEN_HEIGHT * 3;
+        let audio = 324 + 8 + frame_bytes;
+        assert_eq!(&data[audio..audio + 4], b"01wb");
+        assert_eq!(u32_at(&data, audio + 4), 12);
+        assert_eq!(data[audio + 8..audio + 12], [1, 0, 0xFF, 0xFF]);
+
+        let index = audio + 8 + 12 + 8 + frame_bytes;
+        assert_eq!(&data[index..index + 4], b"idx1");
+        assert_eq!(u32_at(&data, index + 4), 48);
+        assert_eq!(&data[index + 24..index + 28], b"01wb");
+        assert_eq!(u32_at(&data, index + 32) as usize, 4 + 8 + frame_bytes);
+        assert_eq!(u32_at(&data, index + 36), 12);
+        assert_eq!(&data[index + 40..index + 44], b"00db");
+    }
+
+    #[test]
+    fn test_empty_recording() {
+        let writer = AviWriter::new(Cursor::new(Vec::new())).unwrap();
+        let data = writer.finish().unwrap().into_inner();
+        assert_eq!(data.len(), 324 + 8);
+        assert_eq!(u32_at(&data, 48), 0);
+        assert_eq!(u32_at(&data, 316), 4);
+    }
+}
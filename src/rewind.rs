@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+
+use serde::Deserialize;
+
+// A keyframe is stored whole, the snapshots after it only as the bytes that changed
+const KEYFRAME_EVERY: usize = 60;
+// Eviction drops a whole group, so groups stay small next to the buffer to keep close to
+// the configured number of seconds
+const MIN_GROUPS: usize = 8;
+const FRAMES_PER_SECOND: u32 = 60;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RewindConfig {
+    pub enabled: bool,
+    // Rewinding shows one snapshot per frame, so this is also the rewind speed
+    pub interval_frames: u32,
+    pub seconds: u32,
+    pub max_megabytes: u32,
+}
+
+impl Default for RewindConfig {
+    fn default() -> Self {
+        RewindConfig {
+            enabled: true,
+            interval_frames: 2,
+            seconds: 30,
+            max_megabytes: 64,
+        }
+    }
+}
+
+struct Group {
+    keyframe: Vec<u8>,
+    deltas: Vec<Vec<u8>>,
+}
+
+pub struct RewindBuffer {
+    config: RewindConfig,
+    groups: VecDeque<Group>,
+    frames_since_snapshot: u32,
+    len: usize,
+    bytes: usize,
+}
+
+// Runs of (unchanged length, changed length) as little endian u16 followed by the changed
+// bytes. Trailing unchanged bytes are implied.
+pub fn encode_delta(base: &[u8], state: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let mut pos = 0;
+    while pos < state.len() {
+        let same = state[pos..]
+            .iter()
+            .zip(&base[pos..])
+            .take(u16::MAX as usize)
+            .take_while(|(new, old)| new == old)
+            .count();
+        let start = pos + same;
+        if start == state.len() {
+            break;
+        }
+        let changed = state[start..]
+            .iter()
+            .zip(&base[start..])
+            .take(u16::MAX as usize)
+            .take_while(|(new, old)| new != old)
+            .count();
+        delta.extend_from_slice(&(same as u16).to_le_bytes());
+        delta.extend_from_slice(&(changed as u16).to_le_bytes());
+        delta.extend_from_slice(&state[start..start + changed]);
+        pos = start + changed;
+    }
+    delta
+}
+
+pub fn decode_delta(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut state = base.to_vec();
+    let mut pos = 0;
+    let mut i = 0;
+    while i + 4 <= delta.len() {
+        let same = u16::from_le_bytes([delta[i], delta[i + 1]]) as usize;
+        let changed = u16::from_le_bytes([delta[i + 2], delta[i + 3]]) as usize;
+        pos += same;
+        state[pos..pos + changed].copy_from_slice(&delta[i + 4..i + 4 + changed]);
+        pos += changed;
+        i += 4 + changed;
+    }
+    state
+}
+
+impl RewindBuffer {
+    pub fn new(config: RewindConfig) -> RewindBuffer {
+        RewindBuffer {
+            config,
+            groups: VecDeque::new(),
+            frames_since_snapshot: 0,
+            len: 0,
+            bytes: 0,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        (self.config.seconds * FRAMES_PER_SECOND / self.config.interval_frames.max(1)).max(1)
+            as usize
+    }
+
+    fn group_size(&self) -> usize {
+        (self.capacity() / MIN_GROUPS).clamp(1, KEYFRAME_EVERY)
+    }
+
+    fn max_bytes(&self) -> usize {
+        self.config.max_megabytes as usize * 1024 * 1024
+    }
+
+    // Called once per emulated frame, true when a snapshot should be pushed
+    pub fn frame_due(&mut self) -> bool {
+        self.frames_since_snapshot += 1;
+        if self.frames_since_snapshot < self.config.interval_frames {
+            return false;
+        }
+        self.frames_since_snapshot = 0;
+        true
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        let group_size = self.group_size();
+        match self.groups.back_mut() {
+            Some(group)
+                if group.deltas.len() + 1 < group_size && group.keyframe.len() == state.len() =>
+            {
+                let delta = encode_delta(&group.keyframe, &state);
+                self.bytes += delta.len();
+                group.deltas.push(delta);
+            }
+            _ => {
+                self.bytes += state.len();
+                self.groups.push_back(Group {
+                    keyframe: state,
+                    deltas: Vec::new(),
+                });
+            }
+        }
+        self.len += 1;
+        // Deltas are useless without their keyframe, so whole groups are dropped at once
+        while self.groups.len() > 1 && (self.len > self.capacity() || self.bytes > self.max_bytes())
+        {
+            if let Some(group) = self.groups.pop_front() {
+                self.len -= 1 + group.deltas.len();
+                self.bytes -=
+                    group.keyframe.len() + group.deltas.iter().map(Vec::len).sum::<usize>();
+            }
+        }
+    }
+
+    // Newest snapshot first
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let group = self.groups.back_mut()?;
+        let state = match group.deltas.pop() {
+            Some(delta) => {
+                self.bytes -= delta.len();
+                decode_delta(&group.keyframe, &delta)
+            }
+            None => {
+                let group = self.groups.pop_back()?;
+                self.bytes -= group.keyframe.len();
+                group.keyframe
+            }
+        };
+        self.len -= 1;
+        self.frames_since_snapshot = 0;
+        Some(state)
+    }
+
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.len = 0;
+        self.bytes = 0;
+    }
+}
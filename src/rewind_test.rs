@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Register;
+    use crate::emulator::{Emulator, RunConfig};
+    use crate::rewind::{RewindBuffer, RewindConfig, decode_delta, encode_delta};
+
+    fn config(seconds: u32, max_megabytes: u32) -> RewindConfig {
+        RewindConfig {
+            enabled: true,
+            interval_frames: 1,
+            seconds,
+            max_megabytes,
+        }
+    }
+
+    #[test]
+    fn test_delta_roundtrip() {
+        let base: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        // Only the run length headers for the long unchanged stretch
+        assert_eq!(encode_delta(&base, &base).len(), 4 * 3);
+
+        let mut state = base.clone();
+        state[0] ^= 1;
+        state[10..20].fill(0xaa);
+        state[100_000..170_000].fill(0x55);
+        state[199_999] ^= 0xff;
+        let delta = encode_delta(&base, &state);
+        assert!(delta.len() < state.len());
+        assert_eq!(decode_delta(&base, &delta), state);
+    }
+
+    #[test]
+    fn test_newest_snapshot_first() {
+        let mut buffer = RewindBuffer::new(config(10, 64));
+        for i in 0..100u8 {
+            buffer.push(vec![i; 64]);
+        }
+        for i in (0..100u8).rev() {
+            assert_eq!(buffer.pop(), Some(vec![i; 64]));
+        }
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn test_time_budget_keeps_about_the_configured_seconds() {
+        // One second is 60 snapshots, split into groups of 7
+        let mut buffer = RewindBuffer::new(config(1, 64));
+        for i in 0..130u32 {
+            buffer.push(i.to_le_bytes().to_vec());
+        }
+        let mut remaining = Vec::new();
+        while let Some(state) = buffer.pop() {
+            remaining.push(u32::from_le_bytes(state.try_into().unwrap()));
+        }
+        assert!(remaining.len() > 60 - 7 && remaining.len() <= 60);
+        assert_eq!(remaining.first(), Some(&129));
+        assert_eq!(remaining.last(), Some(&(130 - remaining.len() as u32)));
+    }
+
+    #[test]
+    fn test_memory_cap_keeps_newest_group() {
+        let mut buffer = RewindBuffer::new(config(60, 1));
+        for i in 0..3u8 {
+            // Completely different states cost a full keyframe each time
+            for j in 0..60u8 {
+                buffer.push(vec![i.wrapping_mul(60).wrapping_add(j); 100_000]);
+            }
+        }
+        let mut count = 0;
+        while buffer.pop().is_some() {
+            count += 1;
+        }
+        assert!(count < 180);
+        assert!(count >= 1);
+    }
+
+    #[test]
+    fn test_snapshot_interval() {
+        let mut buffer = RewindBuffer::new(RewindConfig {
+            interval_frames: 3,
+            ..RewindConfig::default()
+        });
+        let due: Vec<bool> = (0..6).map(|_| buffer.frame_due()).collect();
+        assert_eq!(due, vec![false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn test_save_and_load_state() {
+        let path = std::env::temp_dir().join("rewind_test_state.gb");
+        let mut rom = vec![0; 32 * 1024];
+        // INC A; LD ($C000),A; JR -6
+        rom[0x100..0x106].copy_from_slice(&[0x3c, 0xea, 0x00, 0xc0, 0x18, 0xfa]);
+        std::fs::write(&path, rom).unwrap();
+        let mut emulator = Emulator::new(RunConfig::default());
+        emulator
+            .load_rom(&path.to_string_lossy().to_string())
+            .unwrap();
+
+        let state = emulator.save_state();
+        for _ in 0..30 {
+            emulator.step().unwrap();
+        }
+        assert_eq!(emulator.cpu().get_reg(Register::A), 11);
+        emulator.record_rewind_frame();
+        emulator.record_rewind_frame();
+
+        emulator.load_state(&state).unwrap();
+        assert_eq!(emulator.cpu().get_reg(Register::A), 1);
+        assert_eq!(emulator.cpu().PC(), 0x100);
+        assert_eq!(emulator.memory().peek(0xc000), 0);
+        assert_eq!(emulator.save_state(), state);
+
+        assert!(emulator.load_state(&state[1..]).is_err());
+        assert_eq!(emulator.save_state(), state);
+
+        // The default config snapshots every other frame
+        assert!(emulator.rewind_frame());
+        assert_eq!(emulator.cpu().get_reg(Register::A), 11);
+        assert_eq!(emulator.memory().peek(0xc000), 11);
+        assert!(!emulator.rewind_frame());
+    }
+}
@@ -0,0 +1,93 @@
+use crate::video::{self, GBColor};
+
+// Saves or loads machine state through one field list per component, so the save and
+// load paths can't drift apart. Values are little endian with no padding or tags.
+pub struct StateBuffer {
+    data: Vec<u8>,
+    pos: usize,
+    loading: bool,
+    overrun: bool,
+}
+
+macro_rules! number {
+    ($name:ident, $ty:ty) => {
+        pub fn $name(&mut self, val: &mut $ty) {
+            let mut bytes = val.to_le_bytes();
+            self.bytes(&mut bytes);
+            *val = <$ty>::from_le_bytes(bytes);
+        }
+    };
+}
+
+impl StateBuffer {
+    pub fn for_saving() -> StateBuffer {
+        StateBuffer {
+            data: Vec::new(),
+            pos: 0,
+            loading: false,
+            overrun: false,
+        }
+    }
+
+    pub fn for_loading(data: &[u8]) -> StateBuffer {
+        StateBuffer {
+            data: data.to_vec(),
+            pos: 0,
+            loading: true,
+            overrun: false,
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading
+    }
+
+    pub fn bytes(&mut self, val: &mut [u8]) {
+        if !self.loading {
+            self.data.extend_from_slice(val);
+        } else if let Some(saved) = self.data.get(self.pos..self.pos + val.len()) {
+            val.copy_from_slice(saved);
+        } else {
+            self.overrun = true;
+        }
+        self.pos += val.len();
+    }
+
+    number!(u8, u8);
+    number!(u16, u16);
+    number!(u32, u32);
+    number!(u64, u64);
+    number!(u128, u128);
+
+    pub fn usize(&mut self, val: &mut usize) {
+        let mut wide = *val as u64;
+        self.u64(&mut wide);
+        *val = wide as usize;
+    }
+
+    pub fn bool(&mut self, val: &mut bool) {
+        let mut byte = *val as u8;
+        self.u8(&mut byte);
+        *val = byte != 0;
+    }
+
+    pub fn colors(&mut self, colors: &mut [GBColor]) {
+        for color in colors {
+            let mut byte = *color as u8;
+            self.u8(&mut byte);
+            *color = video::byte_to_color(byte & 3);
+        }
+    }
+
+    // The saved bytes, or an error if a load didn't consume exactly the whole state
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        if self.overrun || (self.loading && self.pos != self.data.len()) {
+            return Err(format!(
+                "state is {} bytes but {} were expected",
+                self.data.len(),
+                self.pos
+            ));
+        }
+        Ok(self.data)
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use crate::emulator::{Emulator, RunConfig};
+use crate::input::Input;
+use crate::video::{BYTES_PER_PIXEL, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScreenshotConfig {
+    pub directory: String,
+    // 1 saves the native 160x144, larger values repeat every pixel
+    pub scale: u32,
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        ScreenshotConfig {
+            directory: ".".to_string(),
+            scale: 1,
+        }
+    }
+}
+
+// Nearest neighbour upscale of an RGBA framebuffer, returns the pixels and their size
+pub fn scale_frame(frame: &[u8], scale: u32) -> (Vec<u8>, u32, u32) {
+    scale_pixels(frame, BYTES_PER_PIXEL, scale)
+}
+
+// Same for a 160x144 screen stored with any number of bytes per pixel
+pub fn scale_pixels(frame: &[u8], bytes_per_pixel: usize, scale: u32) -> (Vec<u8>, u32, u32) {
+    let scale = scale.max(1) as usize;
+    let (width, height) = (SCREEN_WIDTH * scale, SCREEN_HEIGHT * scale);
+    let mut scaled = Vec::with_capacity(width * height * bytes_per_pixel);
+    for row in frame.chunks_exact(SCREEN_WIDTH * bytes_per_pixel) {
+        let mut line = Vec::with_capacity(width * bytes_per_pixel);
+        for pixel in row.chunks_exact(bytes_per_pixel) {
+            for _ in 0..scale {
+                line.extend_from_slice(pixel);
+            }
+        }
+        for _ in 0..scale {
+            scaled.extend_from_slice(&line);
+        }
+    }
+    (scaled, width as u32, height as u32)
+}
+
+pub fn write_png(path: &Path, frame: &[u8], scale: u32) -> Result<(), String> {
+    let (pixels, width, height) = scale_frame(frame, scale);
+    let file = File::create(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(|err| format!("{}: {err}", path.display()))
+}
+
+// Like screenshot-20240131-235959-123.png, in UTC
+pub fn timestamped_name(prefix: &str, extension: &str, time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let seconds_of_day = secs % 86_400;
+    format!(
+        "{prefix}-{year:04}{month:02}{day:02}-{:02}{:02}{:02}-{:03}.{extension}",
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+// Days since 1970-01-01 to a proleptic gregorian date, Howard Hinnant's algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+pub fn save(frame: &[u8], config: &ScreenshotConfig) -> Result<PathBuf, String> {
+    fs::create_dir_all(&config.directory).map_err(|err| format!("{}: {err}", config.directory))?;
+    let path =
+        Path::new(&config.directory).join(timestamped_name("screenshot", "png", SystemTime::now()));
+    write_png(&path, frame, config.scale)?;
+    Ok(path)
+}
+
+// screenshot <rom> <frames> [output.png] [scale], runs without a window and saves the
+// screen after the given number of frames
+pub fn run(args: &[String]) {
+    let path_to_rom = args.first().expect("missing rom path");
+    let frames: u32 = args
+        .get(1)
+        .expect("missing frame count")
+        .parse()
+        .expect("invalid frame count");
+    let output = args.get(2).map_or("screenshot.png", |arg| arg.as_str());
+    let scale = args.get(3).map_or(1, |arg| {
+        arg.parse()
+            .unwrap_or_else(|_| panic!("invalid scale: {arg}"))
+    });
+
+    let mut emulator = Emulator::new(RunConfig::default());
+    emulator
+        .load_rom(path_to_rom)
+        .unwrap_or_else(|err| panic!("{path_to_rom}: {err}"));
+    let input = Input::new();
+    let mut clock_t = 0;
+    for _ in 0..frames {
+        crate::run_frame(&mut emulator, &input, None, &mut clock_t);
+    }
+    match write_png(Path::new(output), emulator.screen(), scale) {
+        Ok(()) => println!("Saved {output} after {frames} frames"),
+        Err(err) => println!("Screenshot failed: {err}"),
+    }
+}
@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::screenshot::{scale_frame, timestamped_name, write_png};
+    use crate::video::{FRAMEBUFFER_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    fn test_frame() -> Vec<u8> {
+        let mut frame = vec![0xFF; FRAMEBUFFER_SIZE];
+        // Top left pixel red
+        frame[..4].copy_from_slice(&[0xFF, 0x00, 0x00, 0xFF]);
+        frame
+    }
+
+    #[test]
+    fn test_scale_frame() {
+        let (scaled, width, height) = scale_frame(&test_frame(), 2);
+        assert_eq!((width, height), (320, 288));
+        assert_eq!(scaled.len(), 320 * 288 * 4);
+        let red = [0xFF, 0x00, 0x00, 0xFF];
+        assert_eq!(scaled[0..4], red);
+        assert_eq!(scaled[4..8], red);
+        assert_eq!(scaled[8..12], [0xFF; 4]);
+        assert_eq!(scaled[320 * 4..320 * 4 + 4], red);
+        assert_eq!(scaled[2 * 320 * 4..2 * 320 * 4 + 4], [0xFF; 4]);
+
+        assert_eq!(scale_frame(&test_frame(), 0).0, test_frame());
+    }
+
+    #[test]
+    fn test_timestamped_name() {
+        let time = UNIX_EPOCH + Duration::from_millis(951_868_799_042);
+        assert_eq!(
+            timestamped_name("screenshot", "png", time),
+            "screenshot-20000229-235959-042.png"
+        );
+        assert_eq!(
+            timestamped_name("recording", "avi", UNIX_EPOCH),
+            "recording-19700101-000000-000.avi"
+        );
+    }
+
+    #[test]
+    fn test_write_png() {
+        let path = std::env::temp_dir().join(format!("gameboy-test-{}.png", std::process::id()));
+        write_png(&path, &test_frame(), 1).unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            (info.width, info.height),
+            (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+        );
+        assert_eq!(pixels, test_frame());
+    }
+}
@@ -40,6 +40,26 @@ impl SdlWrapper {
         canvas
     }
 
+    pub fn get_resizable_window_canvas(
+        &self,
+        title: &str,
+        width: u32,
+        height: u32,
+    ) -> sdl2::render::Canvas<sdl2::video::Window> {
+        let window = self
+            .video
+            .window(title, width, height)
+            .position_centered()
+            .resizable()
+            .allow_highdpi()
+            .build()
+            .unwrap();
+        let mut canvas = window.into_canvas().build().unwrap();
+        canvas.clear();
+        canvas.present();
+        canvas
+    }
+
     pub fn get_events(&mut self) -> Vec<Event> {
         self.event_pump.poll_iter().collect()
     }
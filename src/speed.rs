@@ -0,0 +1,128 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::FRAME_LENGTH;
+use crate::input::{Button, Input, KeyEdges};
+
+const CPU_HZ: u64 = 4_194_304;
+// Real time frames that can be missed before pacing gives up catching up, e.g. after
+// sitting at the debugger prompt
+const MAX_LAG_FRAMES: u32 = 4;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SpeedConfig {
+    // Emulated frames per real frame while fast forwarding, 0 runs as fast as possible
+    pub fast_forward: f64,
+    pub slow_motion: f64,
+}
+
+impl Default for SpeedConfig {
+    fn default() -> Self {
+        SpeedConfig {
+            fast_forward: 4.0,
+            slow_motion: 0.25,
+        }
+    }
+}
+
+// Decides how many emulated frames run per displayed frame and paces the main loop.
+// Only the last emulated frame of each batch gets rendered.
+pub struct SpeedControl {
+    config: SpeedConfig,
+    fast_forward_held: bool,
+    fast_forward_toggled: bool,
+    slow_motion: bool,
+    paused: bool,
+    frame_advance: bool,
+    // Fractional frames carried over, so 0.25x runs a frame every fourth displayed frame
+    credit: f64,
+    next_frame: Instant,
+    keys: KeyEdges,
+}
+
+impl SpeedControl {
+    pub fn new(config: SpeedConfig) -> SpeedControl {
+        SpeedControl {
+            config,
+            fast_forward_held: false,
+            fast_forward_toggled: false,
+            slow_motion: false,
+            paused: false,
+            frame_advance: false,
+            credit: 0.0,
+            next_frame: Instant::now(),
+            keys: KeyEdges::default(),
+        }
+    }
+
+    pub fn frame_duration() -> Duration {
+        Duration::from_nanos(FRAME_LENGTH as u64 * 1_000_000_000 / CPU_HZ)
+    }
+
+    // Reads the speed keys, once per displayed frame
+    pub fn update(&mut self, input: &Input) {
+        self.fast_forward_held = input.is_down(&Button::FastForward);
+        if self.keys.pressed(input, Button::ToggleFastForward) {
+            self.fast_forward_toggled = !self.fast_forward_toggled;
+        }
+        if self.keys.pressed(input, Button::ToggleSlowMotion) {
+            self.slow_motion = !self.slow_motion;
+        }
+        if self.keys.pressed(input, Button::Pause) {
+            self.paused = !self.paused;
+            println!("{}", if self.paused { "Paused" } else { "Resumed" });
+        }
+        if self.keys.pressed(input, Button::FrameAdvance) && self.paused {
+            self.frame_advance = true;
+        }
+    }
+
+    // None when fast forward is uncapped
+    pub fn multiplier(&self) -> Option<f64> {
+        if self.fast_forward_held || self.fast_forward_toggled {
+            return (self.config.fast_forward > 0.0).then_some(self.config.fast_forward);
+        }
+        if self.slow_motion {
+            return Some(self.config.slow_motion);
+        }
+        Some(1.0)
+    }
+
+    // Asked before each emulated frame of a displayed frame, with the number already run
+    pub fn run_another(&mut self, frames_done: u32, started: Instant) -> bool {
+        if self.paused {
+            return frames_done == 0 && std::mem::take(&mut self.frame_advance);
+        }
+        match self.multiplier() {
+            None => frames_done == 0 || started.elapsed() < Self::frame_duration(),
+            Some(multiplier) => {
+                if frames_done == 0 {
+                    self.credit += multiplier;
+                }
+                if self.credit < 1.0 {
+                    return false;
+                }
+                self.credit -= 1.0;
+                true
+            }
+        }
+    }
+
+    // Sleeps until the next displayed frame is due
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if !self.paused && self.multiplier().is_none() {
+            self.next_frame = now;
+            return;
+        }
+        let frame = Self::frame_duration();
+        if self.next_frame > now {
+            std::thread::sleep(self.next_frame - now);
+        } else if now - self.next_frame > frame * MAX_LAG_FRAMES {
+            self.next_frame = now;
+        }
+        self.next_frame += frame;
+    }
+}
@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use sdl2::event::Event;
+    use sdl2::keyboard::{Keycode, Mod};
+
+    use crate::input::Input;
+    use crate::speed::{SpeedConfig, SpeedControl};
+
+    fn key(input: &mut Input, keycode: Keycode, down: bool) {
+        let event = if down {
+            Event::KeyDown {
+                timestamp: 0,
+                window_id: 0,
+                keycode: Some(keycode),
+                scancode: None,
+                keymod: Mod::NOMOD,
+                repeat: false,
+            }
+        } else {
+            Event::KeyUp {
+                timestamp: 0,
+                window_id: 0,
+                keycode: Some(keycode),
+                scancode: None,
+                keymod: Mod::NOMOD,
+                repeat: false,
+            }
+        };
+        input.consume_keys(event);
+    }
+
+    // Emulated frames run for each of `displayed` frames
+    fn frames(speed: &mut SpeedControl, input: &Input, displayed: u32) -> Vec<u32> {
+        (0..displayed)
+            .map(|_| {
+                speed.update(input);
+                let started = Instant::now();
+                let mut count = 0;
+                while speed.run_another(count, started) {
+                    count += 1;
+                }
+                count
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_normal_speed() {
+        let mut speed = SpeedControl::new(SpeedConfig::default());
+        assert_eq!(frames(&mut speed, &Input::new(), 3), vec![1, 1, 1]);
+        assert_eq!(
+            SpeedControl::frame_duration(),
+            Duration::from_nanos(16_666_650)
+        );
+    }
+
+    #[test]
+    fn test_fast_forward() {
+        let mut speed = SpeedControl::new(SpeedConfig {
+            fast_forward: 2.5,
+            ..SpeedConfig::default()
+        });
+        let mut input = Input::new();
+        key(&mut input, Keycode::Tab, true);
+        assert_eq!(frames(&mut speed, &input, 4), vec![2, 3, 2, 3]);
+        key(&mut input, Keycode::Tab, false);
+        assert_eq!(frames(&mut speed, &input, 1), vec![1]);
+
+        // The toggle survives releasing the key
+        key(&mut input, Keycode::F, true);
+        key(&mut input, Keycode::F, false);
+        speed.update(&input);
+        key(&mut input, Keycode::F, true);
+        assert_eq!(frames(&mut speed, &input, 2), vec![2, 3]);
+        key(&mut input, Keycode::F, false);
+        assert_eq!(frames(&mut speed, &input, 1), vec![2]);
+    }
+
+    #[test]
+    fn test_uncapped_runs_for_a_frame_of_real_time() {
+        let mut speed = SpeedControl::new(SpeedConfig {
+            fast_forward: 0.0,
+            ..SpeedConfig::default()
+        });
+        let mut input = Input::new();
+        key(&mut input, Keycode::Tab, true);
+        speed.update(&input);
+        assert_eq!(speed.multiplier(), None);
+        let started = Instant::now() - SpeedControl::frame_duration();
+        assert!(speed.run_another(0, started));
+        assert!(!speed.run_another(1, started));
+        assert!(speed.run_another(1, Instant::now()));
+    }
+
+    #[test]
+    fn test_slow_motion() {
+        let mut speed = SpeedControl::new(SpeedConfig::default());
+        let mut input = Input::new();
+        key(&mut input, Keycode::S, true);
+        assert_eq!(frames(&mut speed, &input, 8), vec![0, 0, 0, 1, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_pause_and_frame_advance() {
+        let mut speed = SpeedControl::new(SpeedConfig::default());
+        let mut input = Input::new();
+        key(&mut input, Keycode::P, true);
+        assert_eq!(frames(&mut speed, &input, 2), vec![0, 0]);
+        key(&mut input, Keycode::N, true);
+        assert_eq!(frames(&mut speed, &input, 3), vec![1, 0, 0]);
+        key(&mut input, Keycode::N, false);
+        speed.update(&input);
+        key(&mut input, Keycode::N, true);
+        assert_eq!(frames(&mut speed, &input, 1), vec![1]);
+
+        key(&mut input, Keycode::P, false);
+        speed.update(&input);
+        key(&mut input, Keycode::P, true);
+        assert_eq!(frames(&mut speed, &input, 2), vec![1, 1]);
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+fn region_start(addr: u16) -> u16 {
+    match addr {
+        0x0000..=0x3fff => 0x0000,
+        0x4000..=0x7fff => 0x4000,
+        0x8000..=0x9fff => 0x8000,
+        0xa000..=0xbfff => 0xa000,
+        0xc000..=0xcfff => 0xc000,
+        0xd000..=0xdfff => 0xd000,
+        0xe000..=0xfdff => 0xe000,
+        0xfe00..=0xfeff => 0xfe00,
+        0xff00..=0xff7f => 0xff00,
+        0xff80..=0xffff => 0xff80,
+    }
+}
+
+// Labels from an RGBDS .sym file, lines look like `01:4a2f Main.loop`
+#[derive(Default)]
+pub struct SymbolTable {
+    by_location: BTreeMap<(u16, u16), String>,
+    by_name: HashMap<String, (u16, u16)>,
+}
+
+impl SymbolTable {
+    pub fn parse(text: &str) -> Result<SymbolTable, String> {
+        let mut table = SymbolTable::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("line {}: invalid symbol: {line}", number + 1);
+            let (location, name) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let (bank, addr) = location.split_once(':').ok_or_else(invalid)?;
+            let bank = u16::from_str_radix(bank, 16).map_err(|_| invalid())?;
+            let addr = u16::from_str_radix(addr, 16).map_err(|_| invalid())?;
+            table.insert(bank, addr, name.trim());
+        }
+        Ok(table)
+    }
+
+    // Looks for foo.sym next to foo.gb, a missing file just means no symbols
+    pub fn load_for_rom(path_to_rom: &str) -> Result<SymbolTable, String> {
+        let path = Path::new(path_to_rom).with_extension("sym");
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                SymbolTable::parse(&text).map_err(|err| format!("{}: {err}", path.display()))
+            }
+            Err(_) => Ok(SymbolTable::default()),
+        }
+    }
+
+    pub fn insert(&mut self, bank: u16, addr: u16, name: &str) {
+        self.by_location.insert((bank, addr), name.to_string());
+        self.by_name.insert(name.to_string(), (bank, addr));
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_location.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_location.is_empty()
+    }
+
+    // Bank and address of a label
+    pub fn lookup(&self, name: &str) -> Option<(u16, u16)> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn label(&self, bank: u16, addr: u16) -> Option<&str> {
+        self.by_location.get(&(bank, addr)).map(String::as_str)
+    }
+
+    // Closest label at or before addr in the same bank and memory region, formatted as `Label+$offset`
+    pub fn describe(&self, bank: u16, addr: u16) -> Option<String> {
+        let region = region_start(addr);
+        let ((_, start), name) = self
+            .by_location
+            .range((bank, region)..=(bank, addr))
+            .next_back()?;
+        match addr - start {
+            0 => Some(name.clone()),
+            offset => Some(format!("{name}+${offset:X}")),
+        }
+    }
+}
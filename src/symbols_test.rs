@@ -0,0 +1,49 @@
+#[cfg(test)]
+mod tests {
+    use crate::symbols::SymbolTable;
+
+    const SYM_FILE: &str = "; File generated by rgblink
+00:0150 Init
+00:0160 Init.clearLoop
+01:4000 Bank1Func
+02:4000 Bank2Func
+00:c000 wCounter
+";
+
+    #[test]
+    fn test_parse_sym_file() {
+        let symbols = SymbolTable::parse(SYM_FILE).unwrap();
+        assert_eq!(symbols.len(), 5);
+        assert_eq!(symbols.lookup("Init.clearLoop"), Some((0, 0x0160)));
+        assert_eq!(symbols.lookup("Bank2Func"), Some((2, 0x4000)));
+        assert_eq!(symbols.lookup("Missing"), None);
+        assert!(SymbolTable::parse("0150 Init").is_err());
+        assert!(SymbolTable::parse("zz:0150 Init").is_err());
+    }
+
+    #[test]
+    fn test_labels_are_bank_aware() {
+        let symbols = SymbolTable::parse(SYM_FILE).unwrap();
+        assert_eq!(symbols.label(1, 0x4000), Some("Bank1Func"));
+        assert_eq!(symbols.label(2, 0x4000), Some("Bank2Func"));
+        assert_eq!(symbols.label(3, 0x4000), None);
+    }
+
+    #[test]
+    fn test_describe_nearest_symbol() {
+        let symbols = SymbolTable::parse(SYM_FILE).unwrap();
+        assert_eq!(symbols.describe(0, 0x0150), Some("Init".to_string()));
+        assert_eq!(symbols.describe(0, 0x015a), Some("Init+$A".to_string()));
+        assert_eq!(
+            symbols.describe(0, 0x0165),
+            Some("Init.clearLoop+$5".to_string())
+        );
+        assert_eq!(
+            symbols.describe(2, 0x4010),
+            Some("Bank2Func+$10".to_string())
+        );
+        assert_eq!(symbols.describe(0, 0x0100), None);
+        // Rom labels never describe ram addresses
+        assert_eq!(symbols.describe(0, 0x8000), None);
+    }
+}
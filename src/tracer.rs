@@ -0,0 +1,373 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use serde::Deserialize;
+
+use crate::cpu::{Cpu, Register16};
+use crate::disassembler;
+use crate::memory::Memory;
+use crate::symbols::SymbolTable;
+
+pub const DOCTOR_LOG: &str = "blargg_log_instr.txt";
+
+#[derive(Default, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum TraceFormat {
+    // Matches gameboy-doctor logs line for line
+    #[default]
+    Doctor,
+    // Adds instruction count, bank, symbol, disassembly and cycles
+    Rich,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TraceConfig {
+    pub path: String,
+    pub format: TraceFormat,
+    // Inclusive
+    pub pc_range: Option<(u16, u16)>,
+    pub bank: Option<u16>,
+    // Instruction counts, from inclusive and to exclusive
+    pub from_instruction: Option<u128>,
+    pub to_instruction: Option<u128>,
+    // Only keep the last N instructions in memory, compressed, and write them when
+    // execution stops. 0 keeps nothing.
+    pub ring_buffer: Option<usize>,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        TraceConfig {
+            path: DOCTOR_LOG.to_string(),
+            format: TraceFormat::Doctor,
+            pc_range: None,
+            bank: None,
+            from_instruction: None,
+            to_instruction: None,
+            ring_buffer: None,
+        }
+    }
+}
+
+// State before an instruction runs, kept as plain numbers so the ring buffer stays small
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceRecord {
+    pub operation: u128,
+    pub registers: [u16; 6],
+    pub bank: u16,
+    pub pcmem: [u8; 4],
+    pub cycles: u8,
+}
+
+impl TraceRecord {
+    pub fn capture(cpu: &Cpu, memory: &Memory) -> TraceRecord {
+        let pc = cpu.PC();
+        let registers = [
+            Register16::AF,
+            Register16::BC,
+            Register16::DE,
+            Register16::HL,
+            Register16::SP,
+            Register16::PC,
+        ]
+        .map(|register| cpu.get_register16(register));
+        TraceRecord {
+            operation: cpu.operations,
+            registers,
+            bank: memory.bank_at(pc),
+            pcmem: [0, 1, 2, 3].map(|i| memory.peek(pc.wrapping_add(i))),
+            cycles: 0,
+        }
+    }
+
+    pub fn format(&self, format: TraceFormat, symbols: &SymbolTable) -> String {
+        let [af, bc, de, hl, sp, pc] = self.registers;
+        let [a, f] = af.to_be_bytes();
+        let [b, c] = bc.to_be_bytes();
+        let [d, e] = de.to_be_bytes();
+        let [h, l] = hl.to_be_bytes();
+        let [m0, m1, m2, m3] = self.pcmem;
+        let doctor = format!(
+            "A:{a:02X} F:{f:02X} B:{b:02X} C:{c:02X} D:{d:02X} E:{e:02X} H:{h:02X} L:{l:02X} SP:{sp:04X} PC:{pc:04X} PCMEM:{m0:02X},{m1:02X},{m2:02X},{m3:02X}"
+        );
+        match format {
+            TraceFormat::Doctor => doctor,
+            TraceFormat::Rich => {
+                let instruction = disassembler::decode_labeled(
+                    |addr| self.pcmem[addr.wrapping_sub(pc) as usize & 3],
+                    pc,
+                    |addr| symbols.label(self.bank_of(addr), addr).map(str::to_string),
+                );
+                let symbol = symbols
+                    .describe(self.bank, pc)
+                    .map(|symbol| format!(" <{symbol}>"))
+                    .unwrap_or_default();
+                format!(
+                    "{:>10} {:02X}:{pc:04X}{symbol} {:<20} {:>2}t | {doctor}",
+                    self.operation, self.bank, instruction.text, self.cycles
+                )
+            }
+        }
+    }
+
+    // Jump targets in switchable rom are assumed to be in the bank the instruction runs from
+    fn bank_of(&self, addr: u16) -> u16 {
+        match addr {
+            0x4000..=0x7fff => self.bank,
+            0xd000..=0xdfff => 1,
+            _ => 0,
+        }
+    }
+}
+
+// Records per block, each block starts from scratch so whole blocks can be dropped
+const RECORDS_PER_BLOCK: usize = 256;
+
+struct TraceBlock {
+    bytes: Vec<u8>,
+    records: usize,
+    // What the next record is encoded against
+    last: TraceRecord,
+}
+
+const EMPTY_RECORD: TraceRecord = TraceRecord {
+    operation: 0,
+    registers: [0; 6],
+    bank: 0,
+    pcmem: [0; 4],
+    cycles: 0,
+};
+
+fn write_varint(bytes: &mut Vec<u8>, mut val: u128) {
+    while val >= 0x80 {
+        bytes.push(val as u8 | 0x80);
+        val >>= 7;
+    }
+    bytes.push(val as u8);
+}
+
+fn read_varint(bytes: &[u8], at: &mut usize) -> u128 {
+    let mut val = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*at];
+        *at += 1;
+        val |= ((byte & 0x7F) as u128) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return val;
+        }
+    }
+}
+
+// Each record is stored as the difference to the one before it: the instruction count
+// step, a mask of the registers and bank that changed followed by their new values, and
+// the opcode bytes and cycles. That is around 10 bytes instead of 48 for a typical
+// instruction.
+impl TraceBlock {
+    fn new() -> TraceBlock {
+        TraceBlock {
+            bytes: Vec::new(),
+            records: 0,
+            last: EMPTY_RECORD,
+        }
+    }
+
+    fn push(&mut self, record: &TraceRecord) {
+        let last = &self.last;
+        write_varint(
+            &mut self.bytes,
+            record.operation.wrapping_sub(last.operation),
+        );
+        let mut changed = 0u8;
+        for (i, (new, old)) in record.registers.iter().zip(&last.registers).enumerate() {
+            if new != old {
+                changed |= 1 << i;
+            }
+        }
+        if record.bank != last.bank {
+            changed |= 1 << 6;
+        }
+        self.bytes.push(changed);
+        for (i, register) in record.registers.iter().enumerate() {
+            if changed & (1 << i) != 0 {
+                self.bytes.extend(register.to_le_bytes());
+            }
+        }
+        if record.bank != last.bank {
+            self.bytes.extend(record.bank.to_le_bytes());
+        }
+        self.bytes.extend(record.pcmem);
+        self.bytes.push(record.cycles);
+        self.records += 1;
+        self.last = *record;
+    }
+
+    fn decode(&self) -> Vec<TraceRecord> {
+        let mut records = Vec::with_capacity(self.records);
+        let mut record = EMPTY_RECORD;
+        let mut at = 0;
+        let bytes = &self.bytes;
+        for _ in 0..self.records {
+            record.operation = record.operation.wrapping_add(read_varint(bytes, &mut at));
+            let changed = bytes[at];
+            at += 1;
+            for (i, register) in record.registers.iter_mut().enumerate() {
+                if changed & (1 << i) != 0 {
+                    *register = u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+                    at += 2;
+                }
+            }
+            if changed & (1 << 6) != 0 {
+                record.bank = u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+                at += 2;
+            }
+            record.pcmem.copy_from_slice(&bytes[at..at + 4]);
+            record.cycles = bytes[at + 4];
+            at += 5;
+            records.push(record);
+        }
+        records
+    }
+}
+
+// The last `capacity` records, delta compressed in blocks
+pub struct TraceRing {
+    capacity: usize,
+    blocks: VecDeque<TraceBlock>,
+    records: usize,
+}
+
+impl TraceRing {
+    pub fn new(capacity: usize) -> TraceRing {
+        TraceRing {
+            capacity,
+            blocks: VecDeque::new(),
+            records: 0,
+        }
+    }
+
+    pub fn push(&mut self, record: &TraceRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self
+            .blocks
+            .back()
+            .is_none_or(|block| block.records == RECORDS_PER_BLOCK)
+        {
+            self.blocks.push_back(TraceBlock::new());
+        }
+        self.blocks.back_mut().unwrap().push(record);
+        self.records += 1;
+        // Old blocks go once the rest still hold capacity records
+        while let Some(front) = self.blocks.front()
+            && self.records - front.records >= self.capacity
+        {
+            self.records -= front.records;
+            self.blocks.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.min(self.capacity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Bytes the encoded records take up
+    pub fn encoded_size(&self) -> usize {
+        self.blocks.iter().map(|block| block.bytes.len()).sum()
+    }
+
+    // Oldest first
+    pub fn records(&self) -> Vec<TraceRecord> {
+        let mut records: Vec<_> = self.blocks.iter().flat_map(TraceBlock::decode).collect();
+        records.drain(..records.len() - self.len());
+        records
+    }
+}
+
+pub struct Tracer {
+    config: TraceConfig,
+    writer: Option<BufWriter<File>>,
+    ring: TraceRing,
+    // Records came in since the last dump
+    unsaved: bool,
+}
+
+impl Tracer {
+    pub fn new(config: TraceConfig) -> std::io::Result<Tracer> {
+        // In ring buffer mode the file is only created when there is something to dump
+        let writer = match config.ring_buffer {
+            Some(_) => None,
+            None => Some(BufWriter::new(File::create(&config.path)?)),
+        };
+        Ok(Tracer {
+            ring: TraceRing::new(config.ring_buffer.unwrap_or(0)),
+            config,
+            writer,
+            unsaved: false,
+        })
+    }
+
+    pub fn wants(&self, operation: u128, pc: u16, bank: u16) -> bool {
+        let config = &self.config;
+        config
+            .pc_range
+            .is_none_or(|(start, end)| (start..=end).contains(&pc))
+            && config.bank.is_none_or(|b| b == bank || pc < 0x4000)
+            && config.from_instruction.is_none_or(|from| operation >= from)
+            && config.to_instruction.is_none_or(|to| operation < to)
+    }
+
+    pub fn record(&mut self, record: TraceRecord, symbols: &SymbolTable) {
+        match self.config.ring_buffer {
+            Some(_) => {
+                self.ring.push(&record);
+                self.unsaved = true;
+            }
+            None => {
+                let line = record.format(self.config.format, symbols);
+                if let Some(writer) = self.writer.as_mut()
+                    && let Err(err) = writeln!(writer, "{line}")
+                {
+                    println!("Trace write failed, tracing stopped: {err}");
+                    self.writer = None;
+                }
+            }
+        }
+    }
+
+    // Called when execution stops on a breakpoint or a crash. Stopping again without
+    // anything new run in between doesn't write the file again.
+    pub fn dump(&mut self, symbols: &SymbolTable) {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush().ok();
+            return;
+        }
+        if self.ring.is_empty() || !std::mem::take(&mut self.unsaved) {
+            return;
+        }
+        let result = File::create(&self.config.path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            for record in &self.ring.records() {
+                writeln!(writer, "{}", record.format(self.config.format, symbols))?;
+            }
+            writer.flush()
+        });
+        match result {
+            Ok(()) => println!(
+                "Wrote the last {} instructions, {} bytes in memory, to {}",
+                self.ring.len(),
+                self.ring.encoded_size(),
+                self.config.path
+            ),
+            Err(err) => println!("Could not write trace to {}: {err}", self.config.path),
+        }
+    }
+}
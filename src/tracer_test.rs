@@ -0,0 +1,186 @@
+#[cfg(test)]
+mod tests {
+    use crate::emulator::{Emulator, RunConfig};
+    use crate::memory::MemoryType;
+    use crate::symbols::SymbolTable;
+    use crate::tracer::{TraceConfig, TraceFormat, TraceRecord, TraceRing, Tracer};
+
+    fn record(operation: u128, pc: u16) -> TraceRecord {
+        TraceRecord {
+            operation,
+            registers: [0x01b0, 0x0013, 0x00d8, 0x014d, 0xfffe, pc],
+            bank: 1,
+            // JP $4010
+            pcmem: [0xc3, 0x10, 0x40, 0x00],
+            cycles: 16,
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(name)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn test_doctor_format() {
+        let line = record(0, 0x0100).format(TraceFormat::Doctor, &SymbolTable::default());
+        assert_eq!(
+            line,
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:C3,10,40,00"
+        );
+    }
+
+    #[test]
+    fn test_rich_format() {
+        let mut symbols = SymbolTable::default();
+        symbols.insert(1, 0x4000, "Main");
+        symbols.insert(1, 0x4010, "Loop");
+        let line = record(42, 0x4004).format(TraceFormat::Rich, &symbols);
+        assert!(line.contains("01:4004 <Main+$4>"), "{line}");
+        assert!(line.contains("JP Loop"), "{line}");
+        assert!(line.contains("16t"), "{line}");
+        assert!(line.ends_with("PC:4004 PCMEM:C3,10,40,00"), "{line}");
+    }
+
+    #[test]
+    fn test_filters() {
+        let tracer = Tracer::new(TraceConfig {
+            pc_range: Some((0x4000, 0x4fff)),
+            bank: Some(2),
+            from_instruction: Some(10),
+            to_instruction: Some(20),
+            ring_buffer: Some(1),
+            ..TraceConfig::default()
+        })
+        .unwrap();
+        assert!(tracer.wants(10, 0x4000, 2));
+        assert!(!tracer.wants(9, 0x4000, 2));
+        assert!(!tracer.wants(20, 0x4000, 2));
+        assert!(!tracer.wants(10, 0x5000, 2));
+        assert!(!tracer.wants(10, 0x4000, 1));
+    }
+
+    #[test]
+    fn test_ring_buffer_keeps_last_instructions() {
+        let path = temp_path("tracer_test_ring.txt");
+        let _ = std::fs::remove_file(&path);
+        let symbols = SymbolTable::default();
+        let mut tracer = Tracer::new(TraceConfig {
+            path: path.clone(),
+            ring_buffer: Some(2),
+            ..TraceConfig::default()
+        })
+        .unwrap();
+        assert!(!std::path::Path::new(&path).exists());
+        for pc in 0x100..0x105 {
+            tracer.record(record(0, pc), &symbols);
+        }
+        tracer.dump(&symbols);
+        let lines: Vec<String> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("PC:0103"));
+        assert!(lines[1].contains("PC:0104"));
+    }
+
+    #[test]
+    fn test_streams_to_file() {
+        let path = temp_path("tracer_test_stream.txt");
+        let symbols = SymbolTable::default();
+        let mut tracer = Tracer::new(TraceConfig {
+            path: path.clone(),
+            ..TraceConfig::default()
+        })
+        .unwrap();
+        tracer.record(record(0, 0x100), &symbols);
+        tracer.record(record(1, 0x103), &symbols);
+        tracer.dump(&symbols);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_ring_compresses_and_round_trips() {
+        let mut ring = TraceRing::new(1000);
+        let mut expected = Vec::new();
+        for operation in 0..1500u128 {
+            let mut record = record(operation, 0x4000 + (operation % 64) as u16 * 3);
+            record.registers[0] = (operation / 7) as u16;
+            record.bank = 1 + (operation / 600) as u16;
+            ring.push(&record);
+            expected.push(record);
+        }
+        assert_eq!(ring.len(), 1000);
+        assert_eq!(ring.records(), expected[500..]);
+        // Raw records take 48 bytes each
+        assert!(ring.encoded_size() < 1256 * 16, "{}", ring.encoded_size());
+    }
+
+    #[test]
+    fn test_ring_of_zero_keeps_nothing() {
+        let mut ring = TraceRing::new(0);
+        for operation in 0..1000 {
+            ring.push(&record(operation, 0x100));
+        }
+        assert!(ring.is_empty());
+        assert_eq!(ring.encoded_size(), 0);
+        assert!(ring.records().is_empty());
+    }
+
+    #[test]
+    fn test_dump_only_writes_new_records() {
+        let path = temp_path("tracer_test_dump_twice.txt");
+        let symbols = SymbolTable::default();
+        let mut tracer = Tracer::new(TraceConfig {
+            path: path.clone(),
+            ring_buffer: Some(4),
+            ..TraceConfig::default()
+        })
+        .unwrap();
+        tracer.record(record(0, 0x100), &symbols);
+        tracer.dump(&symbols);
+        std::fs::remove_file(&path).unwrap();
+        tracer.dump(&symbols);
+        assert!(!std::path::Path::new(&path).exists());
+        tracer.record(record(1, 0x101), &symbols);
+        tracer.dump(&symbols);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_trace_spans_an_interrupt() {
+        let rom_path = std::env::temp_dir().join("tracer_test_interrupt.gb");
+        let mut rom = vec![0; 32 * 1024];
+        // EI, NOP, NOP, with the VBlank handler a run of NOPs at $0040
+        rom[0x100..0x103].copy_from_slice(&[0xfb, 0x00, 0x00]);
+        std::fs::write(&rom_path, rom).unwrap();
+        let path = temp_path("tracer_test_interrupt.txt");
+        let mut config = RunConfig::default();
+        config.trace = Some(TraceConfig {
+            path: path.clone(),
+            ..TraceConfig::default()
+        });
+        let mut emulator = Emulator::new(config);
+        emulator
+            .load_rom(&rom_path.to_string_lossy().to_string())
+            .unwrap();
+        emulator.memory_mut().write_byte(0xFFFF, 0x01);
+        emulator.memory_mut().write_byte(0xFF0F, 0x01);
+        for _ in 0..5 {
+            emulator.step().unwrap();
+        }
+        emulator.dump_trace();
+
+        let pcs: Vec<String> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| line[line.find("PC:").unwrap() + 3..][..4].to_string())
+            .collect();
+        // One line per instruction that ran, none for the dispatch tick
+        assert_eq!(pcs, ["0100", "0101", "0040", "0041"]);
+    }
+}
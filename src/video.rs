@@ -1,14 +1,16 @@
-use sdl2::pixels::Color;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::video::{Window, WindowContext};
 
 pub const SCREEN_WIDTH: usize = 160;
 pub const SCREEN_HEIGHT: usize = 144;
 pub const PIXEL_SIZE: usize = 4;
+// Framebuffer layout, RGBA bytes in row order
+pub const BYTES_PER_PIXEL: usize = 4;
+pub const FRAMEBUFFER_PITCH: usize = SCREEN_WIDTH * BYTES_PER_PIXEL;
+pub const FRAMEBUFFER_SIZE: usize = FRAMEBUFFER_PITCH * SCREEN_HEIGHT;
 
-#[allow(dead_code)]
-pub enum ColorScheme {
-    Green,
-    BlackWhite,
-}
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum GBColor {
     White = 0,
@@ -27,19 +29,31 @@ pub fn byte_to_color(val: u8) -> GBColor {
     }
 }
 
-pub fn get_color(color: &GBColor, scheme: &ColorScheme) -> Color {
-    match scheme {
-        ColorScheme::Green => match color {
-            GBColor::White => Color::RGBA(0x9C, 0xBD, 0x0F, 0xFF),
-            GBColor::LightGray => Color::RGBA(0x8C, 0xAD, 0x0F, 0xFF),
-            GBColor::DarkGray => Color::RGBA(0x30, 0x62, 0x30, 0xFF),
-            GBColor::Black => Color::RGBA(0x0F, 0x38, 0x0F, 0xFF),
-        },
-        ColorScheme::BlackWhite => match color {
-            GBColor::White => Color::RGBA(0xFF, 0xFF, 0xFF, 0xFF),
-            GBColor::LightGray => Color::RGBA(0x8C, 0x8C, 0x8C, 0xFF),
-            GBColor::DarkGray => Color::RGBA(0x30, 0x30, 0x30, 0xFF),
-            GBColor::Black => Color::RGBA(0, 0, 0, 0xFF),
-        },
-    }
+// RGBA32 matches the framebuffer's byte order on any endianness. Filters can make the
+// image bigger than the screen.
+pub fn create_screen_texture(
+    creator: &TextureCreator<WindowContext>,
+    (width, height): (u32, u32),
+) -> Texture<'_> {
+    creator
+        .create_texture_streaming(PixelFormatEnum::RGBA32, width, height)
+        .unwrap_or_else(|err| panic!("cannot create screen texture: {err}"))
+}
+
+// One upload per frame, the texture keeps it for redraws after the window changes
+pub fn upload_frame(texture: &mut Texture, frame: &[u8]) {
+    let pitch = texture.query().width as usize * BYTES_PER_PIXEL;
+    texture
+        .update(None, frame, pitch)
+        .unwrap_or_else(|err| panic!("{err}"));
+}
+
+// SDL scales the texture into target and the rest is cleared black
+pub fn present_screen(canvas: &mut Canvas<Window>, texture: &Texture, target: Rect) {
+    canvas.set_draw_color(Color::BLACK);
+    canvas.clear();
+    canvas
+        .copy(texture, None, target)
+        .unwrap_or_else(|err| panic!("{err}"));
+    canvas.present();
 }
@@ -0,0 +1,94 @@
+use crate::palette::Rgb;
+use crate::video::BYTES_PER_PIXEL;
+
+// 3x5 pixel glyphs, enough to label registers without a font library. Lowercase
+// letters are drawn as capitals.
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+// Glyph plus the space after it
+pub const ADVANCE: usize = GLYPH_WIDTH + 1;
+
+#[rustfmt::skip]
+const GLYPHS: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    (' ', ["...", "...", "...", "...", "..."]),
+    ('0', ["###", "#.#", "#.#", "#.#", "###"]),
+    ('1', [".#.", "##.", ".#.", ".#.", "###"]),
+    ('2', ["###", "..#", "###", "#..", "###"]),
+    ('3', ["###", "..#", ".##", "..#", "###"]),
+    ('4', ["#.#", "#.#", "###", "..#", "..#"]),
+    ('5', ["###", "#..", "###", "..#", "###"]),
+    ('6', ["###", "#..", "###", "#.#", "###"]),
+    ('7', ["###", "..#", "..#", ".#.", ".#."]),
+    ('8', ["###", "#.#", "###", "#.#", "###"]),
+    ('9', ["###", "#.#", "###", "..#", "###"]),
+    ('A', [".#.", "#.#", "###", "#.#", "#.#"]),
+    ('B', ["##.", "#.#", "##.", "#.#", "##."]),
+    ('C', [".##", "#..", "#..", "#..", ".##"]),
+    ('D', ["##.", "#.#", "#.#", "#.#", "##."]),
+    ('E', ["###", "#..", "##.", "#..", "###"]),
+    ('F', ["###", "#..", "##.", "#..", "#.."]),
+    ('G', [".##", "#..", "#.#", "#.#", ".##"]),
+    ('H', ["#.#", "#.#", "###", "#.#", "#.#"]),
+    ('I', ["###", ".#.", ".#.", ".#.", "###"]),
+    ('J', ["..#", "..#", "..#", "#.#", ".#."]),
+    ('K', ["#.#", "#.#", "##.", "#.#", "#.#"]),
+    ('L', ["#..", "#..", "#..", "#..", "###"]),
+    ('M', ["#.#", "###", "###", "#.#", "#.#"]),
+    ('N', ["##.", "#.#", "#.#", "#.#", "#.#"]),
+    ('O', [".#.", "#.#", "#.#", "#.#", ".#."]),
+    ('P', ["##.", "#.#", "##.", "#..", "#.."]),
+    ('Q', [".#.", "#.#", "#.#", "##.", ".##"]),
+    ('R', ["##.", "#.#", "##.", "#.#", "#.#"]),
+    ('S', [".##", "#..", ".#.", "..#", "##."]),
+    ('T', ["###", ".#.", ".#.", ".#.", ".#."]),
+    ('U', ["#.#", "#.#", "#.#", "#.#", "###"]),
+    ('V', ["#.#", "#.#", "#.#", "#.#", ".#."]),
+    ('W', ["#.#", "#.#", "###", "###", "#.#"]),
+    ('X', ["#.#", "#.#", ".#.", "#.#", "#.#"]),
+    ('Y', ["#.#", "#.#", ".#.", ".#.", ".#."]),
+    ('Z', ["###", "..#", ".#.", "#..", "###"]),
+    ('$', [".##", "##.", ".#.", ".##", "##."]),
+    (':', ["...", ".#.", "...", ".#.", "..."]),
+    ('=', ["...", "###", "...", "###", "..."]),
+    ('.', ["...", "...", "...", "...", ".#."]),
+    (',', ["...", "...", "...", ".#.", "#.."]),
+    ('-', ["...", "...", "###", "...", "..."]),
+    ('/', ["..#", "..#", ".#.", "#..", "#.."]),
+    ('(', [".#.", "#..", "#..", "#..", ".#."]),
+    (')', [".#.", "..#", "..#", "..#", ".#."]),
+    ('+', ["...", ".#.", "###", ".#.", "..."]),
+    ('%', ["#.#", "..#", ".#.", "#..", "#.#"]),
+    ('?', ["##.", "..#", ".#.", "...", ".#."]),
+];
+
+pub fn glyph(c: char) -> Option<&'static [&'static str; GLYPH_HEIGHT]> {
+    let c = c.to_ascii_uppercase();
+    GLYPHS
+        .iter()
+        .find(|(glyph_char, _)| *glyph_char == c)
+        .map(|(_, rows)| rows)
+}
+
+// Draws text into an RGBA image width pixels wide and returns the x after it. Characters
+// without a glyph show as '?'.
+pub fn draw_text(
+    out: &mut [u8],
+    width: usize,
+    (mut x, y): (usize, usize),
+    text: &str,
+    [r, g, b]: Rgb,
+) -> usize {
+    for c in text.chars() {
+        let rows = glyph(c).or_else(|| glyph('?')).unwrap();
+        for (dy, row) in rows.iter().enumerate() {
+            for (dx, pixel) in row.bytes().enumerate() {
+                let at = ((x + dx) + (y + dy) * width) * BYTES_PER_PIXEL;
+                if pixel == b'#' && x + dx < width && at + BYTES_PER_PIXEL <= out.len() {
+                    out[at..at + BYTES_PER_PIXEL].copy_from_slice(&[r, g, b, 0xFF]);
+                }
+            }
+        }
+        x += ADVANCE;
+    }
+    x
+}
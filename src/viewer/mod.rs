@@ -0,0 +1,195 @@
+mod font;
+mod oam;
+mod oam_test;
+mod registers;
+mod registers_test;
+mod tilemap;
+mod tilemap_test;
+mod tiles;
+mod tiles_test;
+
+use sdl2::event::{Event, WindowEvent};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use serde::Deserialize;
+
+use crate::emulator::Emulator;
+use crate::sdl_wrapper::SdlWrapper;
+use crate::video::BYTES_PER_PIXEL;
+
+pub use self::oam::OamViewer;
+pub use self::registers::RegisterViewer;
+pub use self::tilemap::TilemapViewer;
+pub use self::tiles::TileViewer;
+
+// Default scale, most images are about as big as the screen
+const SCALE: usize = 3;
+
+// Viewers open at start
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ViewerConfig {
+    pub tiles: bool,
+    pub tilemaps: bool,
+    pub oam: bool,
+    pub registers: bool,
+}
+
+// A live picture of some part of the machine, drawn again every displayed frame
+pub trait Viewer {
+    // Tells the viewers apart, only one of each name is open at a time
+    fn name(&self) -> &'static str;
+    fn title(&self) -> String {
+        self.name().to_string()
+    }
+    // Image size in game boy pixels
+    fn size(&self) -> (usize, usize);
+    // Window pixels per image pixel
+    fn scale(&self) -> usize {
+        SCALE
+    }
+    // Fills an RGBA image of size()
+    fn render(&self, emulator: &Emulator, out: &mut [u8]);
+    // What is under the given image pixel, shown in the window title
+    fn describe(&self, emulator: &Emulator, x: usize, y: usize) -> Option<String>;
+    fn click(&mut self, _x: usize, _y: usize) {}
+}
+
+pub struct ViewerWindow {
+    viewer: Box<dyn Viewer>,
+    canvas: Canvas<Window>,
+    scale: usize,
+    pixels: Vec<u8>,
+    // Image pixel under the mouse
+    hover: Option<(usize, usize)>,
+}
+
+impl ViewerWindow {
+    pub fn open(sdl: &SdlWrapper, viewer: Box<dyn Viewer>) -> ViewerWindow {
+        let scale = viewer.scale();
+        let (width, height) = viewer.size();
+        let canvas = sdl.get_window_canvas(
+            &viewer.title(),
+            (width * scale) as u32,
+            (height * scale) as u32,
+        );
+        ViewerWindow {
+            pixels: vec![0; width * height * BYTES_PER_PIXEL],
+            viewer,
+            canvas,
+            scale,
+            hover: None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    // Window coordinates to an image pixel
+    fn to_image(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        let (width, height) = self.viewer.size();
+        let (x, y) = (
+            x.max(0) as usize / self.scale,
+            y.max(0) as usize / self.scale,
+        );
+        (x < width && y < height).then_some((x, y))
+    }
+
+    // Returns false when the window was closed
+    pub fn handle(&mut self, event: &Event) -> bool {
+        match *event {
+            Event::Window {
+                win_event: WindowEvent::Close,
+                ..
+            } => return false,
+            Event::Window {
+                win_event: WindowEvent::Leave,
+                ..
+            } => self.hover = None,
+            Event::MouseMotion { x, y, .. } => self.hover = self.to_image(x, y),
+            Event::MouseButtonDown { x, y, .. } => {
+                if let Some((x, y)) = self.to_image(x, y) {
+                    self.viewer.click(x, y);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn present(&mut self, emulator: &Emulator) {
+        self.viewer.render(emulator, &mut self.pixels);
+        let mut title = self.viewer.title();
+        if let Some(detail) = self
+            .hover
+            .and_then(|(x, y)| self.viewer.describe(emulator, x, y))
+        {
+            title = format!("{title} - {detail}");
+        }
+        if self.canvas.window().title() != title {
+            let _ = self.canvas.window_mut().set_title(&title);
+        }
+        // Textures borrow their creator, so a viewer makes a fresh one each frame
+        let (width, height) = self.viewer.size();
+        let creator = self.canvas.texture_creator();
+        let mut texture = creator
+            .create_texture_streaming(PixelFormatEnum::RGBA32, width as u32, height as u32)
+            .unwrap_or_else(|err| panic!("{err}"));
+        texture
+            .update(None, &self.pixels, width * BYTES_PER_PIXEL)
+            .unwrap_or_else(|err| panic!("{err}"));
+        self.canvas.clear();
+        self.canvas
+            .copy(&texture, None, None)
+            .unwrap_or_else(|err| panic!("{err}"));
+        self.canvas.present();
+    }
+}
+
+// The open viewer windows, at most one of each kind
+#[derive(Default)]
+pub struct Viewers {
+    windows: Vec<ViewerWindow>,
+}
+
+impl Viewers {
+    pub fn open(&mut self, sdl: &SdlWrapper, viewer: Box<dyn Viewer>) {
+        self.windows.push(ViewerWindow::open(sdl, viewer));
+    }
+
+    // Opens the viewer, or closes the one open with the same name
+    pub fn toggle(&mut self, sdl: &SdlWrapper, viewer: Box<dyn Viewer>) {
+        let name = viewer.name();
+        let before = self.windows.len();
+        self.windows.retain(|window| window.viewer.name() != name);
+        if self.windows.len() == before {
+            self.open(sdl, viewer);
+        }
+    }
+
+    // Takes the window and mouse events meant for viewer windows, returns the rest.
+    // Keys are left to the emulator whichever window has focus.
+    pub fn handle(&mut self, event: Event) -> Option<Event> {
+        let window_id = match event {
+            Event::Window { window_id, .. }
+            | Event::MouseMotion { window_id, .. }
+            | Event::MouseButtonDown { window_id, .. } => window_id,
+            _ => return Some(event),
+        };
+        let Some(index) = self.windows.iter().position(|w| w.id() == window_id) else {
+            return Some(event);
+        };
+        if !self.windows[index].handle(&event) {
+            self.windows.remove(index);
+        }
+        None
+    }
+
+    pub fn present(&mut self, emulator: &Emulator) {
+        for window in &mut self.windows {
+            window.present(emulator);
+        }
+    }
+}
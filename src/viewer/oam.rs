@@ -0,0 +1,277 @@
+use crate::emulator::Emulator;
+use crate::memory::{Memory, OBJECTS_PER_LINE};
+use crate::palette::Rgb;
+use crate::video::{BYTES_PER_PIXEL, SCREEN_HEIGHT};
+
+use super::Viewer;
+use super::tiles::apply_register;
+
+const OBJECTS: usize = 40;
+const COLUMNS: usize = 8;
+// Room for an 8x16 object drawn at twice the size and a frame around it
+const CELL_WIDTH: usize = 24;
+const CELL_HEIGHT: usize = 40;
+const PREVIEW_SCALE: usize = 2;
+const GRID_WIDTH: usize = COLUMNS * CELL_WIDTH;
+// A column of the screen's lines right of the objects, colored by how busy they are
+const STRIP_X: usize = GRID_WIDTH + 8;
+const STRIP_WIDTH: usize = 16;
+pub const WIDTH: usize = STRIP_X + STRIP_WIDTH;
+pub const HEIGHT: usize = OBJECTS / COLUMNS * CELL_HEIGHT;
+
+const BACKGROUND: Rgb = [0x20, 0x20, 0x20];
+const CELL: Rgb = [0x50, 0x50, 0x50];
+const DRAWN: Rgb = [0x00, 0xC0, 0x00];
+const DROPPED: Rgb = [0xE0, 0x00, 0x00];
+const SELECTED_LINE: Rgb = [0xFF, 0xFF, 0x00];
+
+// One OAM entry as the game wrote it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Object {
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub flags: u8,
+}
+
+impl Object {
+    pub fn from_bytes([y, x, tile, flags]: [u8; 4]) -> Object {
+        Object { y, x, tile, flags }
+    }
+
+    fn read(memory: &Memory, index: usize) -> Object {
+        let addr = 0xFE00 + index as u16 * 4;
+        Object::from_bytes(std::array::from_fn(|i| memory.peek(addr + i as u16)))
+    }
+
+    pub fn behind_background(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+
+    pub fn y_flip(&self) -> bool {
+        self.flags & 0x40 != 0
+    }
+
+    pub fn x_flip(&self) -> bool {
+        self.flags & 0x20 != 0
+    }
+
+    pub fn obp1(&self) -> bool {
+        self.flags & 0x10 != 0
+    }
+
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "x {} y {} (screen {}, {}), tile ${:02X}, {}",
+            self.x,
+            self.y,
+            self.x as i32 - 8,
+            self.y as i32 - 16,
+            self.tile,
+            if self.obp1() { "OBP1" } else { "OBP0" }
+        );
+        for (set, flag) in [
+            (self.x_flip(), "x flip"),
+            (self.y_flip(), "y flip"),
+            (self.behind_background(), "behind background"),
+        ] {
+            if set {
+                text += ", ";
+                text += flag;
+            }
+        }
+        text
+    }
+}
+
+// How an object fares on the inspected line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineStatus {
+    Off,
+    Drawn,
+    // Covers the line but comes after the first OBJECTS_PER_LINE in OAM
+    Dropped,
+}
+
+// The line with the most objects from per line counts, the topmost of equally busy ones
+pub fn busiest_line(counts: &[usize]) -> Option<u8> {
+    (0..=u8::MAX)
+        .zip(counts)
+        .filter(|&(_, &count)| count > 0)
+        .max_by_key(|&(line, &count)| (count, std::cmp::Reverse(line)))
+        .map(|(line, _)| line)
+}
+
+pub fn line_status(on_line: &[usize], index: usize) -> LineStatus {
+    match on_line.iter().position(|&i| i == index) {
+        Some(position) if position < OBJECTS_PER_LINE => LineStatus::Drawn,
+        Some(_) => LineStatus::Dropped,
+        None => LineStatus::Off,
+    }
+}
+
+// All 40 objects with a preview each, framed green when drawn on the inspected line and
+// red when the per line limit drops them. The line is LY while it is on screen and the
+// busiest line otherwise, as LY is in VBlank whenever a frame was just shown. Clicking a
+// line in the strip on the right inspects it, clicking it again goes back.
+pub struct OamViewer {
+    line: Option<u8>,
+}
+
+impl OamViewer {
+    pub fn new() -> OamViewer {
+        OamViewer { line: None }
+    }
+
+    fn inspected_line(&self, memory: &Memory) -> Option<u8> {
+        let ly = memory.peek(0xFF44);
+        self.line.or_else(|| {
+            if (ly as usize) < SCREEN_HEIGHT {
+                return Some(ly);
+            }
+            let counts: Vec<usize> = (0..SCREEN_HEIGHT)
+                .map(|line| memory.objects_on_line(line as u8).len())
+                .collect();
+            busiest_line(&counts)
+        })
+    }
+}
+
+fn fill(out: &mut [u8], (x, y): (usize, usize), (width, height): (usize, usize), rgb: Rgb) {
+    let [r, g, b] = rgb;
+    for y in y..y + height {
+        for x in x..x + width {
+            let at = (x + y * WIDTH) * BYTES_PER_PIXEL;
+            out[at..at + BYTES_PER_PIXEL].copy_from_slice(&[r, g, b, 0xFF]);
+        }
+    }
+}
+
+fn draw_object(memory: &Memory, object: &Object, (x, y): (usize, usize), out: &mut [u8]) {
+    let tall = memory.peek(0xFF40) & 0x04 != 0;
+    let height = if tall { 16 } else { 8 };
+    let palette = memory.palette();
+    let colors = if object.obp1() {
+        apply_register(memory.peek(0xFF49), palette.obj1)
+    } else {
+        apply_register(memory.peek(0xFF48), palette.obj0)
+    };
+    let tiles = memory.tiles();
+    let first = if tall {
+        object.tile & 0xFE
+    } else {
+        object.tile
+    };
+    for row in 0..height {
+        let tile_row = if object.y_flip() {
+            height - 1 - row
+        } else {
+            row
+        };
+        let tile = &tiles[first as usize + tile_row / 8];
+        for column in 0..8 {
+            let tile_column = if object.x_flip() { 7 - column } else { column };
+            let color = tile[tile_row % 8][tile_column] as usize;
+            // Color 0 is transparent and shows the cell through
+            if color != 0 {
+                let at = (x + column * PREVIEW_SCALE, y + row * PREVIEW_SCALE);
+                fill(out, at, (PREVIEW_SCALE, PREVIEW_SCALE), colors[color]);
+            }
+        }
+    }
+}
+
+impl Viewer for OamViewer {
+    fn name(&self) -> &'static str {
+        "Objects"
+    }
+
+    fn title(&self) -> String {
+        match self.line {
+            Some(line) => format!("Objects (line {line})"),
+            None => "Objects (line LY or busiest)".to_string(),
+        }
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (WIDTH, HEIGHT)
+    }
+
+    fn scale(&self) -> usize {
+        2
+    }
+
+    fn render(&self, emulator: &Emulator, out: &mut [u8]) {
+        let memory = emulator.memory();
+        fill(out, (0, 0), (WIDTH, HEIGHT), BACKGROUND);
+        let line = self.inspected_line(memory);
+        let on_line = line.map_or(Vec::new(), |line| memory.objects_on_line(line));
+        for index in 0..OBJECTS {
+            let cell = (index % COLUMNS * CELL_WIDTH, index / COLUMNS * CELL_HEIGHT);
+            let frame = match line_status(&on_line, index) {
+                LineStatus::Off => BACKGROUND,
+                LineStatus::Drawn => DRAWN,
+                LineStatus::Dropped => DROPPED,
+            };
+            fill(out, cell, (CELL_WIDTH, CELL_HEIGHT), frame);
+            fill(
+                out,
+                (cell.0 + 2, cell.1 + 2),
+                (CELL_WIDTH - 4, CELL_HEIGHT - 4),
+                CELL,
+            );
+            let object = Object::read(memory, index);
+            draw_object(memory, &object, (cell.0 + 4, cell.1 + 4), out);
+        }
+        for screen_line in 0..SCREEN_HEIGHT {
+            let count = memory.objects_on_line(screen_line as u8).len();
+            let color = if line == Some(screen_line as u8) {
+                SELECTED_LINE
+            } else if count > OBJECTS_PER_LINE {
+                DROPPED
+            } else if count > 0 {
+                DRAWN
+            } else {
+                CELL
+            };
+            fill(out, (STRIP_X, screen_line), (STRIP_WIDTH, 1), color);
+        }
+    }
+
+    fn describe(&self, emulator: &Emulator, x: usize, y: usize) -> Option<String> {
+        let memory = emulator.memory();
+        if x >= STRIP_X {
+            let count = (y < SCREEN_HEIGHT).then(|| memory.objects_on_line(y as u8).len())?;
+            let dropped = count.saturating_sub(OBJECTS_PER_LINE);
+            return Some(format!("line {y}: {count} objects, {dropped} dropped"));
+        }
+        if x >= GRID_WIDTH {
+            return None;
+        }
+        let index = x / CELL_WIDTH + y / CELL_HEIGHT * COLUMNS;
+        let object = Object::read(memory, index);
+        let mut text = format!(
+            "object {index} at ${:04X}: {}",
+            0xFE00 + index * 4,
+            object.describe()
+        );
+        if let Some(line) = self.inspected_line(memory) {
+            match line_status(&memory.objects_on_line(line), index) {
+                LineStatus::Off => {}
+                LineStatus::Drawn => text += &format!(", drawn on line {line}"),
+                LineStatus::Dropped => {
+                    text +=
+                        &format!(", dropped on line {line} by the {OBJECTS_PER_LINE} object limit")
+                }
+            }
+        }
+        Some(text)
+    }
+
+    fn click(&mut self, x: usize, y: usize) {
+        if x >= STRIP_X && y < SCREEN_HEIGHT {
+            let line = y as u8;
+            self.line = (self.line != Some(line)).then_some(line);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+#[cfg(test)]
+mod tests {
+    use crate::viewer::oam::{LineStatus, Object, busiest_line, line_status};
+
+    #[test]
+    fn test_object_describe() {
+        let object = Object::from_bytes([16, 8, 0x2A, 0x00]);
+        assert_eq!(object.describe(), "x 8 y 16 (screen 0, 0), tile $2A, OBP0");
+        let object = Object::from_bytes([0, 0, 0x01, 0xF0]);
+        assert!(object.obp1() && object.x_flip() && object.y_flip());
+        assert_eq!(
+            object.describe(),
+            "x 0 y 0 (screen -8, -16), tile $01, OBP1, x flip, y flip, behind background"
+        );
+    }
+
+    #[test]
+    fn test_line_status() {
+        let on_line: Vec<usize> = (0..12).map(|i| i * 3).collect();
+        assert_eq!(line_status(&on_line, 0), LineStatus::Drawn);
+        assert_eq!(line_status(&on_line, 27), LineStatus::Drawn);
+        assert_eq!(line_status(&on_line, 30), LineStatus::Dropped);
+        assert_eq!(line_status(&on_line, 33), LineStatus::Dropped);
+        assert_eq!(line_status(&on_line, 1), LineStatus::Off);
+    }
+
+    #[test]
+    fn test_busiest_line() {
+        let mut counts = vec![0; 144];
+        assert_eq!(busiest_line(&counts), None);
+        counts[20] = 3;
+        counts[90] = 12;
+        counts[100] = 12;
+        assert_eq!(busiest_line(&counts), Some(90));
+        counts[143] = 13;
+        assert_eq!(busiest_line(&counts), Some(143));
+    }
+}
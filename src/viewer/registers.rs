@@ -0,0 +1,275 @@
+use crate::emulator::Emulator;
+use crate::memory::Memory;
+use crate::palette::Rgb;
+use crate::video::BYTES_PER_PIXEL;
+
+use super::Viewer;
+use super::font::{ADVANCE, GLYPH_HEIGHT, draw_text};
+use super::tiles::apply_register;
+
+const COLUMNS: usize = 92;
+const ROWS: usize = 18;
+const LINE_HEIGHT: usize = GLYPH_HEIGHT + 2;
+const MARGIN: usize = 2;
+pub const WIDTH: usize = COLUMNS * ADVANCE + MARGIN * 2;
+pub const HEIGHT: usize = ROWS * LINE_HEIGHT + MARGIN * 2;
+
+const BACKGROUND: Rgb = [0x10, 0x10, 0x18];
+const TEXT: Rgb = [0xE0, 0xE0, 0xE0];
+const HEADING: Rgb = [0x80, 0xC0, 0xFF];
+// One swatch per shade, as wide as a glyph plus spacing
+const SWATCH: usize = ADVANCE + 1;
+
+// Sound registers by channel, FF15, FF1F and FF27-FF2F don't exist
+const SOUND: [(&str, &[(&str, u16)]); 5] = [
+    (
+        "ch1",
+        &[
+            ("nr10", 0xFF10),
+            ("nr11", 0xFF11),
+            ("nr12", 0xFF12),
+            ("nr13", 0xFF13),
+            ("nr14", 0xFF14),
+        ],
+    ),
+    (
+        "ch2",
+        &[
+            ("nr21", 0xFF16),
+            ("nr22", 0xFF17),
+            ("nr23", 0xFF18),
+            ("nr24", 0xFF19),
+        ],
+    ),
+    (
+        "ch3",
+        &[
+            ("nr30", 0xFF1A),
+            ("nr31", 0xFF1B),
+            ("nr32", 0xFF1C),
+            ("nr33", 0xFF1D),
+            ("nr34", 0xFF1E),
+        ],
+    ),
+    (
+        "ch4",
+        &[
+            ("nr41", 0xFF20),
+            ("nr42", 0xFF21),
+            ("nr43", 0xFF22),
+            ("nr44", 0xFF23),
+        ],
+    ),
+    (
+        "ctrl",
+        &[("nr50", 0xFF24), ("nr51", 0xFF25), ("nr52", 0xFF26)],
+    ),
+];
+
+fn on_off(set: bool) -> &'static str {
+    if set { "on" } else { "off" }
+}
+
+fn map_addr(high: bool) -> &'static str {
+    if high { "$9C00" } else { "$9800" }
+}
+
+pub fn describe_lcdc(lcdc: u8) -> String {
+    let bit = |n: u8| lcdc & (1 << n) != 0;
+    format!(
+        "lcd {}, bg {} map {}, tiles {}, window {} map {}, objects {} {}",
+        on_off(bit(7)),
+        on_off(bit(0)),
+        map_addr(bit(3)),
+        if bit(4) { "$8000" } else { "$8800" },
+        on_off(bit(5)),
+        map_addr(bit(6)),
+        on_off(bit(1)),
+        if bit(2) { "8x16" } else { "8x8" }
+    )
+}
+
+pub fn describe_stat(stat: u8) -> String {
+    let mode = match stat & 3 {
+        0 => "hblank",
+        1 => "vblank",
+        2 => "oam scan",
+        _ => "drawing",
+    };
+    let sources: Vec<_> = [(3, "hblank"), (4, "vblank"), (5, "oam"), (6, "lyc")]
+        .into_iter()
+        .filter(|(bit, _)| stat & (1 << bit) != 0)
+        .map(|(_, name)| name)
+        .collect();
+    format!(
+        "mode {} {mode}, lyc=ly {}, interrupts on {}",
+        stat & 3,
+        if stat & 4 != 0 { "yes" } else { "no" },
+        if sources.is_empty() {
+            "none".to_string()
+        } else {
+            sources.join(" ")
+        }
+    )
+}
+
+// Names of the set IE or IF bits
+pub fn describe_interrupts(bits: u8) -> String {
+    let names: Vec<_> = ["vblank", "stat", "timer", "serial", "joypad"]
+        .into_iter()
+        .enumerate()
+        .filter(|(bit, _)| bits & (1 << bit) != 0)
+        .map(|(_, name)| name)
+        .collect();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(" ")
+    }
+}
+
+pub fn describe_tac(tac: u8) -> String {
+    let hz = match tac & 3 {
+        0 => 4096,
+        1 => 262_144,
+        2 => 65_536,
+        _ => 16_384,
+    };
+    format!("{}, {hz} hz", on_off(tac & 4 != 0))
+}
+
+// A piece of a panel line
+enum Span {
+    Text(String),
+    Heading(String),
+    Swatches([Rgb; 4]),
+}
+
+fn hex(name: &str, memory: &Memory, addr: u16) -> String {
+    format!("{name} ${:02X}", memory.peek(addr))
+}
+
+fn lines(emulator: &Emulator) -> Vec<Vec<Span>> {
+    use Span::{Heading, Swatches, Text};
+    let memory = emulator.memory();
+    let peek = |addr| memory.peek(addr);
+    let palette = memory.palette();
+    let dmg_palette = |name: &str, addr, colors| {
+        [
+            Text(format!("{} ", hex(name, memory, addr))),
+            Swatches(apply_register(peek(addr), colors)),
+            Text("  ".to_string()),
+        ]
+    };
+    let mut lines = vec![
+        vec![Heading("lcd".to_string())],
+        vec![Text(format!(
+            "{}: {}",
+            hex("lcdc", memory, 0xFF40),
+            describe_lcdc(peek(0xFF40))
+        ))],
+        vec![Text(format!(
+            "{}: {}",
+            hex("stat", memory, 0xFF41),
+            describe_stat(peek(0xFF41))
+        ))],
+        vec![Text(format!(
+            "ly {}  lyc {}  scx {}  scy {}  wx {}  wy {}",
+            peek(0xFF44),
+            peek(0xFF45),
+            peek(0xFF43),
+            peek(0xFF42),
+            peek(0xFF4B),
+            peek(0xFF4A)
+        ))],
+        [
+            dmg_palette("bgp", 0xFF47, palette.bg),
+            dmg_palette("obp0", 0xFF48, palette.obj0),
+            dmg_palette("obp1", 0xFF49, palette.obj1),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+        vec![Text("cgb palette ram: not emulated".to_string())],
+        vec![Heading("timer and interrupts".to_string())],
+        vec![Text(format!(
+            "{}  {}  {}  {}: {}",
+            hex("div", memory, 0xFF04),
+            hex("tima", memory, 0xFF05),
+            hex("tma", memory, 0xFF06),
+            hex("tac", memory, 0xFF07),
+            describe_tac(peek(0xFF07))
+        ))],
+        vec![Text(format!(
+            "ime {}  {}: {}  {}: {}",
+            on_off(emulator.cpu().IME),
+            hex("ie", memory, 0xFFFF),
+            describe_interrupts(peek(0xFFFF)),
+            hex("if", memory, 0xFF0F),
+            describe_interrupts(peek(0xFF0F))
+        ))],
+        vec![Heading("sound".to_string())],
+    ];
+    for (channel, registers) in SOUND {
+        let registers: Vec<_> = registers
+            .iter()
+            .map(|(name, addr)| hex(name, memory, *addr))
+            .collect();
+        lines.push(vec![Text(format!("{channel:<5}{}", registers.join("  ")))]);
+    }
+    let wave: String = (0xFF30..=0xFF3F)
+        .map(|addr| format!("{:02X}", peek(addr)))
+        .collect();
+    lines.push(vec![Text(format!("wave {wave}"))]);
+    lines
+}
+
+// Every I/O register decoded, redrawn each frame
+pub struct RegisterViewer;
+
+impl Viewer for RegisterViewer {
+    fn name(&self) -> &'static str {
+        "Registers"
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (WIDTH, HEIGHT)
+    }
+
+    fn scale(&self) -> usize {
+        2
+    }
+
+    fn render(&self, emulator: &Emulator, out: &mut [u8]) {
+        let [r, g, b] = BACKGROUND;
+        for rgba in out.chunks_exact_mut(BYTES_PER_PIXEL) {
+            rgba.copy_from_slice(&[r, g, b, 0xFF]);
+        }
+        for (row, line) in lines(emulator).into_iter().enumerate().take(ROWS) {
+            let y = MARGIN + row * LINE_HEIGHT;
+            let mut x = MARGIN;
+            for span in line {
+                x = match span {
+                    Span::Text(text) => draw_text(out, WIDTH, (x, y), &text, TEXT),
+                    Span::Heading(text) => draw_text(out, WIDTH, (x, y), &text, HEADING),
+                    Span::Swatches(colors) => {
+                        for (i, [r, g, b]) in colors.into_iter().enumerate() {
+                            for dy in 0..GLYPH_HEIGHT {
+                                for dx in 0..SWATCH - 1 {
+                                    let at = ((x + i * SWATCH + dx) + (y + dy) * WIDTH)
+                                        * BYTES_PER_PIXEL;
+                                    out[at..at + BYTES_PER_PIXEL].copy_from_slice(&[r, g, b, 0xFF]);
+                                }
+                            }
+                        }
+                        x + colors.len() * SWATCH
+                    }
+                };
+            }
+        }
+    }
+
+    fn describe(&self, _emulator: &Emulator, _x: usize, _y: usize) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use crate::video::BYTES_PER_PIXEL;
+    use crate::viewer::font::{ADVANCE, draw_text, glyph};
+    use crate::viewer::registers::{
+        describe_interrupts, describe_lcdc, describe_stat, describe_tac,
+    };
+
+    #[test]
+    fn test_describe_lcdc() {
+        assert_eq!(
+            describe_lcdc(0x91),
+            "lcd on, bg on map $9800, tiles $8000, window off map $9800, objects off 8x8"
+        );
+        assert_eq!(
+            describe_lcdc(0x6E),
+            "lcd off, bg off map $9C00, tiles $8800, window on map $9C00, objects on 8x16"
+        );
+    }
+
+    #[test]
+    fn test_describe_stat() {
+        assert_eq!(
+            describe_stat(0x85),
+            "mode 1 vblank, lyc=ly yes, interrupts on none"
+        );
+        assert_eq!(
+            describe_stat(0x4B),
+            "mode 3 drawing, lyc=ly no, interrupts on hblank lyc"
+        );
+    }
+
+    #[test]
+    fn test_describe_interrupts_and_timer() {
+        assert_eq!(describe_interrupts(0xE0), "none");
+        assert_eq!(describe_interrupts(0x15), "vblank timer joypad");
+        assert_eq!(describe_tac(0xF8), "off, 4096 hz");
+        assert_eq!(describe_tac(0x05), "on, 262144 hz");
+    }
+
+    #[test]
+    fn test_draw_text() {
+        assert_eq!(glyph('a'), glyph('A'));
+        assert!(glyph('~').is_none());
+        let width = 16;
+        let mut out = vec![0; width * 5 * BYTES_PER_PIXEL];
+        let end = draw_text(&mut out, width, (0, 0), "1~", [0xFF; 3]);
+        assert_eq!(end, 2 * ADVANCE);
+        let lit = |x: usize, y: usize| out[(x + y * width) * BYTES_PER_PIXEL + 3] != 0;
+        // The middle column of '1' and the '?' drawn for '~'
+        assert!((0..5).all(|y| lit(1, y)));
+        assert!(!lit(0, 0) && lit(0, 1));
+        assert!(lit(ADVANCE, 0) && lit(ADVANCE + 1, 0) && !lit(ADVANCE + 2, 0));
+    }
+}
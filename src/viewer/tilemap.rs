@@ -0,0 +1,138 @@
+use crate::emulator::Emulator;
+use crate::memory::Memory;
+use crate::palette::Rgb;
+use crate::video::{BYTES_PER_PIXEL, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use super::Viewer;
+use super::tiles::{apply_register, draw_tile};
+
+const MAP_SIZE: usize = 256;
+const GAP: usize = 8;
+pub const WIDTH: usize = MAP_SIZE * 2 + GAP;
+pub const HEIGHT: usize = MAP_SIZE;
+const MAPS: [u16; 2] = [0x9800, 0x9C00];
+
+const GAP_COLOR: Rgb = [0x20, 0x20, 0x20];
+const VIEWPORT_COLOR: Rgb = [0xFF, 0x00, 0x00];
+const WINDOW_COLOR: Rgb = [0x00, 0x80, 0xFF];
+
+// Both 32x32 maps side by side, the part the screen shows of the background outlined in
+// red and of the window in blue. There is no CGB VRAM bank 1, so no tile attributes.
+pub struct TilemapViewer;
+
+// Index into the 384 tiles for a map entry, LCDC bit 4 picks $8000 or signed $8800
+// addressing
+pub fn tile_for(index: u8, unsigned: bool) -> usize {
+    if unsigned || index >= 128 {
+        index as usize
+    } else {
+        256 + index as usize
+    }
+}
+
+// The edge of a width x height rectangle at x, y on a map, wrapping around like the
+// background does
+pub fn outline(x: u8, y: u8, width: usize, height: usize) -> Vec<(usize, usize)> {
+    let mut points = Vec::new();
+    if width == 0 || height == 0 {
+        return points;
+    }
+    let at = |dx: usize, dy: usize| ((x as usize + dx) % MAP_SIZE, (y as usize + dy) % MAP_SIZE);
+    for dx in 0..width {
+        points.push(at(dx, 0));
+        points.push(at(dx, height - 1));
+    }
+    for dy in 0..height {
+        points.push(at(0, dy));
+        points.push(at(width - 1, dy));
+    }
+    points
+}
+
+fn draw_outline(out: &mut [u8], map: usize, points: Vec<(usize, usize)>, [r, g, b]: Rgb) {
+    for (x, y) in points {
+        let at = ((map * (MAP_SIZE + GAP) + x) + y * WIDTH) * BYTES_PER_PIXEL;
+        out[at..at + BYTES_PER_PIXEL].copy_from_slice(&[r, g, b, 0xFF]);
+    }
+}
+
+fn lcdc(memory: &Memory, bit: u8) -> bool {
+    memory.peek(0xFF40) & (1 << bit) != 0
+}
+
+impl Viewer for TilemapViewer {
+    fn name(&self) -> &'static str {
+        "Tilemaps"
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (WIDTH, HEIGHT)
+    }
+
+    fn scale(&self) -> usize {
+        2
+    }
+
+    fn render(&self, emulator: &Emulator, out: &mut [u8]) {
+        let memory = emulator.memory();
+        for rgba in out.chunks_exact_mut(BYTES_PER_PIXEL) {
+            rgba.copy_from_slice(&[GAP_COLOR[0], GAP_COLOR[1], GAP_COLOR[2], 0xFF]);
+        }
+        let colors = apply_register(memory.peek(0xFF47), memory.palette().bg);
+        let tiles = memory.tiles();
+        let unsigned = lcdc(memory, 4);
+        for (map, base) in MAPS.into_iter().enumerate() {
+            for i in 0..32 * 32 {
+                let tile = tile_for(memory.peek(base + i as u16), unsigned);
+                let position = (map * (MAP_SIZE + GAP) + i % 32 * 8, i / 32 * 8);
+                draw_tile(&tiles[tile], colors, out, WIDTH, position);
+            }
+        }
+
+        let (scy, scx) = (memory.peek(0xFF42), memory.peek(0xFF43));
+        let viewport = outline(scx, scy, SCREEN_WIDTH, SCREEN_HEIGHT);
+        draw_outline(out, lcdc(memory, 3) as usize, viewport, VIEWPORT_COLOR);
+        if lcdc(memory, 5) {
+            // The window's top left corner sits at WX - 7, WY and it always starts at
+            // the top left of its map. The gpu skips it for WX below 7.
+            let (wy, wx) = (memory.peek(0xFF4A) as usize, memory.peek(0xFF4B) as usize);
+            let width = if wx < 7 {
+                0
+            } else {
+                (SCREEN_WIDTH + 7).saturating_sub(wx)
+            };
+            let height = SCREEN_HEIGHT.saturating_sub(wy);
+            let window = outline(0, 0, width, height);
+            draw_outline(out, lcdc(memory, 6) as usize, window, WINDOW_COLOR);
+        }
+    }
+
+    fn describe(&self, emulator: &Emulator, x: usize, y: usize) -> Option<String> {
+        let map = x / (MAP_SIZE + GAP);
+        let x = x % (MAP_SIZE + GAP);
+        if x >= MAP_SIZE {
+            return None;
+        }
+        let memory = emulator.memory();
+        let (column, row) = (x / 8, y / 8);
+        let addr = MAPS[map] + (column + row * 32) as u16;
+        let index = memory.peek(addr);
+        let tile = tile_for(index, lcdc(memory, 4));
+        let mut uses = Vec::new();
+        if lcdc(memory, 3) as usize == map {
+            uses.push("background");
+        }
+        if lcdc(memory, 5) && lcdc(memory, 6) as usize == map {
+            uses.push("window");
+        }
+        let uses = if uses.is_empty() {
+            "unused".to_string()
+        } else {
+            uses.join(" and ")
+        };
+        Some(format!(
+            "${addr:04X} ({column}, {row}) {uses}: index ${index:02X}, tile {tile} at ${:04X}",
+            0x8000 + tile * 16
+        ))
+    }
+}
@@ -0,0 +1,26 @@
+#[cfg(test)]
+mod tests {
+    use crate::viewer::tilemap::{outline, tile_for};
+
+    #[test]
+    fn test_tile_for() {
+        assert_eq!(tile_for(0x00, true), 0);
+        assert_eq!(tile_for(0xFF, true), 255);
+        assert_eq!(tile_for(0x00, false), 256);
+        assert_eq!(tile_for(0x7F, false), 383);
+        assert_eq!(tile_for(0x80, false), 128);
+    }
+
+    #[test]
+    fn test_outline_wraps() {
+        let points = outline(250, 200, 160, 144);
+        assert!(points.contains(&(250, 200)));
+        // Right edge at 250 + 159 and bottom edge at 200 + 143, both past the map
+        assert!(points.contains(&(153, 200)));
+        assert!(points.contains(&(250, 87)));
+        assert!(points.contains(&(153, 87)));
+        assert!(points.iter().all(|&(x, y)| x < 256 && y < 256));
+        assert!(!points.contains(&(0, 0)));
+        assert!(outline(0, 0, 0, 144).is_empty());
+    }
+}
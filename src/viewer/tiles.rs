@@ -0,0 +1,122 @@
+use crate::emulator::Emulator;
+use crate::palette::Rgb;
+use crate::video::{BYTES_PER_PIXEL, GBColor};
+
+use super::Viewer;
+
+const TILES: usize = 384;
+const TILES_PER_ROW: usize = 16;
+pub const WIDTH: usize = TILES_PER_ROW * 8;
+pub const HEIGHT: usize = TILES / TILES_PER_ROW * 8;
+
+// Which colors the tiles are shown in, a click cycles through them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TileColors {
+    // Color numbers as the plain shades, ignoring the palette registers
+    Shades,
+    Bgp,
+    Obp0,
+    Obp1,
+}
+
+impl TileColors {
+    fn next(self) -> TileColors {
+        match self {
+            TileColors::Shades => TileColors::Bgp,
+            TileColors::Bgp => TileColors::Obp0,
+            TileColors::Obp0 => TileColors::Obp1,
+            TileColors::Obp1 => TileColors::Shades,
+        }
+    }
+}
+
+// All of tile data at $8000-$97FF. There is no CGB VRAM banking yet, so that is bank 0.
+pub struct TileViewer {
+    colors: TileColors,
+}
+
+impl TileViewer {
+    pub fn new() -> TileViewer {
+        TileViewer {
+            colors: TileColors::Shades,
+        }
+    }
+}
+
+// Color number n shows as colors[shade n of the register], the way BGP and OBP work
+pub fn apply_register(register: u8, colors: [Rgb; 4]) -> [Rgb; 4] {
+    std::array::from_fn(|n| colors[(register >> (n * 2) & 3) as usize])
+}
+
+pub fn draw_tiles(tiles: &[[[GBColor; 8]; 8]], colors: [Rgb; 4], out: &mut [u8]) {
+    for (index, tile) in tiles.iter().enumerate() {
+        let (x, y) = (index % TILES_PER_ROW * 8, index / TILES_PER_ROW * 8);
+        draw_tile(tile, colors, out, WIDTH, (x, y));
+    }
+}
+
+// Draws a tile with its top left corner at x, y of an RGBA image width pixels wide
+pub fn draw_tile(
+    tile: &[[GBColor; 8]; 8],
+    colors: [Rgb; 4],
+    out: &mut [u8],
+    width: usize,
+    (x, y): (usize, usize),
+) {
+    for (row_y, row) in tile.iter().enumerate() {
+        for (row_x, color) in row.iter().enumerate() {
+            let at = ((x + row_x) + (y + row_y) * width) * BYTES_PER_PIXEL;
+            let [r, g, b] = colors[*color as usize];
+            out[at..at + BYTES_PER_PIXEL].copy_from_slice(&[r, g, b, 0xFF]);
+        }
+    }
+}
+
+// Tiles 0-127 are only reachable with LCDC bit 4 set and 256-383 only with it clear
+pub fn describe_tile(index: usize) -> String {
+    let addr = 0x8000 + index * 16;
+    let mode = match index {
+        0..=127 => " with $8000 addressing",
+        256.. => " with $8800 addressing",
+        _ => "",
+    };
+    format!(
+        "tile {index} at ${addr:04X}, index ${:02X}{mode}",
+        index % 256
+    )
+}
+
+impl Viewer for TileViewer {
+    fn name(&self) -> &'static str {
+        "Tiles"
+    }
+
+    fn title(&self) -> String {
+        format!("Tiles ({:?})", self.colors)
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (WIDTH, HEIGHT)
+    }
+
+    fn render(&self, emulator: &Emulator, out: &mut [u8]) {
+        let memory = emulator.memory();
+        let palette = memory.palette();
+        let colors = match self.colors {
+            TileColors::Shades => palette.bg,
+            TileColors::Bgp => apply_register(memory.peek(0xFF47), palette.bg),
+            TileColors::Obp0 => apply_register(memory.peek(0xFF48), palette.obj0),
+            TileColors::Obp1 => apply_register(memory.peek(0xFF49), palette.obj1),
+        };
+        draw_tiles(memory.tiles(), colors, out);
+    }
+
+    fn describe(&self, _emulator: &Emulator, x: usize, y: usize) -> Option<String> {
+        let index = x / 8 + y / 8 * TILES_PER_ROW;
+        (index < TILES).then(|| describe_tile(index))
+    }
+
+    fn click(&mut self, _x: usize, _y: usize) {
+        self.colors = self.colors.next();
+    }
+}
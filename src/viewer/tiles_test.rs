@@ -0,0 +1,47 @@
+#[cfg(test)]
+mod tests {
+    use crate::video::{BYTES_PER_PIXEL, GBColor};
+    use crate::viewer::tiles::{HEIGHT, WIDTH, apply_register, describe_tile, draw_tiles};
+
+    const COLORS: [[u8; 3]; 4] = [[0xFF; 3], [0xAA; 3], [0x55; 3], [0x00; 3]];
+
+    #[test]
+    fn test_apply_register() {
+        assert_eq!(apply_register(0xE4, COLORS), COLORS);
+        assert_eq!(
+            apply_register(0x1B, COLORS),
+            [COLORS[3], COLORS[2], COLORS[1], COLORS[0]]
+        );
+        assert_eq!(apply_register(0xFF, COLORS), [COLORS[3]; 4]);
+    }
+
+    #[test]
+    fn test_draw_tiles_layout() {
+        let mut tiles = [[[GBColor::White; 8]; 8]; 384];
+        // Second tile of the second row, bottom right pixel
+        tiles[17][7][7] = GBColor::Black;
+        tiles[383][0][0] = GBColor::DarkGray;
+        let mut out = vec![0; WIDTH * HEIGHT * BYTES_PER_PIXEL];
+        draw_tiles(&tiles, COLORS, &mut out);
+        let pixel = |x: usize, y: usize| {
+            let at = (x + y * WIDTH) * BYTES_PER_PIXEL;
+            out[at..at + BYTES_PER_PIXEL].to_vec()
+        };
+        assert_eq!(pixel(15, 15), [0x00, 0x00, 0x00, 0xFF]);
+        assert_eq!(pixel(16, 15), [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(pixel(WIDTH - 8, HEIGHT - 8), [0x55, 0x55, 0x55, 0xFF]);
+    }
+
+    #[test]
+    fn test_describe_tile() {
+        assert_eq!(
+            describe_tile(1),
+            "tile 1 at $8010, index $01 with $8000 addressing"
+        );
+        assert_eq!(describe_tile(128), "tile 128 at $8800, index $80");
+        assert_eq!(
+            describe_tile(383),
+            "tile 383 at $97F0, index $7F with $8800 addressing"
+        );
+    }
+}